@@ -0,0 +1,389 @@
+// src/commands.rs
+// What the Tauri commands do, apart from Tauri.
+//
+// `AppState` is the managed state: the platform backend and the inventory
+// cache. The commands in lib.rs unpack their arguments and call the method
+// of the same name here, which checks capabilities and system disk
+// protection, calls the backend, logs the outcome and turns failures into
+// `DiskError`s. Tests run these methods against a mock backend.
+use crate::disk_operations::backend::DiskBackend;
+use crate::disk_operations::protection;
+use crate::inventory::InventoryCache;
+use crate::logger;
+use crate::structs::{
+    BlockingProcess, BusyReport, DiskCapabilities, DiskError, DiskInfo, ErrorCode, ErrorDetails,
+    MountOptions, MountedPartition, OfflineReport, ProtectedOperation, RescanReport, StepReport,
+    StepStatus, SystemInfo, UnmountMode,
+};
+use std::fmt::Write as _;
+
+pub struct AppState {
+    pub backend: Box<dyn DiskBackend>,
+    pub inventory: InventoryCache,
+}
+
+impl AppState {
+    pub fn new(backend: Box<dyn DiskBackend>) -> Self {
+        Self {
+            backend,
+            inventory: InventoryCache::default(),
+        }
+    }
+
+    /// The disk inventory, from the cache unless `refresh` is set
+    ///
+    /// # Errors
+    ///
+    /// Fails when the backend cannot enumerate the disks
+    pub fn enumerate_disks(&self, refresh: bool) -> Result<Vec<DiskInfo>, DiskError> {
+        if refresh {
+            self.inventory.invalidate();
+        }
+        Ok(self.inventory.disks(|| self.backend.enumerate_disks())?)
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the disks or the system facts cannot be read
+    pub fn system_info(&self) -> Result<SystemInfo, DiskError> {
+        let disks = self.enumerate_disks(false)?;
+        Ok(self.backend.system_info(&disks)?)
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the disk is unknown, cannot be brought online on this
+    /// platform, or the backend fails
+    pub fn set_disk_online(&self, disk_id: &str) -> Result<(), DiskError> {
+        let _invalidate = self.inventory.invalidate_on_drop();
+        let disks = self.backend.enumerate_disks()?;
+        require_capability(
+            disks.iter().find(|d| d.id == disk_id),
+            |c| c.can_online,
+            "brought online",
+        )?;
+        self.backend
+            .set_disk_online(disk_id)
+            .map_err(|e| DiskError::from(e).on_disk(disk_id))?;
+        logger::log_activity(&format!("Set Disk {disk_id} Online"));
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the disk cannot be taken offline on this platform, is
+    /// protected and `override_protection` is unset, or the sequence stopped
+    /// part way (`Incomplete`, with the report in the details)
+    pub fn set_disk_offline(
+        &self,
+        disk_id: &str,
+        override_protection: bool,
+    ) -> Result<OfflineReport, DiskError> {
+        let _invalidate = self.inventory.invalidate_on_drop();
+        let disks = self.backend.enumerate_disks()?;
+        let disk = disks.iter().find(|d| d.id == disk_id);
+        require_capability(disk, |c| c.can_offline, "taken offline")?;
+        guard_protected(disk, ProtectedOperation::Offline, override_protection)?;
+        let report = self
+            .backend
+            .set_disk_offline(disk_id)
+            .map_err(|e| DiskError::from(e).on_disk(disk_id))?;
+        if !report.completed {
+            let message = format!(
+                "Failed to set Disk {disk_id} Offline: {}",
+                report.failure().unwrap_or("unknown error")
+            );
+            logger::log_activity(&message);
+            return Err(DiskError::new(ErrorCode::Incomplete, message)
+                .on_disk(disk_id)
+                .with_details(ErrorDetails::Offline { report }));
+        }
+        logger::log_activity(&format!("Set Disk {disk_id} Offline"));
+        Ok(report)
+    }
+
+    /// # Errors
+    ///
+    /// Fails like `set_disk_offline`, with the processes keeping the disk
+    /// busy named in the message
+    pub fn eject_disk(
+        &self,
+        disk_id: &str,
+        override_protection: bool,
+    ) -> Result<OfflineReport, DiskError> {
+        let _invalidate = self.inventory.invalidate_on_drop();
+        let disks = self.backend.enumerate_disks()?;
+        let disk = disks.iter().find(|d| d.id == disk_id);
+        require_capability(disk, |c| c.can_eject, "ejected")?;
+        guard_protected(disk, ProtectedOperation::Eject, override_protection)?;
+        let report = self
+            .backend
+            .eject_disk(disk_id)
+            .map_err(|e| DiskError::from(e).on_disk(disk_id))?;
+        if !report.completed {
+            let mut message = format!(
+                "Failed to eject Disk {disk_id}: {}",
+                report.failure().unwrap_or("unknown error")
+            );
+            if !report.blockers.is_empty() {
+                let blockers = describe_blockers(&report.blockers);
+                let _ = write!(message, " (in use by {blockers})");
+            }
+            logger::log_activity(&message);
+            return Err(DiskError::new(ErrorCode::Incomplete, message)
+                .on_disk(disk_id)
+                .with_details(ErrorDetails::Offline { report }));
+        }
+        logger::log_activity(&format!("Ejected Disk {disk_id}"));
+        Ok(report)
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the disks cannot be enumerated before or after the scan
+    pub fn rescan_storage(&self) -> Result<RescanReport, DiskError> {
+        let _invalidate = self.inventory.invalidate_on_drop();
+        let report = self.backend.rescan_storage()?;
+        let ids: Vec<&str> = report.reappeared.iter().map(|d| d.id.as_str()).collect();
+        if ids.is_empty() {
+            logger::log_activity("Rescanned storage, no disks reappeared");
+        } else {
+            let ids = ids.join(", ");
+            logger::log_activity(&format!("Rescanned storage, Disk {ids} reappeared"));
+        }
+        Ok(report)
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the partition is not found, the options are refused or
+    /// the mount itself fails
+    pub fn mount_partition(
+        &self,
+        disk_id: &str,
+        partition_number: u32,
+        letter: Option<char>,
+        options: &MountOptions,
+    ) -> Result<MountedPartition, DiskError> {
+        let _invalidate = self.inventory.invalidate_on_drop();
+        let mounted = self
+            .backend
+            .mount_partition(disk_id, partition_number, letter, options)
+            .map_err(|e| {
+                DiskError::from(e)
+                    .on_disk(disk_id)
+                    .on_partition(partition_number.to_string())
+            })?;
+        let at = mounted
+            .mount_point
+            .as_ref()
+            .map(|p| format!(" at {p}"))
+            .unwrap_or_default();
+        logger::log_activity(&format!(
+            "Mounted Partition {partition_number} on Disk {disk_id}{at}"
+        ));
+        Ok(mounted)
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the target is on a protected disk without
+    /// `override_protection`, or the unmount fails; the message then says
+    /// what keeps the filesystem busy
+    pub fn unmount_partition(
+        &self,
+        volume_or_letter: &str,
+        override_protection: bool,
+        mode: UnmountMode,
+    ) -> Result<Vec<StepReport>, DiskError> {
+        let _invalidate = self.inventory.invalidate_on_drop();
+        let disks = self.backend.enumerate_disks()?;
+        match protection::disk_for_target(&disks, volume_or_letter) {
+            Some(disk) => {
+                guard_protected(Some(disk), ProtectedOperation::Unmount, override_protection)?;
+            }
+            None => self.guard_target(
+                volume_or_letter,
+                ProtectedOperation::Unmount,
+                override_protection,
+            )?,
+        }
+        let results = self
+            .backend
+            .unmount_partition(volume_or_letter, mode)
+            .map_err(|e| DiskError::from(e).on_partition(volume_or_letter))?;
+        if let Some(failed) = results.iter().find(|r| r.status == StepStatus::Failed) {
+            let message = failed.message.clone().unwrap_or_default();
+            return Err(self.unmount_error(volume_or_letter, message));
+        }
+        logger::log_activity(&format!(
+            "Unmounted/Ejected Partition {volume_or_letter}{}",
+            describe_mode(mode)
+        ));
+        Ok(results)
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the disk is protected without `override_protection`, or
+    /// any of its filesystems stays mounted (`PartlyUnmounted`, with every
+    /// step in the details)
+    pub fn unmount_disk(
+        &self,
+        disk_id: &str,
+        override_protection: bool,
+        mode: UnmountMode,
+    ) -> Result<Vec<StepReport>, DiskError> {
+        let _invalidate = self.inventory.invalidate_on_drop();
+        let disks = self.backend.enumerate_disks()?;
+        guard_protected(
+            disks.iter().find(|d| d.id == disk_id),
+            ProtectedOperation::Unmount,
+            override_protection,
+        )?;
+        let results = self
+            .backend
+            .unmount_disk(disk_id, mode)
+            .map_err(|e| DiskError::from(e).on_disk(disk_id))?;
+        let failed: Vec<&str> = results
+            .iter()
+            .filter(|r| r.status == StepStatus::Failed)
+            .map(|r| r.target.as_str())
+            .collect();
+        if !failed.is_empty() {
+            let failed = failed.join(", ");
+            let message = format!("Failed to unmount {failed} on Disk {disk_id}");
+            logger::log_activity(&message);
+            return Err(DiskError::new(ErrorCode::PartlyUnmounted, message)
+                .on_disk(disk_id)
+                .with_details(ErrorDetails::Unmount { results }));
+        }
+        logger::log_activity(&format!(
+            "Unmounted all partitions of Disk {disk_id}{}",
+            describe_mode(mode)
+        ));
+        Ok(results)
+    }
+
+    /// List the processes and swap areas keeping a mount point or device busy
+    ///
+    /// # Errors
+    ///
+    /// Fails when `target` is neither a mount point nor a device node
+    pub fn diagnose_busy(&self, target: &str) -> Result<BusyReport, DiskError> {
+        self.backend
+            .diagnose_busy(target)
+            .map_err(|e| DiskError::from(e).on_partition(target))
+    }
+
+    /// Enforce system protection for a target no disk in the inventory
+    /// holds, such as / on an LVM volume, from the device it is mounted
+    /// from. A target the backend cannot resolve is refused, not let through.
+    fn guard_target(
+        &self,
+        target: &str,
+        operation: ProtectedOperation,
+        override_protection: bool,
+    ) -> Result<(), DiskError> {
+        let reasons = self
+            .backend
+            .target_protection(target)
+            .map_err(|e| DiskError::from(e).on_partition(target))?;
+        if let Err(refusal) =
+            protection::check_target(target, &reasons, operation, override_protection)
+        {
+            logger::log_activity(&refusal.message);
+            return Err(refusal.into());
+        }
+        if override_protection && !reasons.is_empty() {
+            logger::log_activity(&format!(
+                "Overriding system disk protection for {operation} of {target}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// A failed unmount, saying what holds the filesystem when that can be
+    /// found out
+    fn unmount_error(&self, target: &str, mut message: String) -> DiskError {
+        let report = self
+            .backend
+            .diagnose_busy(target)
+            .ok()
+            .filter(|report| !report.is_empty());
+        let Some(report) = report else {
+            logger::log_activity(&message);
+            return DiskError::new(ErrorCode::Failed, message).on_partition(target);
+        };
+        if !report.processes.is_empty() {
+            let blockers = describe_blockers(&report.processes);
+            let _ = write!(message, " (in use by {blockers})");
+        }
+        if !report.swap.is_empty() {
+            let swap = report.swap.join(", ");
+            let _ = write!(message, " (swap in use: {swap})");
+        }
+        logger::log_activity(&message);
+        DiskError::new(ErrorCode::Busy, message)
+            .on_partition(target)
+            .with_details(ErrorDetails::Busy { report })
+    }
+}
+
+/// Refuse an operation the backend did not list in the disk's capabilities.
+/// Unknown disks are left for the backend to report.
+fn require_capability(
+    disk: Option<&DiskInfo>,
+    supported: fn(&DiskCapabilities) -> bool,
+    action: &str,
+) -> Result<(), DiskError> {
+    match disk {
+        Some(disk) if !supported(&disk.capabilities) => Err(DiskError::new(
+            ErrorCode::Unsupported,
+            format!("Disk {} cannot be {action}", disk.id),
+        )
+        .on_disk(&disk.id)),
+        _ => Ok(()),
+    }
+}
+
+/// Enforce system disk protection before a destructive operation on `disk`.
+/// Refusals and overrides are both logged.
+fn guard_protected(
+    disk: Option<&DiskInfo>,
+    operation: ProtectedOperation,
+    override_protection: bool,
+) -> Result<(), DiskError> {
+    let Some(disk) = disk else {
+        return Ok(());
+    };
+    if let Err(refusal) = protection::check(disk, operation, override_protection) {
+        logger::log_activity(&refusal.message);
+        return Err(refusal.into());
+    }
+    if override_protection && !disk.protection.is_empty() {
+        logger::log_activity(&format!(
+            "Overriding system disk protection for {operation} of Disk {}",
+            disk.id
+        ));
+    }
+    Ok(())
+}
+
+/// "vim (4242), bash (4300)"
+fn describe_blockers(blockers: &[BlockingProcess]) -> String {
+    let blockers: Vec<String> = blockers
+        .iter()
+        .map(|b| format!("{} ({})", b.command, b.pid))
+        .collect();
+    blockers.join(", ")
+}
+
+fn describe_mode(mode: UnmountMode) -> &'static str {
+    match mode {
+        UnmountMode::Normal => "",
+        UnmountMode::Lazy => " (lazy)",
+        UnmountMode::Force => " (forced)",
+        UnmountMode::RemountReadOnly => " (remounted read-only)",
+    }
+}
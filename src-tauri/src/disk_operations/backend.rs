@@ -0,0 +1,103 @@
+// src/disk_operations/backend.rs
+// The operations a platform provides to the commands.
+//
+// Each platform module implements `DiskBackend` on top of its free
+// functions; the Tauri commands only see the trait, handed to them as
+// managed state, so they can be run against an in-memory backend in tests.
+use crate::structs::{
+    BusyReport, DiskInfo, MountOptions, MountedPartition, OfflineReport, ProtectionReason,
+    RescanReport, StepReport, SystemInfo, UnmountMode,
+};
+use anyhow::Result;
+
+pub trait DiskBackend: Send + Sync {
+    /// # Errors
+    ///
+    /// Fails when the platform's disk list cannot be read
+    fn enumerate_disks(&self) -> Result<Vec<DiskInfo>>;
+
+    /// System facts, with the disk figures taken from `disks`
+    ///
+    /// # Errors
+    ///
+    /// Fails when the operating system cannot be queried
+    fn system_info(&self, disks: &[DiskInfo]) -> Result<SystemInfo>;
+
+    /// # Errors
+    ///
+    /// Fails when the disk is unknown or refuses to come back
+    fn set_disk_online(&self, disk_id: &str) -> Result<()>;
+
+    /// Take `disk_id` offline. A sequence that stopped part way is a report
+    /// with `completed` unset, not an error.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the disk is unknown or has no way of going offline
+    fn set_disk_offline(&self, disk_id: &str) -> Result<OfflineReport>;
+
+    /// Unmount `disk_id` and power it off, reporting like `set_disk_offline`
+    ///
+    /// # Errors
+    ///
+    /// Fails when the disk is unknown
+    fn eject_disk(&self, disk_id: &str) -> Result<OfflineReport>;
+
+    /// # Errors
+    ///
+    /// Fails when the disks cannot be enumerated around the scan
+    fn rescan_storage(&self) -> Result<RescanReport>;
+
+    /// Mount a partition; `letter` only means something on Windows
+    ///
+    /// # Errors
+    ///
+    /// Fails when the partition is not found or cannot be mounted
+    fn mount_partition(
+        &self,
+        disk_id: &str,
+        partition_number: u32,
+        letter: Option<char>,
+        options: &MountOptions,
+    ) -> Result<MountedPartition>;
+
+    /// Unmount a mount point (Linux) or drive letter or folder (Windows). Failed
+    /// unmounts are reported per step.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `target` is not mounted
+    fn unmount_partition(&self, target: &str, mode: UnmountMode) -> Result<Vec<StepReport>>;
+
+    /// # Errors
+    ///
+    /// Fails when the disk is unknown
+    fn unmount_disk(&self, disk_id: &str, mode: UnmountMode) -> Result<Vec<StepReport>>;
+
+    /// What keeps a mount point, drive letter or device busy
+    ///
+    /// # Errors
+    ///
+    /// Fails when `target` is neither mounted nor a device
+    fn diagnose_busy(&self, target: &str) -> Result<BusyReport>;
+
+    /// Why a mount point or device that no disk in the inventory holds must
+    /// be protected, from the device it is mounted from
+    ///
+    /// # Errors
+    ///
+    /// Fails when `target` cannot be resolved to a device
+    fn target_protection(&self, target: &str) -> Result<Vec<ProtectionReason>>;
+}
+
+/// The backend of the platform this was built for
+#[cfg(target_os = "linux")]
+pub fn platform() -> Box<dyn DiskBackend> {
+    Box::new(super::LinuxBackend)
+}
+
+/// The backend of the platform this was built for
+#[cfg(target_os = "windows")]
+pub fn platform() -> Box<dyn DiskBackend> {
+    Box::new(super::WindowsBackend)
+}
@@ -0,0 +1,196 @@
+// src/disk_operations/busy.rs
+// Which processes keep a filesystem busy, found by walking /proc the way
+// fuser -m does: open file descriptors, working directory, root and mapped
+// files (executables, libraries, mmap). Swap files on the filesystem keep it
+// busy too, without any process holding them.
+use super::mountinfo::{read_mountinfo, MountEntry};
+use super::swaps::read_swaps;
+use super::sysfs::{read_attr, SysfsRoot};
+use crate::structs::{BlockingProcess, BusyReport};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// True when `path` is `mount_point` or lies below it
+pub fn is_inside(path: &str, mount_point: &str) -> bool {
+    mount_point == "/"
+        || path == mount_point
+        || path
+            .strip_prefix(mount_point)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn link_target(path: &Path) -> Option<String> {
+    let target = std::fs::read_link(path).ok()?;
+    Some(target.to_string_lossy().into_owned())
+}
+
+/// Command line of a process, falling back to its short name for kernel
+/// threads and zombies, which have an empty cmdline
+fn command_of(proc_dir: &Path) -> String {
+    let cmdline = std::fs::read(proc_dir.join("cmdline")).unwrap_or_default();
+    let args: Vec<String> = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if args.is_empty() {
+        std::fs::read_to_string(proc_dir.join("comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_default()
+    } else {
+        args.join(" ")
+    }
+}
+
+/// User names by uid, from /etc/passwd
+fn read_users(root: &SysfsRoot) -> HashMap<u32, String> {
+    std::fs::read_to_string(root.host_path("/etc/passwd"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+/// Real uid of a process as a user name, or the bare uid when it has none
+fn user_of(proc_dir: &Path, users: &HashMap<u32, String>) -> String {
+    let status = std::fs::read_to_string(proc_dir.join("status")).unwrap_or_default();
+    let uid = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().next())
+        .and_then(|uid| uid.parse::<u32>().ok());
+    match uid {
+        Some(uid) => users.get(&uid).cloned().unwrap_or_else(|| uid.to_string()),
+        None => String::new(),
+    }
+}
+
+// address           perms offset   dev   inode    pathname
+// 7f2c4a000000-7f2c4a021000 r-xp 00000000 08:11 1234   /media/stick/tool
+fn mapped_files(proc_dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(proc_dir.join("maps"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut rest = line;
+            for _ in 0..5 {
+                rest = rest.trim_start().split_once(' ')?.1;
+            }
+            let path = rest.trim();
+            path.starts_with('/')
+                .then(|| path.trim_end_matches(" (deleted)").to_string())
+        })
+        .collect()
+}
+
+/// Paths a process holds inside any of `mount_points`
+fn held_paths(proc_dir: &Path, mount_points: &[String]) -> Vec<String> {
+    let fds = std::fs::read_dir(proc_dir.join("fd"))
+        .into_iter()
+        .flatten()
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path());
+    let links = [proc_dir.join("cwd"), proc_dir.join("root")]
+        .into_iter()
+        .chain(fds)
+        .filter_map(|link| link_target(&link));
+
+    let mut paths: Vec<String> = links
+        .chain(mapped_files(proc_dir))
+        .filter(|target| mount_points.iter().any(|m| is_inside(target, m)))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Processes holding anything inside `mount_points`. Processes that vanish
+/// or cannot be inspected while walking /proc are skipped.
+pub fn find_blockers(root: &SysfsRoot, mount_points: &[String]) -> Vec<BlockingProcess> {
+    let Ok(entries) = std::fs::read_dir(root.host_path("/proc")) else {
+        return Vec::new();
+    };
+    let users = read_users(root);
+    let mut blockers: Vec<BlockingProcess> = entries
+        .filter_map(std::result::Result::ok)
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let proc_dir = entry.path();
+            let paths = held_paths(&proc_dir, mount_points);
+            if paths.is_empty() {
+                return None;
+            }
+            Some(BlockingProcess {
+                pid,
+                command: command_of(&proc_dir),
+                user: user_of(&proc_dir, &users),
+                paths,
+            })
+        })
+        .collect();
+    blockers.sort_by_key(|b| b.pid);
+    blockers
+}
+
+/// True when `mount` is `target`, either as its mount point or its device
+fn mounts_target(root: &SysfsRoot, mount: &MountEntry, target: &str) -> bool {
+    if mount.mount_point == target || mount.source == target {
+        return true;
+    }
+    let Some(name) = target.strip_prefix("/dev/") else {
+        return false;
+    };
+    read_attr(&root.class_block_device(name).join("dev")).is_some_and(|dev| dev == mount.dev())
+}
+
+/// What keeps `target` busy. `target` is a mount point or a device node;
+/// filesystems mounted below it are included, since they keep it busy too.
+///
+/// # Errors
+///
+/// Fails when the mount table cannot be read, or `target` is neither
+/// a mount point nor a device node
+pub fn diagnose(root: &SysfsRoot, target: &str) -> Result<BusyReport> {
+    let mounts = read_mountinfo(&root.mountinfo_path())?;
+    let mut mount_points: Vec<String> = mounts
+        .iter()
+        .filter(|m| mounts_target(root, m, target))
+        .map(|m| m.mount_point.clone())
+        .collect();
+    if mount_points.is_empty() {
+        bail!("{target} is not mounted");
+    }
+    for mount in &mounts {
+        let nested = mount_points
+            .iter()
+            .any(|m| m != &mount.mount_point && is_inside(&mount.mount_point, m));
+        if nested && !mount_points.contains(&mount.mount_point) {
+            mount_points.push(mount.mount_point.clone());
+        }
+    }
+
+    let swap = read_swaps(&root.swaps_path())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|s| {
+            if s.is_partition {
+                s.path == target
+            } else {
+                mount_points.iter().any(|m| is_inside(&s.path, m))
+            }
+        })
+        .map(|s| s.path)
+        .collect();
+
+    Ok(BusyReport {
+        processes: find_blockers(root, &mount_points),
+        mount_points,
+        swap,
+    })
+}
@@ -0,0 +1,41 @@
+// src/disk_operations/capabilities.rs
+// What a Linux disk supports, read from the attributes its device exposes
+use super::offline_method;
+use super::sysfs::{read_attr, SysfsRoot};
+use crate::structs::DiskCapabilities;
+
+/// Facts about the disk already worked out by the enumerator
+pub struct DiskFacts<'a> {
+    pub transport: Option<&'a str>,
+    pub removable: bool,
+    pub rotational: Option<bool>,
+    pub is_online: bool,
+}
+
+fn flag(root: &SysfsRoot, disk: &str, attr: &str) -> Option<bool> {
+    read_attr(&root.block_device(disk).join(attr)).map(|v| v == "1")
+}
+
+pub fn detect(root: &SysfsRoot, disk: &str, facts: &DiskFacts) -> DiskCapabilities {
+    let dir = root.block_device(disk);
+    let transport = facts.transport.unwrap_or_default();
+    let usb = transport == "usb";
+    // Only SCSI disks take START STOP UNIT, which is how they spin down
+    let scsi = dir.join("device/scsi_disk").exists() || dir.join("device/state").exists();
+    let hotplug = facts.removable || usb || transport == "mmc";
+
+    DiskCapabilities {
+        can_offline: facts.is_online && offline_method::select(root, disk).is_ok(),
+        can_online: !facts.is_online,
+        can_eject: facts.is_online && hotplug,
+        // udisks powers off a disk by disabling its USB port
+        can_power_off: usb,
+        can_spin_down: scsi && facts.rotational == Some(true),
+        // USB bridges rarely pass SMART through, so only direct ATA and NVMe
+        supports_smart: matches!(transport, "sata" | "ata" | "nvme"),
+        supports_trim: read_attr(&dir.join("queue/discard_max_bytes"))
+            .and_then(|v| v.parse::<u64>().ok())
+            .is_some_and(|max| max > 0),
+        is_read_only: flag(root, disk, "ro").unwrap_or(false),
+    }
+}
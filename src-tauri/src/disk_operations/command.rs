@@ -0,0 +1,344 @@
+// src/disk_operations/command.rs
+// Running helper programs (lsblk, udevadm) with a time limit.
+//
+// Every program runs with LC_ALL=C, so its output can be parsed whatever
+// the user's locale, and with stdin closed. It is killed when its timeout
+// runs out or its `CancelToken` is cancelled; the exit code, stdout and
+// stderr are returned either way it exits.
+//
+// `Recorder` saves every command and its output to a transcript file, and
+// `Replay` answers commands from one, so that tests can run against output
+// captured on other systems. Setting DISKOFFLANER_RECORD_COMMANDS to a file
+// path makes `system_runner` record there.
+use crate::structs::{DiskError, ErrorCode};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Environment variable naming a transcript file to record commands to
+pub const RECORD_ENV: &str = "DISKOFFLANER_RECORD_COMMANDS";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a running program is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Stops the commands it is given to, from any thread
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A program and its arguments, as recorded in transcripts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl std::fmt::Display for Invocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A command to run: what, for how long at most, and what can stop it
+#[derive(Debug, Clone)]
+pub struct ExternalCommand {
+    pub invocation: Invocation,
+    pub timeout: Duration,
+    pub cancel: Option<CancelToken>,
+}
+
+impl ExternalCommand {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            invocation: Invocation {
+                program: program.into(),
+                args: Vec::new(),
+            },
+            timeout: DEFAULT_TIMEOUT,
+            cancel: None,
+        }
+    }
+
+    #[must_use]
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.invocation.args.push(arg.into());
+        self
+    }
+
+    #[must_use]
+    pub fn args<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.invocation
+            .args
+            .extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn cancel_with(mut self, token: &CancelToken) -> Self {
+        self.cancel = Some(token.clone());
+        self
+    }
+}
+
+/// How a program exited and what it printed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandOutput {
+    /// Exit code, None when the program was killed by a signal
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    /// The output of a successful run, otherwise an error with the exit
+    /// code and the first line the program wrote to stderr, usually the one
+    /// that says what went wrong
+    ///
+    /// # Errors
+    ///
+    /// Fails unless the program exited with 0
+    pub fn checked(self, invocation: &Invocation) -> Result<Self> {
+        if self.success() {
+            return Ok(self);
+        }
+        let status = self.status.map_or_else(
+            || "was killed".to_string(),
+            |code| format!("exited with {code}"),
+        );
+        match self.stderr.lines().map(str::trim).find(|l| !l.is_empty()) {
+            Some(line) => bail!("{} {status}: {line}", invocation.program),
+            None => bail!("{} {status}", invocation.program),
+        }
+    }
+}
+
+/// Runs external commands; replaced by a transcript in tests
+pub trait CommandRunner: Send + Sync {
+    /// # Errors
+    ///
+    /// Fails when the program cannot be started, overruns its timeout
+    /// (`TimedOut`) or is cancelled (`Cancelled`). A non-zero exit is not an
+    /// error here; see `CommandOutput::checked`.
+    fn run(&self, command: &ExternalCommand) -> Result<CommandOutput>;
+}
+
+/// The real thing: spawns the program and waits for it, up to its timeout
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, command: &ExternalCommand) -> Result<CommandOutput> {
+        let invocation = &command.invocation;
+        let mut child = Command::new(&invocation.program)
+            .args(&invocation.args)
+            .env("LC_ALL", "C")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", invocation.program))?;
+        // Read both pipes while waiting, so a chatty program cannot fill
+        // one and block
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+
+        let deadline = Instant::now() + command.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if command
+                .cancel
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+            {
+                stop(&mut child);
+                bail!(DiskError::new(
+                    ErrorCode::Cancelled,
+                    format!("{invocation} was cancelled")
+                ));
+            }
+            if Instant::now() >= deadline {
+                stop(&mut child);
+                bail!(DiskError::new(
+                    ErrorCode::TimedOut,
+                    format!(
+                        "{invocation} did not finish within {} s",
+                        command.timeout.as_secs_f32()
+                    )
+                ));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+
+        Ok(CommandOutput {
+            status: status.code(),
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+        })
+    }
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> Option<JoinHandle<Vec<u8>>> {
+    let mut pipe = pipe?;
+    Some(std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    }))
+}
+
+fn collect(reader: Option<JoinHandle<Vec<u8>>>) -> String {
+    let bytes = reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Kill a program that overran. Its pipe readers are left to finish on
+/// their own, in case something it started still holds the pipes open.
+fn stop(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panic while recording leaves the transcript as it was
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// One command and its output in a transcript
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub command: Invocation,
+    pub output: CommandOutput,
+}
+
+/// Runs commands with `inner` and adds each one with its output to a
+/// transcript file, rewritten after every command
+pub struct Recorder<R: CommandRunner> {
+    inner: R,
+    path: PathBuf,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl<R: CommandRunner> Recorder<R> {
+    /// Record to `path`, after the exchanges already in it
+    pub fn new(inner: R, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let exchanges = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            inner,
+            path,
+            exchanges: Mutex::new(exchanges),
+        }
+    }
+}
+
+impl<R: CommandRunner> CommandRunner for Recorder<R> {
+    fn run(&self, command: &ExternalCommand) -> Result<CommandOutput> {
+        // Timeouts and failures to start are not recorded, only exits
+        let output = self.inner.run(command)?;
+        let mut exchanges = lock(&self.exchanges);
+        exchanges.push(Exchange {
+            command: command.invocation.clone(),
+            output: output.clone(),
+        });
+        std::fs::write(&self.path, serde_json::to_string_pretty(&*exchanges)?)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(output)
+    }
+}
+
+/// Answers commands from a transcript. Each exchange is used once, the
+/// first one with the same program and arguments; a command with none left
+/// fails.
+pub struct Replay {
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl Replay {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Self {
+            exchanges: Mutex::new(exchanges),
+        }
+    }
+
+    /// Read a transcript written by `Recorder`
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be read or is not a transcript
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content)
+    }
+
+    /// # Errors
+    ///
+    /// Fails when `transcript` is not a JSON list of exchanges
+    pub fn parse(transcript: &str) -> Result<Self> {
+        Ok(Self::new(serde_json::from_str(transcript)?))
+    }
+
+    /// Exchanges not asked for yet
+    pub fn remaining(&self) -> Vec<Exchange> {
+        lock(&self.exchanges).clone()
+    }
+}
+
+impl CommandRunner for Replay {
+    fn run(&self, command: &ExternalCommand) -> Result<CommandOutput> {
+        let mut exchanges = lock(&self.exchanges);
+        let Some(index) = exchanges
+            .iter()
+            .position(|e| e.command == command.invocation)
+        else {
+            bail!("No recorded output for {}", command.invocation);
+        };
+        Ok(exchanges.remove(index).output)
+    }
+}
+
+/// The runner for the live system, recording to the file named by
+/// `RECORD_ENV` when it is set
+pub fn system_runner() -> Box<dyn CommandRunner> {
+    match std::env::var_os(RECORD_ENV) {
+        Some(path) if !path.is_empty() => Box::new(Recorder::new(SystemRunner, path)),
+        _ => Box::new(SystemRunner),
+    }
+}
@@ -0,0 +1,60 @@
+// src/disk_operations/devnode.rs
+// Mapping between (disk, partition number) and kernel device names.
+//
+// The kernel appends the partition number to the disk name, inserting a "p"
+// when the disk name itself ends in a digit (see disk_name() in
+// block/partitions/core.c):
+//   sda -> sda1, vda -> vda1, xvda -> xvda1
+//   nvme0n1 -> nvme0n1p1, mmcblk0 -> mmcblk0p1, loop0 -> loop0p1,
+//   nbd0 -> nbd0p1, md127 -> md127p1
+use super::sysfs::{read_attr, SysfsRoot};
+use std::path::PathBuf;
+
+/// Kernel name of partition `number` on `disk`, from the naming rule alone
+pub fn partition_name(disk: &str, number: u32) -> String {
+    if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{disk}p{number}")
+    } else {
+        format!("{disk}{number}")
+    }
+}
+
+/// Inverse of `partition_name`: the partition number encoded in a kernel
+/// partition name, if `partition` belongs to `disk`.
+pub fn partition_number_from_name(disk: &str, partition: &str) -> Option<u32> {
+    let suffix = partition.strip_prefix(disk)?;
+    let digits = if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        suffix.strip_prefix('p')?
+    } else {
+        suffix
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Partition number as recorded by the kernel in sysfs
+pub fn read_partition_number(root: &SysfsRoot, partition: &str) -> Option<u32> {
+    read_attr(&root.class_block_device(partition).join("partition"))?
+        .parse()
+        .ok()
+}
+
+/// Find the partition of `disk` whose sysfs `partition` attribute is `number`,
+/// falling back to the naming rule when sysfs has no such entry.
+pub fn resolve_partition_name(root: &SysfsRoot, disk: &str, number: u32) -> String {
+    let from_sysfs = std::fs::read_dir(root.block_device(disk))
+        .into_iter()
+        .flatten()
+        .filter_map(std::result::Result::ok)
+        .find(|entry| read_attr(&entry.path().join("partition")) == Some(number.to_string()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned());
+
+    from_sysfs.unwrap_or_else(|| partition_name(disk, number))
+}
+
+/// Device node of partition `number` on `disk`, e.g. /dev/nvme0n1p2
+pub fn resolve_partition_node(root: &SysfsRoot, disk: &str, number: u32) -> PathBuf {
+    root.dev_node(&resolve_partition_name(root, disk, number))
+}
@@ -1,238 +1,167 @@
+
 // src/disk_operations/disk_operations_linux.rs
-use crate::structs::{DiskInfo, DiskType, PartitionInfo, SystemInfo};
+use super::backend::DiskBackend;
+use super::busy;
+use super::command::{self, ExternalCommand};
+use super::hotplug::{self, HotplugEvent};
+use super::lsblk;
+use super::mount;
+use super::offline;
+use super::rescan;
+use super::sysfs::{self, SysfsRoot};
+use super::system_devices;
+use super::unmount;
+use crate::structs::{
+    BusyReport, DiskInfo, MountOptions, MountedPartition, OfflineReport, ProtectionReason,
+    RescanReport, StepReport, SystemInfo, UnmountMode,
+};
 use anyhow::Result;
-use serde::Deserialize;
-use std::process::Command;
+use std::time::Duration;
 
-#[derive(Debug, Deserialize)]
-struct LsblkOutput {
-    blockdevices: Vec<BlockDevice>,
+/// # Errors
+///
+/// Fails when neither sysfs nor lsblk can list the disks
+pub fn enumerate_disks() -> Result<Vec<DiskInfo>> {
+    // Read sysfs directly; lsblk is only needed when /sys/block is unavailable
+    let disks = match sysfs::enumerate_disks(&SysfsRoot::system()) {
+        Ok(disks) => disks,
+        Err(_) => enumerate_disks_lsblk()?,
+    };
+    // Detached NVMe, virtio and MMC disks are no longer in sysfs
+    Ok(offline::with_offline_disks(&SysfsRoot::system(), disks))
 }
 
-#[derive(Debug, Deserialize, Debug, Deserialize)]
-struct BlockDevice {
-    name: String,
-    size: Option<u64>, // lsblk -b gives bytes
-    #[serde(rename = "type")]
-    device_type: Option<String>,
-    mountpoint: Option<String>,
-    model: Option<String>,
-    #[serde(rename = "serial")]
-    serial: Option<String>,
-    state: Option<String>,
-    rm: Option<String>,   // Removable flag
-    rota: Option<String>, // Rotational (1 = HDD, 0 = SSD)
-    tran: Option<String>, // Transport type (nvme, usb, sata, etc.)
-    children: Option<Vec<BlockDevice>>,
+fn enumerate_disks_lsblk() -> Result<Vec<DiskInfo>> {
+    let output = lsblk::read_lsblk(command::system_runner().as_ref())?;
+    Ok(lsblk::disks_from_lsblk(output, &SysfsRoot::system()))
 }
 
-pub fn enumerate_disks() -> Result<Vec<DiskInfo>> {
-    // lsblk -J -b -o NAME,SIZE,TYPE,MOUNTPOINT,MODEL,SERIAL,STATE,RM,ROTA,TRAN
-    let output = Command::new("lsblk")
-        .arg("-J") // JSON output
-        .arg("-b") // Bytes
-        .arg("-o")
-        .arg("NAME,SIZE,TYPE,MOUNTPOINT,MODEL,SERIAL,STATE,RM,ROTA,TRAN")
-        .output()?;
-
-    if !output.status.success() {
-        return Err(anyhow::anyhow!("lsblk failed"));
-    }
-
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let lsblk: LsblkOutput = serde_json::from_str(&output_str)?;
-
-    let mut disks = Vec::new();
-
-    for device in lsblk.blockdevices {
-        // Filter for actual disks
-        if device.device_type.as_deref() != Some("disk") {
-            continue;
-        }
-
-        let id = device.name.clone();
-        let model = device.model.unwrap_or_else(|| format!("Disk {}", id));
-        let size_bytes = device.size.unwrap_or(0);
-
-        // Check state. If state is "offline", then it's offline.
-        let is_online = device.state.as_deref() != Some("offline");
-
-        // Determine disk type based on transport and properties
-        let disk_type = get_disk_type_linux(&device);
-
-        let mut partitions = Vec::new();
-        let mut is_system_disk = false;
-
-        if let Some(children) = device.children {
-            for (i, child) in children.into_iter().enumerate() {
-                if child.device_type.as_deref() == Some("part") {
-                    let mountpoint = child.mountpoint.clone().unwrap_or_default();
-                    if mountpoint == "/" || mountpoint == "/boot" || mountpoint == "/boot/efi" {
-                        is_system_disk = true;
-                    }
-
-                    partitions.push(PartitionInfo {
-                        partition_number: (i + 1) as u32,
-                        size_bytes: child.size.unwrap_or(0),
-                        used_bytes: None, // Logic for used_bytes on Linux can be added later
-                        drive_letter: mountpoint, // Using drive_letter field for mountpoint
-                        partition_id: child.name, // Using partition_id for device name (e.g. sda1)
-                    });
-                }
-            }
-        }
-
-        disks.push(DiskInfo {
-            id,
-            model,
-            size_bytes,
-            is_online,
-            is_system_disk,
-            partitions,
-            disk_type,
-            serial_number: device.serial,
-            health_percentage: None,
-            usage_percentage: None,
-        });
-    }
-
-    Ok(disks)
+/// # Errors
+///
+/// Fails when `disk_id` has no offline record or SCSI state to restore,
+/// or a bring-back write fails
+pub fn set_disk_online(disk_id: &str) -> Result<()> {
+    // Replay what set_disk_offline recorded: "running" to device/state, a
+    // PCI rescan or a driver bind. Note: disk_id is like "sda"
+    offline::bring_online(&SysfsRoot::system(), disk_id, &mut offline::SystemActions)
 }
 
-fn get_disk_type_linux(device: &BlockDevice) -> DiskType {
-    // Check transport type first
-    if let Some(tran) = &device.tran {
-        match tran.as_str() {
-            "nvme" => return DiskType::NVMe,
-            "usb" => {
-                // Check if removable
-                if device.rm.as_deref() == Some("1") {
-                    return DiskType::USBFlash;
-                } else {
-                    return DiskType::ExtHDD;
-                }
-            }
-            _ => {}
-        }
-    }
-
-    // Check rotational flag (SSD vs HDD)
-    if let Some(rota) = &device.rota {
-        if rota == "0" {
-            return DiskType::SSD;
-        } else if rota == "1" {
-            return DiskType::HDD;
-        }
-    }
-
-    // Default to HDD
-    DiskType::HDD
+/// # Errors
+///
+/// Fails when `disk_id` does not exist or cannot be taken offline
+pub fn set_disk_offline(disk_id: &str) -> Result<OfflineReport> {
+    // Unmount, sync and flush before taking the disk offline the way its
+    // device type allows, rolling back on failure
+    offline::take_offline(&SysfsRoot::system(), disk_id, &mut offline::SystemActions)
 }
 
-pub fn set_disk_online(disk_id: String) -> Result<()> {
-    // Try to write "running" to /sys/block/disk_id/device/state
-    // Note: disk_id is like "sda"
-    let path = format!("/sys/block/{}/device/state", disk_id);
-    if std::path::Path::new(&path).exists() {
-        std::fs::write(path, "running")?;
-    } else {
-        return Err(anyhow::anyhow!("Cannot change state for {}", disk_id));
-    }
-    Ok(())
+/// # Errors
+///
+/// Fails when `disk_id` does not exist
+pub fn eject_disk(disk_id: &str) -> Result<OfflineReport> {
+    // Unmount everything and flush before udisks powers the drive off,
+    // falling back to /sys/block/disk_id/device/delete
+    offline::eject(&SysfsRoot::system(), disk_id, &mut offline::SystemActions)
 }
 
-pub fn set_disk_offline(disk_id: String) -> Result<()> {
-    // Try to write "offline" to /sys/block/disk_id/device/state
-    let path = format!("/sys/block/{}/device/state", disk_id);
-    if std::path::Path::new(&path).exists() {
-        std::fs::write(path, "offline")?;
-    } else {
-        return Err(anyhow::anyhow!("Cannot change state for {}", disk_id));
-    }
-    Ok(())
+/// # Errors
+///
+/// Fails when the disks cannot be enumerated around the scan
+pub fn rescan_storage() -> Result<RescanReport> {
+    // Disks found by the scan are probed asynchronously; udevadm settle
+    // waits until their uevents have been handled
+    rescan::rescan(&SysfsRoot::system(), &mut offline::SystemActions, || {
+        let settle = ExternalCommand::new("udevadm")
+            .args(["settle", "--timeout=10"])
+            .timeout(Duration::from_secs(15));
+        let _ = command::system_runner().run(&settle);
+    })
 }
 
-#[allow(dead_code)]
-pub fn eject_disk(disk_id: String) -> Result<()> {
-    // For removable drives, use udisksctl to power off
-    let output = Command::new("udisksctl")
-        .arg("power-off")
-        .arg("-b")
-        .arg(format!("/dev/{}", disk_id))
-        .output()?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Failed to eject disk: {}", stderr));
-    }
-    Ok(())
+/// # Errors
+///
+/// Fails when the partition does not exist, `options` are refused or
+/// the mount fails
+pub fn mount_partition(
+    disk_id: &str,
+    partition_number: u32,
+    _letter: Option<char>,
+    options: &MountOptions,
+) -> Result<MountedPartition> {
+    // Drive letters don't exist on Linux; udisks picks the mount point
+    // unless options.target names one
+    let mount_point = mount::mount_partition(
+        &SysfsRoot::system(),
+        disk_id,
+        partition_number,
+        options,
+        &mut offline::SystemActions,
+    )?;
+    Ok(MountedPartition {
+        letter: None,
+        mount_point: Some(mount_point),
+    })
 }
 
-pub fn mount_partition(disk_id: String, partition_number: u32) -> Result<()> {
-    // Construct device path based on disk ID
-    // standard: sda -> sda1
-    // nvme: nvme0n1 -> nvme0n1p1
-    let device_path = if disk_id.starts_with("nvme") {
-        format!("/dev/{}p{}", disk_id, partition_number)
-    } else {
-        format!("/dev/{}{}", disk_id, partition_number)
-    };
-
-    let output = Command::new("udisksctl")
-        .arg("mount")
-        .arg("-b")
-        .arg(&device_path)
-        .output()?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Failed to mount partition: {}", stderr));
-    }
-    Ok(())
+/// # Errors
+///
+/// Fails when `mount_point` is not mounted
+pub fn unmount_partition(mount_point: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+    // mount_point on Linux is one of the partition's mount paths
+    // (e.g., /media/user/USB)
+    unmount::unmount_mount_point(
+        &SysfsRoot::system(),
+        mount_point,
+        mode,
+        &mut offline::SystemActions,
+    )
 }
 
-pub fn unmount_partition(mount_point: String) -> Result<()> {
-    // mount_point on Linux is the actual mount path (e.g., /media/user/USB)
-    // We need to find the device from the mount point
-    let output = Command::new("findmnt")
-        .arg("-n")
-        .arg("-o")
-        .arg("SOURCE")
-        .arg(&mount_point)
-        .output()?;
-
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "Cannot find device for mount point: {}",
-            mount_point
-        ));
-    }
+/// # Errors
+///
+/// Fails when `disk_id` does not exist or the mount table is unreadable
+pub fn unmount_disk(disk_id: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+    unmount::unmount_disk(&SysfsRoot::system(), disk_id, mode, &mut offline::SystemActions)
+}
 
-    let device = String::from_utf8_lossy(&output.stdout).trim().to_string();
+/// # Errors
+///
+/// Fails when `target` is neither a mount point nor a device node
+pub fn diagnose_busy(target: &str) -> Result<BusyReport> {
+    // target is a mount point or a device node such as /dev/sdb1
+    busy::diagnose(&SysfsRoot::system(), target)
+}
 
-    let output = Command::new("udisksctl")
-        .arg("unmount")
-        .arg("-b")
-        .arg(&device)
-        .output()?;
+/// # Errors
+///
+/// Fails when `target` is neither mounted nor a device node
+pub fn target_protection(target: &str) -> Result<Vec<ProtectionReason>> {
+    // / or swap on LVM, LUKS or mdraid is mounted from a dm or md node
+    // that no partition in the inventory stands for
+    system_devices::target_protection(&SysfsRoot::system(), target)
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Failed to unmount partition: {}", stderr));
-    }
-    Ok(())
+/// Report disks that come, go or change until the process exits
+pub fn watch_disks(on_event: impl FnMut(HotplugEvent)) {
+    hotplug::watch(&SysfsRoot::system(), enumerate_disks, on_event);
 }
 
-pub fn get_system_info() -> Result<crate::structs::SystemInfo> {
-    let disks = enumerate_disks()?;
+/// System facts, with the disk figures taken from `disks`
+///
+/// # Errors
+///
+/// Never fails on Linux; the `Result` is for the other platforms
+pub fn get_system_info(disks: &[DiskInfo]) -> Result<SystemInfo> {
     let total_disks = disks.len();
     let total_capacity_bytes = disks.iter().map(|d| d.size_bytes).sum();
+    // Prefer the disk with the root filesystem over boot or swap disks
     let system_disk_id = disks
         .iter()
-        .find(|d| d.is_system_disk)
+        .find(|d| d.protection.contains(&ProtectionReason::RootFilesystem))
+        .or_else(|| disks.iter().find(|d| d.is_system_disk))
         .map(|d| d.id.clone());
 
-    Ok(crate::structs::SystemInfo {
+    Ok(SystemInfo {
         os_name: "Linux".to_string(),
         os_version: "Unknown".to_string(),
         is_admin: crate::utils::is_elevated(),
@@ -241,3 +170,58 @@ pub fn get_system_info() -> Result<crate::structs::SystemInfo> {
         system_disk_id,
     })
 }
+
+/// The commands' view of this module
+pub struct LinuxBackend;
+
+impl DiskBackend for LinuxBackend {
+    fn enumerate_disks(&self) -> Result<Vec<DiskInfo>> {
+        enumerate_disks()
+    }
+
+    fn system_info(&self, disks: &[DiskInfo]) -> Result<SystemInfo> {
+        get_system_info(disks)
+    }
+
+    fn set_disk_online(&self, disk_id: &str) -> Result<()> {
+        set_disk_online(disk_id)
+    }
+
+    fn set_disk_offline(&self, disk_id: &str) -> Result<OfflineReport> {
+        set_disk_offline(disk_id)
+    }
+
+    fn eject_disk(&self, disk_id: &str) -> Result<OfflineReport> {
+        eject_disk(disk_id)
+    }
+
+    fn rescan_storage(&self) -> Result<RescanReport> {
+        rescan_storage()
+    }
+
+    fn mount_partition(
+        &self,
+        disk_id: &str,
+        partition_number: u32,
+        letter: Option<char>,
+        options: &MountOptions,
+    ) -> Result<MountedPartition> {
+        mount_partition(disk_id, partition_number, letter, options)
+    }
+
+    fn unmount_partition(&self, target: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+        unmount_partition(target, mode)
+    }
+
+    fn unmount_disk(&self, disk_id: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+        unmount_disk(disk_id, mode)
+    }
+
+    fn diagnose_busy(&self, target: &str) -> Result<BusyReport> {
+        diagnose_busy(target)
+    }
+
+    fn target_protection(&self, target: &str) -> Result<Vec<ProtectionReason>> {
+        target_protection(target)
+    }
+}
@@ -12,13 +12,15 @@
 #![allow(clippy::cast_ptr_alignment)]
 #![allow(clippy::cast_possible_wrap)]
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::ffi::OsStr;
 use std::io::Write;
 use std::iter::once;
 use std::mem;
 use std::os::windows::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::OnceLock;
 use winapi::um::fileapi::CreateFileW;
 use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
 // src/disk_operations/disk_operations_windows.rs
@@ -27,10 +29,15 @@ use winapi::um::winioctl::{
     DISK_GEOMETRY_EX, DRIVE_LAYOUT_INFORMATION_EX, IOCTL_DISK_GET_DRIVE_GEOMETRY_EX,
     IOCTL_DISK_GET_DRIVE_LAYOUT_EX, IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS, VOLUME_DISK_EXTENTS,
 };
-use winapi::um::winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ};
+use winapi::um::winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE};
 // Removed unused PARTITION_INFORMATION_EX import
 
-use crate::structs::{DiskInfo, DiskType, PartitionInfo, SystemInfo};
+use super::backend::DiskBackend;
+use crate::structs::{
+    BusyReport, DiskCapabilities, DiskError, DiskInfo, DiskType, ErrorCode, MountOptions,
+    MountPoint, MountedPartition, OfflineReport, OfflineStep, PartitionInfo, ProtectionReason,
+    RescanReport, StepReport, StepStatus, SystemInfo, UnmountMode,
+};
 
 const OPEN_EXISTING: u32 = 3;
 
@@ -39,6 +46,13 @@ const IOCTL_STORAGE_QUERY_PROPERTY: u32 = 0x002D_1400;
 const STORAGE_DEVICE_PROPERTY: u32 = 0;
 const PROPERTY_STANDARD_QUERY: u32 = 0;
 const STORAGE_DEVICE_SEEK_PENALTY_PROPERTY: u32 = 7;
+const IOCTL_STORAGE_GET_DEVICE_NUMBER: u32 = 0x002D_1080;
+const FSCTL_LOCK_VOLUME: u32 = 0x0009_0018;
+const FSCTL_DISMOUNT_VOLUME: u32 = 0x0009_0020;
+
+// Device node capabilities, from cfgmgr32.h
+const CM_DRP_CAPABILITIES: u32 = 0x0000_0010;
+const CM_DEVCAP_REMOVABLE: u32 = 0x0000_0004;
 
 #[repr(C)]
 #[allow(non_snake_case)]
@@ -81,6 +95,14 @@ struct DEVICE_SEEK_PENALTY_DESCRIPTOR {
     IncursSeekPenalty: u8,
 }
 
+#[repr(C)]
+#[allow(non_snake_case)]
+struct STORAGE_DEVICE_NUMBER {
+    DeviceType: u32,
+    DeviceNumber: u32,
+    PartitionNumber: u32,
+}
+
 // Bus type constants for STORAGE_DEVICE_DESCRIPTOR.BusType
 const BUS_TYPE_USB: u32 = 7;
 const BUS_TYPE_NVME: u32 = 17;
@@ -88,6 +110,9 @@ const BUS_TYPE_NVME: u32 = 17;
 // Maximum number of physical disks to enumerate
 const MAX_DISK_COUNT: u32 = 32;
 
+/// # Errors
+///
+/// Fails when neither the disk handles nor PowerShell list the disks
 pub fn enumerate_disks() -> Result<Vec<DiskInfo>> {
     // If running as admin, use the robust low-level method
     if crate::utils::is_elevated() {
@@ -214,35 +239,27 @@ fn enumerate_disks_powershell() -> Result<Vec<DiskInfo>> {
         // Get partitions for this disk
         let partitions = partitions_map.get(&drive.Index).cloned().unwrap_or_default();
 
-        let mut total_used_bytes: u64 = 0;
-        let mut total_part_size_bytes: u64 = 0;
-        let mut has_usage = false;
-
-        for p in &partitions {
-            if let Some(used) = p.used_bytes {
-                total_used_bytes += used;
-                total_part_size_bytes += p.size_bytes;
-                has_usage = true;
-            }
-        }
-
-        let usage_percentage = if has_usage && total_part_size_bytes > 0 {
-            Some((total_used_bytes as f64 / total_part_size_bytes as f64) * 100.0)
+        let usage_percentage = crate::utils::usage_percentage(&partitions);
+        let protection = if id == "0" {
+            vec![ProtectionReason::SystemDisk]
         } else {
-            None
+            vec![]
         };
+        let capabilities = disk_capabilities(&disk_type, is_online, id == "0");
 
         disks.push(DiskInfo {
             id: id.clone(),
             model: model_str,
             size_bytes,
             is_online,
-            is_system_disk: id == "0",
+            is_system_disk: !protection.is_empty(),
+            protection,
             partitions,
             disk_type,
             serial_number: serial,
             health_percentage: if status == "OK" { Some(100) } else { Some(0) },
             usage_percentage,
+            capabilities,
         });
     }
 
@@ -301,6 +318,9 @@ struct PsPartition {
     DriveLetter: Option<char>, // "C", "D", or null
     Size: Option<u64>,
     FreeSpace: Option<u64>,
+    FileSystem: Option<String>,
+    // "D:\", folders the volume is mounted in and its \\?\Volume{GUID}\ path
+    AccessPaths: Option<Vec<String>>,
 }
 
 fn get_partitions_powershell() -> std::collections::HashMap<u32, Vec<PartitionInfo>> {
@@ -311,7 +331,7 @@ fn get_partitions_powershell() -> std::collections::HashMap<u32, Vec<PartitionIn
         .args(&[
             "-NoProfile",
             "-Command",
-            "Get-Partition | ForEach-Object { $p = $_; $v = $p | Get-Volume -ErrorAction SilentlyContinue; [PSCustomObject]@{ DiskNumber=$p.DiskNumber; PartitionNumber=$p.PartitionNumber; DriveLetter=$p.DriveLetter; Size=$p.Size; FreeSpace=$v.SizeRemaining } } | ConvertTo-Json"
+            "Get-Partition | ForEach-Object { $p = $_; $v = $p | Get-Volume -ErrorAction SilentlyContinue; [PSCustomObject]@{ DiskNumber=$p.DiskNumber; PartitionNumber=$p.PartitionNumber; DriveLetter=$p.DriveLetter; Size=$p.Size; FreeSpace=$v.SizeRemaining; FileSystem=$v.FileSystem; AccessPaths=@($p.AccessPaths) } } | ConvertTo-Json"
         ])
         .creation_flags(CREATE_NO_WINDOW)
         .output();
@@ -345,12 +365,34 @@ fn get_partitions_powershell() -> std::collections::HashMap<u32, Vec<PartitionIn
             None
         };
 
+        let fs_type = p.FileSystem.filter(|fs| !fs.is_empty());
+        let mount_points = p
+            .AccessPaths
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|path| !path.starts_with("\\\\?\\"))
+            .map(|path| MountPoint {
+                path,
+                fs_type: fs_type.clone().unwrap_or_default(),
+                root: "\\".to_string(),
+                ..MountPoint::default()
+            })
+            .collect();
+
         let partition_info = PartitionInfo {
             partition_number: p.PartitionNumber,
             size_bytes: p.Size.unwrap_or(0),
             used_bytes,
             drive_letter,
             partition_id: format!("{}:{}", p.DiskNumber, p.PartitionNumber),
+            mount_points,
+            usage: None,
+            fs_type,
+            label: None,
+            uuid: None,
+            part_uuid: None,
+            part_type: None,
+            part_flags: Vec::new(),
         };
         result.entry(p.DiskNumber).or_insert_with(Vec::new).push(partition_info);
     }
@@ -505,7 +547,8 @@ fn get_disk_info_with_status(
         );
 
         if handle == INVALID_HANDLE_VALUE {
-            return Err(anyhow::anyhow!("Failed to open disk {}", disk_number));
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to open disk {}", disk_number));
         }
 
         // Get disk geometry to determine size
@@ -544,24 +587,12 @@ fn get_disk_info_with_status(
             .or_else(|| health_map.get(&disk_number).copied());
 
         // Calculate usage percentage
-        let mut total_used: u64 = 0;
-        let mut total_size: u64 = 0;
-        let mut has_usage = false;
-        for p in &partitions {
-            if let Some(used) = p.used_bytes {
-                total_used += used;
-                total_size += p.size_bytes;
-                has_usage = true;
-            }
-        }
-        let usage_percentage = if has_usage && total_size > 0 {
-            Some((total_used as f64 / total_size as f64) * 100.0)
-        } else {
-            None
-        };
+        let usage_percentage = crate::utils::usage_percentage(&partitions);
 
         CloseHandle(handle);
 
+        let capabilities = disk_capabilities(&disk_type, is_online, is_system_disk);
+
         let ioctl_model = get_disk_model(handle);
         let model = if ioctl_model == "Disk" {
              model_map.get(&disk_number).cloned().unwrap_or(format!("Disk {}", disk_number))
@@ -575,11 +606,17 @@ fn get_disk_info_with_status(
             size_bytes,
             is_online,
             is_system_disk,
+            protection: if is_system_disk {
+                vec![ProtectionReason::SystemDisk]
+            } else {
+                vec![]
+            },
             partitions,
             disk_type,
             serial_number,
             health_percentage,
             usage_percentage,
+            capabilities,
         })
     }
 }
@@ -811,7 +848,7 @@ fn get_partitions_layout(disk_number: u32) -> Result<Vec<PartitionInfo>> {
         );
 
         if handle == INVALID_HANDLE_VALUE {
-            return Err(anyhow::anyhow!("Failed to open disk for layout"));
+            return Err(std::io::Error::last_os_error()).context("Failed to open disk for layout");
         }
 
         // Allocate a large buffer for layout info (supports many partitions)
@@ -865,6 +902,14 @@ fn get_partitions_layout(disk_number: u32) -> Result<Vec<PartitionInfo>> {
                     used_bytes: None,
                     drive_letter: String::new(),           // Filled later
                     partition_id: format!("{:X}", offset), // Use Offset as ID for matching
+                    mount_points: Vec::new(),
+                    usage: None,
+                    fs_type: None,
+                    label: None,
+                    uuid: None,
+                    part_uuid: None,
+                    part_type: None,
+                    part_flags: Vec::new(),
                 });
             }
         }
@@ -892,19 +937,33 @@ fn get_partitions(disk_number: u32) -> Result<Vec<PartitionInfo>> {
                 if let Ok(info) = get_partition_on_disk(&volume_path, disk_number, &drive_letter) {
                     // info.partition_id holds the offset in Hex
                     if let Ok(offset) = u64::from_str_radix(&info.partition_id, 16) {
-                        mounted_map.insert(offset, (drive_letter, info.used_bytes));
+                        mounted_map.insert(offset, info);
                     }
                 }
             }
         }
     }
 
-    // 3. Merge Drive Letters
+    // Volumes without a drive letter, only mounted in folders
+    for volume_path in volume_paths() {
+        if let Ok(info) = get_partition_on_disk(&volume_path, disk_number, "") {
+            if info.mount_points.is_empty() {
+                continue;
+            }
+            if let Ok(offset) = u64::from_str_radix(&info.partition_id, 16) {
+                mounted_map.entry(offset).or_insert(info);
+            }
+        }
+    }
+
+    // 3. Merge Drive Letters and mount points
     for part in &mut partitions {
         if let Ok(offset) = u64::from_str_radix(&part.partition_id, 16) {
-            if let Some((letter, used)) = mounted_map.get(&offset) {
-                part.drive_letter = letter.clone();
-                part.used_bytes = *used;
+            if let Some(info) = mounted_map.get(&offset) {
+                part.drive_letter = info.drive_letter.clone();
+                part.used_bytes = info.used_bytes;
+                part.mount_points = info.mount_points.clone();
+                part.fs_type = info.fs_type.clone();
             }
         }
     }
@@ -986,7 +1045,11 @@ fn get_partition_on_disk(
 
         // Fetch used bytes
         let mut used_bytes: Option<u64> = None;
-        let root_path = format!("{}:\\", drive_letter);
+        let root_path = if drive_letter.is_empty() {
+            format!("{}\\", volume_path)
+        } else {
+            format!("{}:\\", drive_letter)
+        };
         let wide_root: Vec<u16> = OsStr::new(&root_path).encode_wide().chain(once(0)).collect();
         let mut free_avail = 0i64;
         let mut total = 0i64;
@@ -1001,23 +1064,465 @@ fn get_partition_on_disk(
             used_bytes = Some((total as u64).saturating_sub(total_free as u64));
         }
 
+        let mount_points = volume_mount_points(&root_path);
+        let fs_type = mount_points
+            .first()
+            .map(|m| m.fs_type.clone())
+            .filter(|fs| !fs.is_empty());
+
         Ok(PartitionInfo {
             partition_number: 0,
             size_bytes: *extent.ExtentLength.QuadPart() as u64,
             used_bytes,
             drive_letter: drive_letter.to_string(),
             partition_id: format!("{:X}", extent.StartingOffset.QuadPart()),
+            mount_points,
+            usage: None,
+            fs_type,
+            label: None,
+            uuid: None,
+            part_uuid: None,
+            part_type: None,
+            part_flags: Vec::new(),
         })
     }
 }
 
-pub fn unmount_partition(drive_letter: String) -> Result<()> {
-    let script = format!("select volume {}\nremove\nexit\n", drive_letter);
-    let _ = run_diskpart_script_output(&script)?;
+/// `\\?\Volume{GUID}` paths of every volume, without the trailing
+/// backslash, as `CreateFileW` wants them
+fn volume_paths() -> Vec<String> {
+    use winapi::um::fileapi::{FindFirstVolumeW, FindNextVolumeW, FindVolumeClose};
+
+    let mut paths = Vec::new();
+    let mut name = [0u16; 260];
+    unsafe {
+        let find = FindFirstVolumeW(name.as_mut_ptr(), name.len() as u32);
+        if find == INVALID_HANDLE_VALUE {
+            return paths;
+        }
+        loop {
+            let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            let path = String::from_utf16_lossy(&name[..len]);
+            paths.push(path.trim_end_matches('\\').to_string());
+            if FindNextVolumeW(find, name.as_mut_ptr(), name.len() as u32) == 0 {
+                break;
+            }
+        }
+        FindVolumeClose(find);
+    }
+    paths
+}
+
+/// Where the volume holding `root_path` (e.g. `D:\`) is mounted: its drive
+/// root and every folder it is mounted in
+fn volume_mount_points(root_path: &str) -> Vec<MountPoint> {
+    use winapi::shared::winerror::ERROR_MORE_DATA;
+    use winapi::um::fileapi::{GetVolumeInformationW, GetVolumePathNamesForVolumeNameW};
+    use winapi::um::winnt::FILE_READ_ONLY_VOLUME;
+
+    let Ok(volume_name) = volume_name_of(root_path) else {
+        return Vec::new();
+    };
+    let volume_name: Vec<u16> = OsStr::new(&volume_name).encode_wide().chain(once(0)).collect();
+    unsafe {
+        // A list of NUL-terminated paths, ending with an empty one
+        let mut names = vec![0u16; 512];
+        let mut len = 0u32;
+        if GetVolumePathNamesForVolumeNameW(
+            volume_name.as_ptr(),
+            names.as_mut_ptr(),
+            names.len() as u32,
+            &mut len,
+        ) == 0
+        {
+            if std::io::Error::last_os_error().raw_os_error() != Some(ERROR_MORE_DATA as i32) {
+                return Vec::new();
+            }
+            names = vec![0u16; len as usize];
+            if GetVolumePathNamesForVolumeNameW(
+                volume_name.as_ptr(),
+                names.as_mut_ptr(),
+                names.len() as u32,
+                &mut len,
+            ) == 0
+            {
+                return Vec::new();
+            }
+        }
+        names.truncate(len as usize);
+
+        let mut fs_name = [0u16; 261];
+        let mut flags = 0u32;
+        let fs_type = if GetVolumeInformationW(
+            volume_name.as_ptr(),
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut flags,
+            fs_name.as_mut_ptr(),
+            fs_name.len() as u32,
+        ) != 0
+        {
+            let fs_len = fs_name.iter().position(|&c| c == 0).unwrap_or(fs_name.len());
+            String::from_utf16_lossy(&fs_name[..fs_len])
+        } else {
+            String::new()
+        };
+        let read_only = flags & FILE_READ_ONLY_VOLUME != 0;
+
+        names
+            .split(|&c| c == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| MountPoint {
+                path: String::from_utf16_lossy(name),
+                fs_type: fs_type.clone(),
+                options: vec![if read_only { "ro" } else { "rw" }.to_string()],
+                read_only,
+                root: "\\".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// The mount point `target` names, ending with a backslash: "E", "E:" and
+/// "E:\" are drive E, anything else must be the absolute path of a folder
+/// a volume is mounted in
+fn mount_point_of(target: &str) -> Result<String> {
+    let trimmed = target.trim_end_matches(['\\', '/']);
+    let letter = trimmed.strip_suffix(':').unwrap_or(trimmed);
+    let mut chars = letter.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return Ok(format!("{}:\\", c.to_ascii_uppercase()));
+        }
+    }
+    if !trimmed.is_empty() && Path::new(trimmed).is_absolute() {
+        return Ok(format!("{}\\", trimmed));
+    }
+    Err(DiskError::new(
+        ErrorCode::InvalidArgument,
+        format!("{} is neither a drive letter nor a mount folder", target),
+    )
+    .on_partition(target)
+    .into())
+}
+
+/// The `\\?\Volume{GUID}\` name of the volume mounted at `mount_point`,
+/// which must end with a backslash
+fn volume_name_of(mount_point: &str) -> Result<String> {
+    use winapi::um::fileapi::GetVolumeNameForVolumeMountPointW;
+
+    let wide_mount_point: Vec<u16> = OsStr::new(mount_point).encode_wide().chain(once(0)).collect();
+    let mut volume_name = [0u16; 50];
+    unsafe {
+        if GetVolumeNameForVolumeMountPointW(
+            wide_mount_point.as_ptr(),
+            volume_name.as_mut_ptr(),
+            volume_name.len() as u32,
+        ) == 0
+        {
+            return Err(DiskError::new(
+                ErrorCode::NotMounted,
+                format!("No volume is mounted at {}", mount_point),
+            )
+            .on_partition(mount_point)
+            .into());
+        }
+    }
+    let len = volume_name.iter().position(|&c| c == 0).unwrap_or(volume_name.len());
+    Ok(String::from_utf16_lossy(&volume_name[..len]))
+}
+
+/// Take the drive letter or folder `mount_point` off its volume, which
+/// stays mounted at any others it has
+fn remove_mount_point(mount_point: &str) -> Result<()> {
+    use winapi::um::winbase::DeleteVolumeMountPointW;
+
+    let wide_mount_point: Vec<u16> = OsStr::new(mount_point).encode_wide().chain(once(0)).collect();
+    unsafe {
+        if DeleteVolumeMountPointW(wide_mount_point.as_ptr()) == 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to remove {}", mount_point));
+        }
+    }
     Ok(())
 }
 
-pub fn mount_partition(disk_id: String, partition_number: u32, letter: Option<char>) -> Result<Option<char>> {
+// Dismount the volume even though files are open on it; the open handles
+// become invalid
+fn force_dismount(volume_name: &str) -> Result<()> {
+    // CreateFileW opens a volume by its name without the trailing backslash
+    let path = volume_name.trim_end_matches('\\');
+    let wide_path: Vec<u16> = OsStr::new(path).encode_wide().chain(once(0)).collect();
+
+    unsafe {
+        let handle = CreateFileW(
+            wide_path.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        );
+
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to open volume {}", path));
+        }
+
+        let mut bytes_returned = 0u32;
+        let success = DeviceIoControl(
+            handle,
+            FSCTL_DISMOUNT_VOLUME,
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            0,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        );
+
+        CloseHandle(handle);
+
+        if success == 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to dismount {}", path));
+        }
+    }
+    Ok(())
+}
+
+/// Unmount the volume at `mount_point`, a drive root or folder ending with
+/// a backslash
+fn unmount_volume(mount_point: &str, mode: UnmountMode) -> Result<()> {
+    match mode {
+        UnmountMode::Normal => remove_mount_point(mount_point),
+        UnmountMode::Force => {
+            // The volume at the mount point, not the drive the folder is on
+            force_dismount(&volume_name_of(mount_point)?)?;
+            remove_mount_point(mount_point)
+        }
+        UnmountMode::Lazy | UnmountMode::RemountReadOnly => Err(DiskError::new(
+            ErrorCode::Unsupported,
+            format!(
+                "Cannot unmount {}: {:?} is not supported on Windows",
+                mount_point, mode
+            ),
+        )
+        .on_partition(mount_point)
+        .into()),
+    }
+}
+
+/// # Errors
+///
+/// Fails when `target` is neither a drive letter nor an absolute folder
+/// path; a volume that stays mounted is a failed step
+pub fn unmount_partition(target: String, mode: UnmountMode) -> Result<Vec<StepReport>> {
+    let mount_point = mount_point_of(&target)?;
+    let result = unmount_volume(&mount_point, mode);
+    Ok(vec![step_report(OfflineStep::Unmount, mount_point, result)])
+}
+
+/// # Errors
+///
+/// Fails when `disk_id` is not a disk number or its partitions cannot
+/// be listed
+pub fn unmount_disk(disk_id: String, mode: UnmountMode) -> Result<Vec<StepReport>> {
+    // Volumes on a basic disk do not nest, so the order does not matter.
+    // Every mount point is removed, drive letters and folders alike.
+    let disk_number = disk_id.parse::<u32>()?;
+    let mut results = Vec::new();
+    for partition in get_partitions(disk_number)? {
+        for mount in &partition.mount_points {
+            let mount_point = format!("{}\\", mount.path.trim_end_matches('\\'));
+            let result = unmount_volume(&mount_point, mode);
+            results.push(step_report(OfflineStep::Unmount, mount_point, result));
+        }
+    }
+    Ok(results)
+}
+
+/// # Errors
+///
+/// Fails for Linux mount options, for a system or non-empty mount folder,
+/// and when the letter or folder cannot be assigned
+pub fn mount_partition(
+    disk_id: String,
+    partition_number: u32,
+    letter: Option<char>,
+    options: MountOptions,
+) -> Result<MountedPartition> {
+    // diskpart can mount a volume in a folder; the filesystem options are
+    // Linux mount options with no equivalent here
+    let target_only = MountOptions {
+        target: options.target.clone(),
+        ..MountOptions::default()
+    };
+    if options != target_only {
+        return Err(anyhow::anyhow!(
+            "Only the target folder can be chosen when mounting on Windows"
+        ));
+    }
+    if let Some(target) = &options.target {
+        let mount_point = assign_mount_folder(disk_id.parse::<u32>()?, partition_number, target)?;
+        return Ok(MountedPartition {
+            letter: None,
+            mount_point: Some(mount_point),
+        });
+    }
+    let letter = assign_drive_letter(disk_id, partition_number, letter)?;
+    Ok(MountedPartition {
+        letter,
+        mount_point: letter.map(|l| format!("{}:\\", l)),
+    })
+}
+
+/// Mount the partition's volume in the empty folder `target`, created if
+/// missing, with `SetVolumeMountPointW` rather than a diskpart script.
+/// Returns the folder it was mounted in, with links resolved.
+fn assign_mount_folder(disk_number: u32, partition_number: u32, target: &str) -> Result<String> {
+    use winapi::um::winbase::SetVolumeMountPointW;
+
+    let folder = resolve_mount_folder(target)?;
+    let volume_name = volume_name_of_partition(disk_number, partition_number)?;
+    std::fs::create_dir_all(&folder)
+        .with_context(|| format!("Failed to create {}", folder.display()))?;
+    if std::fs::read_dir(&folder)?.next().is_some() {
+        return Err(DiskError::new(
+            ErrorCode::InvalidArgument,
+            format!("Mount folder {} is not empty", folder.display()),
+        )
+        .into());
+    }
+
+    // Both paths must end with a backslash
+    let mount_point = format!("{}\\", folder.display().to_string().trim_end_matches('\\'));
+    let wide_mount_point: Vec<u16> = OsStr::new(&mount_point).encode_wide().chain(once(0)).collect();
+    let wide_volume: Vec<u16> = OsStr::new(&volume_name).encode_wide().chain(once(0)).collect();
+    unsafe {
+        if SetVolumeMountPointW(wide_mount_point.as_ptr(), wide_volume.as_ptr()) == 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to mount the volume in {}", mount_point));
+        }
+    }
+    Ok(mount_point)
+}
+
+/// Where `target` really is once junctions and symlinks in the part that
+/// exists are resolved, refused when it names a system folder. Quotes and
+/// control characters are refused outright.
+fn resolve_mount_folder(target: &str) -> Result<PathBuf> {
+    if target.chars().any(|c| c.is_control() || c == '"') {
+        return Err(DiskError::new(
+            ErrorCode::InvalidArgument,
+            "Mount folder must not contain quotes or control characters",
+        )
+        .into());
+    }
+    let path = Path::new(target);
+    if !path.is_absolute()
+        || path
+            .components()
+            .any(|c| matches!(c, Component::CurDir | Component::ParentDir))
+    {
+        return Err(DiskError::new(
+            ErrorCode::InvalidArgument,
+            format!("Mount folder {} must be an absolute path without . or ..", target),
+        )
+        .into());
+    }
+
+    // Canonicalize the deepest folder that exists, then add back the rest
+    let mut existing = path;
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        missing.push(existing.file_name().unwrap_or_default());
+        existing = existing.parent().unwrap_or(existing);
+        if existing.parent().is_none() {
+            break;
+        }
+    }
+    let canonical = std::fs::canonicalize(existing)
+        .with_context(|| format!("Failed to resolve {}", existing.display()))?;
+    let mut resolved = match canonical.to_str().and_then(|s| s.strip_prefix("\\\\?\\")) {
+        Some(stripped) => PathBuf::from(stripped),
+        None => canonical,
+    };
+    for name in missing.into_iter().rev() {
+        resolved.push(name);
+    }
+
+    if is_system_folder(&resolved) {
+        return Err(DiskError::new(
+            ErrorCode::InvalidArgument,
+            format!("Refusing to mount in {}", resolved.display()),
+        )
+        .with_hint("Pick an empty folder of your own, e.g. C:\\Mounts\\Stick")
+        .into());
+    }
+    Ok(resolved)
+}
+
+/// Drive roots, the Users folder and the user's profile, and the Windows
+/// and program folders with everything inside them
+fn is_system_folder(path: &Path) -> bool {
+    let lower = |p: &Path| PathBuf::from(p.to_string_lossy().to_lowercase());
+    let path = lower(path);
+    if path.parent().is_none() || path.parent() == Some(Path::new("")) {
+        return true;
+    }
+    let var = |name: &str| std::env::var_os(name).map(|v| lower(Path::new(&v)));
+    let system_drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+    let exact = [
+        Some(lower(Path::new(&format!("{}\\Users", system_drive)))),
+        var("USERPROFILE"),
+    ];
+    let trees = [
+        var("SystemRoot"),
+        var("ProgramFiles"),
+        var("ProgramFiles(x86)"),
+        var("ProgramData"),
+    ];
+    exact.iter().flatten().any(|dir| path == *dir)
+        || trees.iter().flatten().any(|tree| path.starts_with(tree))
+}
+
+/// The `\\?\Volume{GUID}\` name of a partition's volume, whether or not it
+/// is mounted anywhere
+fn volume_name_of_partition(disk_number: u32, partition_number: u32) -> Result<String> {
+    let offset = get_partitions_layout(disk_number)?
+        .iter()
+        .find(|p| p.partition_number == partition_number)
+        .and_then(|p| u64::from_str_radix(&p.partition_id, 16).ok());
+    let Some(offset) = offset else {
+        return Err(DiskError::new(
+            ErrorCode::NotFound,
+            format!("Partition {} of disk {} not found", partition_number, disk_number),
+        )
+        .into());
+    };
+    for volume_path in volume_paths() {
+        if let Ok(info) = get_partition_on_disk(&volume_path, disk_number, "") {
+            if u64::from_str_radix(&info.partition_id, 16).ok() == Some(offset) {
+                return Ok(format!("{}\\", volume_path));
+            }
+        }
+    }
+    Err(DiskError::new(
+        ErrorCode::NotFound,
+        format!("Partition {} of disk {} holds no volume", partition_number, disk_number),
+    )
+    .into())
+}
+
+fn assign_drive_letter(
+    disk_id: String,
+    partition_number: u32,
+    letter: Option<char>,
+) -> Result<Option<char>> {
     let disk_number = disk_id.parse::<u32>()?;
     let assign_cmd = if let Some(l) = letter {
         format!("assign letter={}", l)
@@ -1053,6 +1558,31 @@ pub fn mount_partition(disk_id: String, partition_number: u32, letter: Option<ch
     Ok(letter)
 }
 
+/// # Errors
+///
+/// Always fails; Windows does not say what holds a volume
+pub fn diagnose_busy(drive_letter: String) -> Result<BusyReport> {
+    // Finding the processes with open handles on a volume needs the
+    // Restart Manager or a kernel handle walk, neither is done here
+    Err(anyhow::anyhow!(
+        "Cannot list the processes using {}: not supported on Windows",
+        drive_letter
+    ))
+}
+
+/// # Errors
+///
+/// Always fails; every mounted volume is reached through a disk in the
+/// inventory, so a target that none holds is not mounted
+pub fn target_protection(target: String) -> Result<Vec<ProtectionReason>> {
+    Err(DiskError::new(
+        ErrorCode::NotMounted,
+        format!("{} is not a mounted volume", target),
+    )
+    .on_partition(&target)
+    .into())
+}
+
 pub fn get_available_drive_letters() -> Vec<String> {
     let mut available = Vec::new();
     unsafe {
@@ -1090,24 +1620,420 @@ fn run_diskpart_script_output(script: &str) -> Result<String> {
     Ok(stdout)
 }
 
+/// # Errors
+///
+/// Fails when `disk_id` is not a disk number or diskpart fails
 pub fn set_disk_online(disk_id: String) -> Result<()> {
     let disk_number = disk_id.parse::<u32>()?;
     execute_disk_command(disk_number, "online")
 }
 
-pub fn set_disk_offline(disk_id: String) -> Result<()> {
+/// # Errors
+///
+/// Fails when `disk_id` is not a disk number
+pub fn set_disk_offline(disk_id: String) -> Result<OfflineReport> {
+    // Windows dismounts the volumes and flushes itself when a disk goes
+    // offline, so there is a single step to report
     let disk_number = disk_id.parse::<u32>()?;
-    execute_disk_command(disk_number, "offline")
+    let result = execute_disk_command(disk_number, "offline");
+    let completed = result.is_ok();
+    Ok(OfflineReport {
+        disk_id: disk_id.clone(),
+        method: None,
+        steps: vec![StepReport {
+            step: OfflineStep::WriteState,
+            target: format!("Disk {}", disk_id),
+            status: if completed {
+                StepStatus::Done
+            } else {
+                StepStatus::Failed
+            },
+            message: result.err().map(|e| format!("{:#}", e)),
+        }],
+        completed,
+        bring_back: Vec::new(),
+        blockers: Vec::new(),
+    })
+}
+
+fn step_report(step: OfflineStep, target: String, result: Result<()>) -> StepReport {
+    StepReport {
+        step,
+        target,
+        status: if result.is_ok() {
+            StepStatus::Done
+        } else {
+            StepStatus::Failed
+        },
+        message: result.err().map(|e| format!("{:#}", e)),
+    }
 }
 
-#[allow(dead_code)]
-pub fn eject_disk(disk_id: String) -> Result<()> {
-    // Windows eject implementation (currently unused)
-    let _disk_number = disk_id.parse::<u32>()?;
-    // Placeholder: actual eject logic would go here
+/// Lock the volume `volume_path` (`\\?\Volume{GUID}`, no trailing
+/// backslash) and dismount it. A volume that cannot be locked has files
+/// open on it and stays mounted.
+fn lock_and_dismount(volume_path: &str) -> Result<()> {
+    let wide_path: Vec<u16> = OsStr::new(volume_path).encode_wide().chain(once(0)).collect();
+
+    unsafe {
+        let handle = CreateFileW(
+            wide_path.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        );
+
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to open volume {}", volume_path));
+        }
+
+        let mut bytes_returned = 0u32;
+        let locked = DeviceIoControl(
+            handle,
+            FSCTL_LOCK_VOLUME,
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            0,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        );
+        if locked == 0 {
+            let error = std::io::Error::last_os_error();
+            CloseHandle(handle);
+            return Err(DiskError::new(
+                ErrorCode::Busy,
+                format!("{} is in use: {}", volume_path, error),
+            )
+            .on_partition(volume_path)
+            .with_hint("Close the files and programs using it, then eject again")
+            .into());
+        }
+
+        let dismounted = DeviceIoControl(
+            handle,
+            FSCTL_DISMOUNT_VOLUME,
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            0,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        );
+        let error = std::io::Error::last_os_error();
+
+        // Closing the handle releases the lock; an open handle of our own
+        // would veto the eject
+        CloseHandle(handle);
+
+        if dismounted == 0 {
+            return Err(error).with_context(|| format!("Failed to dismount {}", volume_path));
+        }
+    }
     Ok(())
 }
 
+/// Disk number of the device at `device_path`, a NUL-terminated interface
+/// path from SetupDi
+unsafe fn device_number(device_path: *const u16) -> Option<u32> {
+    let handle = CreateFileW(
+        device_path,
+        0,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        std::ptr::null_mut(),
+        OPEN_EXISTING,
+        0,
+        std::ptr::null_mut(),
+    );
+    if handle == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    let mut number: STORAGE_DEVICE_NUMBER = mem::zeroed();
+    let mut bytes_returned = 0u32;
+    let success = DeviceIoControl(
+        handle,
+        IOCTL_STORAGE_GET_DEVICE_NUMBER,
+        std::ptr::null_mut(),
+        0,
+        &mut number as *mut _ as *mut _,
+        mem::size_of::<STORAGE_DEVICE_NUMBER>() as u32,
+        &mut bytes_returned,
+        std::ptr::null_mut(),
+    );
+    CloseHandle(handle);
+
+    (success != 0).then_some(number.DeviceNumber)
+}
+
+/// The Plug and Play device node of disk `disk_number`
+fn disk_device_node(disk_number: u32) -> Result<u32> {
+    use winapi::um::setupapi::{
+        SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInterfaces, SetupDiGetClassDevsW,
+        SetupDiGetDeviceInterfaceDetailW, DIGCF_DEVICEINTERFACE, DIGCF_PRESENT,
+        SP_DEVICE_INTERFACE_DATA, SP_DEVICE_INTERFACE_DETAIL_DATA_W, SP_DEVINFO_DATA,
+    };
+    use winapi::um::winioctl::GUID_DEVINTERFACE_DISK;
+
+    unsafe {
+        let devices = SetupDiGetClassDevsW(
+            &GUID_DEVINTERFACE_DISK,
+            std::ptr::null(),
+            std::ptr::null_mut(),
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        );
+        if devices == INVALID_HANDLE_VALUE {
+            return Err(std::io::Error::last_os_error()).context("Failed to list disk devices");
+        }
+
+        let mut found = None;
+        let mut index = 0;
+        loop {
+            let mut interface: SP_DEVICE_INTERFACE_DATA = mem::zeroed();
+            interface.cbSize = mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32;
+            if SetupDiEnumDeviceInterfaces(
+                devices,
+                std::ptr::null_mut(),
+                &GUID_DEVINTERFACE_DISK,
+                index,
+                &mut interface,
+            ) == 0
+            {
+                break;
+            }
+            index += 1;
+
+            // The first call only asks for the size of the detail data
+            let mut size = 0u32;
+            SetupDiGetDeviceInterfaceDetailW(
+                devices,
+                &mut interface,
+                std::ptr::null_mut(),
+                0,
+                &mut size,
+                std::ptr::null_mut(),
+            );
+            if size == 0 {
+                continue;
+            }
+            let mut buffer = vec![0u32; (size as usize + 3) / 4];
+            let detail = buffer.as_mut_ptr() as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W;
+            (*detail).cbSize = mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+            let mut device: SP_DEVINFO_DATA = mem::zeroed();
+            device.cbSize = mem::size_of::<SP_DEVINFO_DATA>() as u32;
+            if SetupDiGetDeviceInterfaceDetailW(
+                devices,
+                &mut interface,
+                detail,
+                size,
+                std::ptr::null_mut(),
+                &mut device,
+            ) == 0
+            {
+                continue;
+            }
+
+            let path = std::ptr::addr_of!((*detail).DevicePath) as *const u16;
+            if device_number(path) == Some(disk_number) {
+                found = Some(device.DevInst);
+                break;
+            }
+        }
+        SetupDiDestroyDeviceInfoList(devices);
+
+        found.ok_or_else(|| {
+            DiskError::new(
+                ErrorCode::NotFound,
+                format!("Disk {} has no Plug and Play device", disk_number),
+            )
+            .on_disk(disk_number.to_string())
+            .into()
+        })
+    }
+}
+
+fn is_removable(device_node: u32) -> bool {
+    use winapi::um::cfgmgr32::{CM_Get_DevNode_Registry_PropertyW, CR_SUCCESS};
+
+    let mut capabilities = 0u32;
+    let mut len = mem::size_of::<u32>() as u32;
+    unsafe {
+        CM_Get_DevNode_Registry_PropertyW(
+            device_node,
+            CM_DRP_CAPABILITIES,
+            std::ptr::null_mut(),
+            &mut capabilities as *mut u32 as *mut _,
+            &mut len,
+            0,
+        ) == CR_SUCCESS
+            && capabilities & CM_DEVCAP_REMOVABLE != 0
+    }
+}
+
+/// What a PNP_VETO_TYPE says kept a device from being removed
+fn veto_reason(veto_type: u32) -> &'static str {
+    match veto_type {
+        1 | 11 => "a legacy driver",
+        2 => "a close is still pending",
+        3 => "an application",
+        4 => "a service",
+        5 => "a file is still open",
+        6 => "another device",
+        7 => "its driver",
+        8 => "the request is not allowed",
+        9 => "not enough power",
+        10 => "it cannot be disabled",
+        12 => "insufficient rights",
+        13 => "it is already removed",
+        _ => "an unknown reason",
+    }
+}
+
+/// Ask Plug and Play to eject disk `disk_number`. The request goes to the
+/// nearest removable device node, the disk itself or the USB device it
+/// hangs off, so that the device is powered down and can be unplugged.
+fn request_eject(disk_number: u32) -> Result<()> {
+    use winapi::um::cfgmgr32::{CM_Get_Parent, CM_Request_Device_EjectW, CR_SUCCESS};
+
+    let disk = disk_device_node(disk_number)?;
+    let mut target = disk;
+    let mut node = disk;
+    loop {
+        if is_removable(node) {
+            target = node;
+            break;
+        }
+        let mut parent = 0u32;
+        if unsafe { CM_Get_Parent(&mut parent, node, 0) } != CR_SUCCESS {
+            break;
+        }
+        node = parent;
+    }
+
+    // With a buffer for the veto name Windows reports the veto to us
+    // instead of showing it to the user
+    let mut veto_type = 0u32;
+    let mut veto_name = [0u16; 260];
+    let result = unsafe {
+        CM_Request_Device_EjectW(
+            target,
+            &mut veto_type,
+            veto_name.as_mut_ptr(),
+            veto_name.len() as u32,
+            0,
+        )
+    };
+    if result == CR_SUCCESS && veto_type == 0 {
+        return Ok(());
+    }
+
+    let len = veto_name.iter().position(|&c| c == 0).unwrap_or(veto_name.len());
+    let vetoed_by = String::from_utf16_lossy(&veto_name[..len]);
+    let message = if veto_type == 0 {
+        format!("Windows refused to eject disk {} (error {:#x})", disk_number, result)
+    } else if vetoed_by.is_empty() {
+        format!("Windows refused to eject disk {}: {}", disk_number, veto_reason(veto_type))
+    } else {
+        format!(
+            "Windows refused to eject disk {}: {} ({})",
+            disk_number,
+            veto_reason(veto_type),
+            vetoed_by
+        )
+    };
+    Err(DiskError::new(ErrorCode::Busy, message)
+        .on_disk(disk_number.to_string())
+        .into())
+}
+
+/// # Errors
+///
+/// Fails when `disk_id` is not a disk number
+pub fn eject_disk(disk_id: String) -> Result<OfflineReport> {
+    // Lock and dismount every volume on the disk, with or without a drive
+    // letter, so nothing is left with unwritten data, then ask Plug and
+    // Play to remove the device
+    let disk_number = disk_id.parse::<u32>()?;
+    let mut steps = Vec::new();
+    let mut completed = true;
+    for volume_path in volume_paths() {
+        let Ok(info) = get_partition_on_disk(&volume_path, disk_number, "") else {
+            continue;
+        };
+        let target = info
+            .mount_points
+            .first()
+            .map_or_else(|| volume_path.clone(), |m| m.path.clone());
+        let result = lock_and_dismount(&volume_path);
+        completed = result.is_ok();
+        steps.push(step_report(OfflineStep::Unmount, target, result));
+        if !completed {
+            break;
+        }
+    }
+    if completed {
+        let result = request_eject(disk_number);
+        completed = result.is_ok();
+        let target = format!("Disk {}", disk_id);
+        steps.push(step_report(OfflineStep::PowerOff, target, result));
+    }
+    Ok(OfflineReport {
+        disk_id,
+        method: None,
+        steps,
+        completed,
+        bring_back: Vec::new(),
+        blockers: Vec::new(),
+    })
+}
+
+/// # Errors
+///
+/// Fails when the disks cannot be enumerated around the rescan
+pub fn rescan_storage() -> Result<RescanReport> {
+    // diskpart's rescan makes the storage stack look for new disks on
+    // every bus
+    let before: Vec<String> = enumerate_disks()?.into_iter().map(|d| d.id).collect();
+    let errors = match run_diskpart_script_output("rescan\nexit\n") {
+        Ok(_) => Vec::new(),
+        Err(e) => vec![e.to_string()],
+    };
+    let reappeared = enumerate_disks()?
+        .into_iter()
+        .filter(|d| !before.contains(&d.id))
+        .collect();
+    Ok(RescanReport {
+        scanned: Vec::new(),
+        errors,
+        reappeared,
+    })
+}
+
+// What Windows lets us do with a disk. The disk Windows boots from cannot be
+// taken offline. USB disks are ejected by dismounting their volumes and
+// asking Plug and Play to remove the device.
+fn disk_capabilities(
+    disk_type: &DiskType,
+    is_online: bool,
+    is_system_disk: bool,
+) -> DiskCapabilities {
+    let usb = matches!(disk_type, DiskType::USBFlash | DiskType::ExtHDD);
+    DiskCapabilities {
+        can_offline: is_online && !is_system_disk,
+        can_online: !is_online,
+        can_eject: usb,
+        can_power_off: false,
+        can_spin_down: false,
+        supports_smart: !usb,
+        supports_trim: matches!(disk_type, DiskType::SSD | DiskType::NVMe),
+        is_read_only: false,
+    }
+}
+
 // Determine disk type based on physical drive properties
 fn get_disk_type(disk_number: u32, _partitions: &Vec<PartitionInfo>) -> DiskType {
     // Windows detection uses bus type and removable flag.
@@ -1295,15 +2221,14 @@ fn get_system_disk_number() -> Option<u32> {
     None
 }
 
-pub fn get_system_info() -> Result<SystemInfo> {
-    let disks = enumerate_disks()?;
-    let total_disks = disks.len();
-    let total_capacity_bytes = disks.iter().map(|d| d.size_bytes).sum();
-    let system_disk_id = disks
-        .iter()
-        .find(|d| d.is_system_disk)
-        .map(|d| d.id.clone());
+/// Caption and version of the running Windows, asked once since neither
+/// changes while the app runs and PowerShell is slow to start
+fn os_release() -> &'static (String, String) {
+    static RELEASE: OnceLock<(String, String)> = OnceLock::new();
+    RELEASE.get_or_init(query_os_release)
+}
 
+fn query_os_release() -> (String, String) {
     let mut os_name = "Windows".to_string();
     let mut os_version = "Unknown".to_string();
 
@@ -1335,6 +2260,23 @@ pub fn get_system_info() -> Result<SystemInfo> {
         }
     }
 
+    (os_name, os_version)
+}
+
+/// System facts, with the disk figures taken from `disks`
+///
+/// # Errors
+///
+/// Never fails; a version that cannot be read is reported as Unknown
+pub fn get_system_info(disks: &[DiskInfo]) -> Result<SystemInfo> {
+    let total_disks = disks.len();
+    let total_capacity_bytes = disks.iter().map(|d| d.size_bytes).sum();
+    let system_disk_id = disks
+        .iter()
+        .find(|d| d.is_system_disk)
+        .map(|d| d.id.clone());
+    let (os_name, os_version) = os_release().clone();
+
     Ok(SystemInfo {
         os_name,
         os_version,
@@ -1344,3 +2286,58 @@ pub fn get_system_info() -> Result<SystemInfo> {
         system_disk_id,
     })
 }
+
+/// The commands' view of this module
+pub struct WindowsBackend;
+
+impl DiskBackend for WindowsBackend {
+    fn enumerate_disks(&self) -> Result<Vec<DiskInfo>> {
+        enumerate_disks()
+    }
+
+    fn system_info(&self, disks: &[DiskInfo]) -> Result<SystemInfo> {
+        get_system_info(disks)
+    }
+
+    fn set_disk_online(&self, disk_id: &str) -> Result<()> {
+        set_disk_online(disk_id.to_string())
+    }
+
+    fn set_disk_offline(&self, disk_id: &str) -> Result<OfflineReport> {
+        set_disk_offline(disk_id.to_string())
+    }
+
+    fn eject_disk(&self, disk_id: &str) -> Result<OfflineReport> {
+        eject_disk(disk_id.to_string())
+    }
+
+    fn rescan_storage(&self) -> Result<RescanReport> {
+        rescan_storage()
+    }
+
+    fn mount_partition(
+        &self,
+        disk_id: &str,
+        partition_number: u32,
+        letter: Option<char>,
+        options: &MountOptions,
+    ) -> Result<MountedPartition> {
+        mount_partition(disk_id.to_string(), partition_number, letter, options.clone())
+    }
+
+    fn unmount_partition(&self, target: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+        unmount_partition(target.to_string(), mode)
+    }
+
+    fn unmount_disk(&self, disk_id: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+        unmount_disk(disk_id.to_string(), mode)
+    }
+
+    fn diagnose_busy(&self, target: &str) -> Result<BusyReport> {
+        diagnose_busy(target.to_string())
+    }
+
+    fn target_protection(&self, target: &str) -> Result<Vec<ProtectionReason>> {
+        target_protection(target.to_string())
+    }
+}
@@ -0,0 +1,304 @@
+// src/disk_operations/hotplug.rs
+// Noticing disks come, go and change without the frontend polling.
+//
+// The kernel broadcasts a uevent on the NETLINK_KOBJECT_UEVENT socket for
+// every device added, removed or changed; block device events trigger a
+// new inventory. Mounts are not devices, so /proc/self/mountinfo is polled
+// for POLLPRI, which the kernel raises when the mount table changes.
+// Without the netlink socket (containers, seccomp) the inventory is taken
+// every POLL_INTERVAL instead, and with it every RESYNC_INTERVAL to catch
+// changes no uevent announces, such as a SCSI device/state write.
+//
+// Each inventory is diffed against the previous one into `HotplugEvent`s.
+use super::sysfs::SysfsRoot;
+use crate::structs::{DiskInfo, PartitionMounted};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const RESYNC_INTERVAL: Duration = Duration::from_secs(10);
+/// How long to wait for the rest of a burst, e.g. a disk and then each
+/// of its partitions
+const SETTLE: Duration = Duration::from_millis(300);
+
+/// A disk inventory change. Serializes as its payload alone; `name` is the
+/// Tauri event it is emitted as.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum HotplugEvent {
+    DiskAdded(DiskInfo),
+    /// Carries the disk as it was last seen
+    DiskRemoved(DiskInfo),
+    DiskChanged(DiskInfo),
+    PartitionMounted(PartitionMounted),
+}
+
+impl HotplugEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DiskAdded(_) => "disk-added",
+            Self::DiskRemoved(_) => "disk-removed",
+            Self::DiskChanged(_) => "disk-changed",
+            Self::PartitionMounted(_) => "partition-mounted",
+        }
+    }
+
+    pub fn disk(&self) -> &DiskInfo {
+        match self {
+            Self::DiskAdded(disk) | Self::DiskRemoved(disk) | Self::DiskChanged(disk) => disk,
+            Self::PartitionMounted(mounted) => &mounted.disk,
+        }
+    }
+}
+
+/// A kernel uevent, e.g. add@/devices/.../block/sdb with SUBSYSTEM=block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uevent {
+    pub action: String,
+    pub devpath: String,
+    pub subsystem: String,
+    pub devname: Option<String>,
+    /// "disk" or "partition" for block devices
+    pub devtype: Option<String>,
+}
+
+/// Parse a kernel uevent datagram: "action@devpath" followed by KEY=value
+/// lines, all NUL-terminated. udev's own rebroadcasts (starting with
+/// "libudev") and anything malformed give None.
+pub fn parse_uevent(datagram: &[u8]) -> Option<Uevent> {
+    let text = std::str::from_utf8(datagram).ok()?;
+    let mut fields = text.split('\0');
+    let header = fields.next()?;
+    let (_, header_path) = header.split_once('@')?;
+    let values: HashMap<&str, &str> = fields.filter_map(|f| f.split_once('=')).collect();
+    Some(Uevent {
+        action: String::from(*values.get("ACTION")?),
+        devpath: values
+            .get("DEVPATH")
+            .map_or_else(|| header_path.to_string(), ToString::to_string),
+        subsystem: String::from(*values.get("SUBSYSTEM")?),
+        devname: values.get("DEVNAME").map(ToString::to_string),
+        devtype: values.get("DEVTYPE").map(ToString::to_string),
+    })
+}
+
+/// Everything an event is about, leaving out usage figures, which move with
+/// every write and would make each inventory look changed
+fn fingerprint(disk: &DiskInfo) -> impl PartialEq + '_ {
+    let partitions: Vec<_> = disk
+        .partitions
+        .iter()
+        .map(|p| {
+            let mounts: Vec<&str> = p.mount_points.iter().map(|m| m.path.as_str()).collect();
+            (
+                p.partition_number,
+                p.size_bytes,
+                &p.fs_type,
+                &p.label,
+                &p.uuid,
+                mounts,
+            )
+        })
+        .collect();
+    (
+        (&disk.model, disk.size_bytes, disk.is_online),
+        (&disk.protection, disk.capabilities, partitions),
+    )
+}
+
+/// Mount points of `disk` by partition number
+fn mounts_of(disk: &DiskInfo) -> HashMap<u32, Vec<&str>> {
+    disk.partitions
+        .iter()
+        .map(|p| {
+            let paths = p.mount_points.iter().map(|m| m.path.as_str()).collect();
+            (p.partition_number, paths)
+        })
+        .collect()
+}
+
+/// Events turning inventory `before` into `after`: removals first, then per
+/// disk in `after` order an addition, or a change followed by a
+/// `PartitionMounted` for each new mount point
+pub fn diff(before: &[DiskInfo], after: &[DiskInfo]) -> Vec<HotplugEvent> {
+    let mut events: Vec<HotplugEvent> = before
+        .iter()
+        .filter(|old| !after.iter().any(|d| d.id == old.id))
+        .map(|old| HotplugEvent::DiskRemoved(old.clone()))
+        .collect();
+
+    for disk in after {
+        let Some(old) = before.iter().find(|d| d.id == disk.id) else {
+            events.push(HotplugEvent::DiskAdded(disk.clone()));
+            continue;
+        };
+        if fingerprint(old) == fingerprint(disk) {
+            continue;
+        }
+        events.push(HotplugEvent::DiskChanged(disk.clone()));
+
+        let old_mounts = mounts_of(old);
+        for partition in &disk.partitions {
+            let known = old_mounts.get(&partition.partition_number);
+            for mount in &partition.mount_points {
+                if known.is_some_and(|paths| paths.contains(&mount.path.as_str())) {
+                    continue;
+                }
+                events.push(HotplugEvent::PartitionMounted(PartitionMounted {
+                    disk: disk.clone(),
+                    partition_number: partition.partition_number,
+                    mount_point: mount.path.clone(),
+                }));
+            }
+        }
+    }
+    events
+}
+
+/// The last inventory, turning each new one into events
+pub struct Tracker {
+    disks: Vec<DiskInfo>,
+}
+
+impl Tracker {
+    pub fn new(disks: Vec<DiskInfo>) -> Self {
+        Self { disks }
+    }
+
+    pub fn update(&mut self, disks: Vec<DiskInfo>) -> Vec<HotplugEvent> {
+        let events = diff(&self.disks, &disks);
+        self.disks = disks;
+        events
+    }
+}
+
+/// The kernel's uevent broadcast
+struct UeventSocket {
+    fd: OwnedFd,
+}
+
+impl UeventSocket {
+    fn open() -> std::io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        address.nl_family = libc::sa_family_t::try_from(libc::AF_NETLINK).unwrap_or_default();
+        // Group 1 carries the kernel's own events
+        address.nl_groups = 1;
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                std::ptr::addr_of!(address).cast(),
+                libc::socklen_t::try_from(std::mem::size_of::<libc::sockaddr_nl>())
+                    .unwrap_or_default(),
+            )
+        };
+        if res != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Read the pending uevent, None for anything that is not one
+    fn receive(&self) -> Option<Uevent> {
+        let mut buf = [0u8; 8192];
+        let len = unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        let len = usize::try_from(len).ok()?;
+        parse_uevent(&buf[..len])
+    }
+}
+
+/// Wait until one of `fds` reports `events`, or `timeout`. Returns the
+/// revents of each.
+fn poll(fds: &[(RawFd, libc::c_short)], timeout: Duration) -> Vec<libc::c_short> {
+    let mut pollfds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&(fd, events)| libc::pollfd {
+            fd,
+            events,
+            revents: 0,
+        })
+        .collect();
+    let timeout = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+    let count = libc::nfds_t::try_from(pollfds.len()).unwrap_or_default();
+    if unsafe { libc::poll(pollfds.as_mut_ptr(), count, timeout) } < 0 {
+        return vec![0; fds.len()];
+    }
+    pollfds.iter().map(|p| p.revents).collect()
+}
+
+/// Block until the inventory may have changed: a block device uevent, a
+/// mount table change, or the interval passing
+fn wait_for_change(uevents: Option<&UeventSocket>, mountinfo: Option<&File>) {
+    let mut fds = Vec::new();
+    if let Some(mountinfo) = mountinfo {
+        fds.push((mountinfo.as_raw_fd(), libc::POLLPRI));
+    }
+    let Some(uevents) = uevents else {
+        poll(&fds, POLL_INTERVAL);
+        return;
+    };
+    fds.push((uevents.fd.as_raw_fd(), libc::POLLIN));
+
+    loop {
+        let revents = poll(&fds, RESYNC_INTERVAL);
+        if revents.iter().all(|r| *r == 0) {
+            return;
+        }
+        let mounts_changed = mountinfo.is_some() && revents[0] != 0;
+        let block_event = revents.last().is_some_and(|r| r & libc::POLLIN != 0)
+            && uevents.receive().is_some_and(|e| e.subsystem == "block");
+        if mounts_changed || block_event {
+            break;
+        }
+    }
+    // Let the rest of the burst arrive before taking the inventory
+    let socket = [(uevents.fd.as_raw_fd(), libc::POLLIN)];
+    while poll(&socket, SETTLE)[0] & libc::POLLIN != 0 {
+        uevents.receive();
+    }
+}
+
+/// Take an inventory with `enumerate` whenever it may have changed and pass
+/// each difference to `on_event`. Runs forever; give it a thread.
+pub fn watch(
+    root: &SysfsRoot,
+    mut enumerate: impl FnMut() -> Result<Vec<DiskInfo>>,
+    mut on_event: impl FnMut(HotplugEvent),
+) {
+    let uevents = UeventSocket::open()
+        .inspect_err(|e| {
+            // A diagnostic, not an operation: keep it out of the activity log
+            eprintln!(
+                "No uevents ({e}), polling for disk changes every {} s",
+                POLL_INTERVAL.as_secs()
+            );
+        })
+        .ok();
+    let mountinfo = File::open(root.mountinfo_path()).ok();
+    let mut tracker = Tracker::new(enumerate().unwrap_or_default());
+
+    loop {
+        wait_for_change(uevents.as_ref(), mountinfo.as_ref());
+        let Ok(disks) = enumerate() else {
+            continue;
+        };
+        for event in tracker.update(disks) {
+            on_event(event);
+        }
+    }
+}
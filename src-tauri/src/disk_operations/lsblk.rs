@@ -0,0 +1,306 @@
+// src/disk_operations/lsblk.rs
+// `lsblk -J` parsing, used when sysfs is not available.
+//
+// The JSON shape changed across util-linux releases:
+// - up to 2.32 every value is a string ("size": "500107862016", "rm": "0")
+// - from 2.33 flags are booleans ("rm": false) and -b sizes are numbers
+// - columns a device doesn't have are null, and some builds pad model and
+//   serial with spaces
+// so every column goes through a deserializer that accepts all of them.
+use super::capabilities::{self, DiskFacts};
+use super::command::{CommandRunner, ExternalCommand};
+use super::devnode;
+use super::mountinfo::{read_mountinfo, MountEntry};
+use super::statvfs::filesystem_usage;
+use super::sysfs::{self, SysfsRoot};
+use super::system_devices::SystemDevices;
+use crate::structs::{DiskCapabilities, DiskInfo, MountPoint, PartitionInfo, ProtectionReason};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::time::Duration;
+
+pub const LSBLK_COLUMNS: &str =
+    "NAME,MAJ:MIN,SIZE,TYPE,MOUNTPOINT,MODEL,SERIAL,STATE,RM,ROTA,TRAN,RO,DISC-MAX";
+
+/// lsblk only reads sysfs and udev, but a hung device can stall it
+const LSBLK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+pub struct LsblkOutput {
+    pub blockdevices: Vec<BlockDevice>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockDevice {
+    pub name: String,
+    #[serde(rename = "maj:min", default, deserialize_with = "de_string")]
+    pub maj_min: Option<String>,
+    #[serde(default, deserialize_with = "de_size")]
+    pub size: Option<u64>, // lsblk -b gives bytes
+    #[serde(rename = "type", default, deserialize_with = "de_string")]
+    pub device_type: Option<String>,
+    #[serde(default, deserialize_with = "de_string")]
+    pub mountpoint: Option<String>,
+    #[serde(default, deserialize_with = "de_string")]
+    pub model: Option<String>,
+    #[serde(default, deserialize_with = "de_string")]
+    pub serial: Option<String>,
+    #[serde(default, deserialize_with = "de_string")]
+    pub state: Option<String>,
+    #[serde(default, deserialize_with = "de_flag")]
+    pub rm: Option<bool>, // Removable flag
+    #[serde(default, deserialize_with = "de_flag")]
+    pub rota: Option<bool>, // Rotational (true = HDD, false = SSD)
+    #[serde(default, deserialize_with = "de_string")]
+    pub tran: Option<String>, // Transport type (nvme, usb, sata, etc.)
+    #[serde(default, deserialize_with = "de_flag")]
+    pub ro: Option<bool>, // Read-only
+    #[serde(rename = "disc-max", default, deserialize_with = "de_size")]
+    pub disc_max: Option<u64>, // Largest discard, 0 without TRIM
+    #[serde(default)]
+    pub children: Option<Vec<BlockDevice>>,
+}
+
+/// Strings are trimmed; empty strings and null become None. Numbers and
+/// booleans are kept in their textual form.
+fn de_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    })
+}
+
+/// Accepts 0/1, "0"/"1", true/false, "true"/"false" and null
+fn de_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(b) => Some(b),
+        Value::Number(n) => n.as_u64().map(|n| n != 0),
+        Value::String(s) => match s.trim() {
+            "1" | "true" => Some(true),
+            "0" | "false" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Accepts a number or a numeric string. Sizes with a unit suffix (lsblk run
+/// without -b) are rejected as unknown rather than misread.
+fn de_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+/// # Errors
+///
+/// Fails when `json` does not have the shape of `lsblk -J` output
+pub fn parse_lsblk(json: &str) -> Result<LsblkOutput> {
+    Ok(serde_json::from_str(json)?)
+}
+
+/// The command `read_lsblk` runs: JSON, sizes in bytes
+pub fn lsblk_command() -> ExternalCommand {
+    ExternalCommand::new("lsblk")
+        .args(["-J", "-b", "-o", LSBLK_COLUMNS])
+        .timeout(LSBLK_TIMEOUT)
+}
+
+/// Run lsblk and parse what it prints
+///
+/// # Errors
+///
+/// Fails when lsblk cannot be run, exits with an error or prints
+/// something other than JSON
+pub fn read_lsblk(runner: &dyn CommandRunner) -> Result<LsblkOutput> {
+    let command = lsblk_command();
+    let output = runner.run(&command)?.checked(&command.invocation)?;
+    parse_lsblk(&output.stdout).context("Cannot parse lsblk output")
+}
+
+fn get_disk_type_lsblk(device: &BlockDevice) -> crate::structs::DiskType {
+    sysfs::classify_disk(
+        device.tran.as_deref(),
+        device.rm.unwrap_or(false),
+        device.rota,
+    )
+}
+
+/// sysfs may be unreadable when lsblk is in use, its own columns fill the gaps
+fn lsblk_capabilities(root: &SysfsRoot, device: &BlockDevice, is_online: bool) -> DiskCapabilities {
+    let mut capabilities = capabilities::detect(
+        root,
+        &device.name,
+        &DiskFacts {
+            transport: device.tran.as_deref(),
+            removable: device.rm.unwrap_or(false),
+            rotational: device.rota,
+            is_online,
+        },
+    );
+    capabilities.is_read_only |= device.ro.unwrap_or(false);
+    capabilities.supports_trim |= device.disc_max.is_some_and(|max| max > 0);
+    capabilities
+}
+
+/// lsblk only reports one mount point per device. Prefer mountinfo, which
+/// lists all of them with their options, and fall back to lsblk's column.
+fn lsblk_mount_points(device: &BlockDevice, mounts: &[MountEntry]) -> Vec<MountPoint> {
+    let source = format!("/dev/{}", device.name);
+    let from_mountinfo: Vec<MountPoint> = mounts
+        .iter()
+        .filter(|m| m.source == source)
+        .map(MountEntry::to_mount_point)
+        .collect();
+    if !from_mountinfo.is_empty() {
+        return from_mountinfo;
+    }
+
+    // "[SWAP]" is not a mount point
+    match device.mountpoint.as_deref() {
+        Some(path) if path.starts_with('/') => vec![MountPoint {
+            path: path.to_string(),
+            root: "/".to_string(),
+            ..MountPoint::default()
+        }],
+        _ => vec![],
+    }
+}
+
+/// Protection that lsblk's mount column gives `device` and everything
+/// stacked on it: LVM volumes and dm-crypt mappings on a partition, or on
+/// the disk itself when it has no partition table
+fn mount_column_reasons(device: &BlockDevice, reasons: &mut Vec<ProtectionReason>) {
+    match device.mountpoint.as_deref() {
+        Some("/") => reasons.push(ProtectionReason::RootFilesystem),
+        Some("/boot" | "/boot/efi" | "/efi") => reasons.push(ProtectionReason::Boot),
+        Some("[SWAP]") => reasons.push(ProtectionReason::Swap),
+        _ => {}
+    }
+    for child in device.children.iter().flatten() {
+        mount_column_reasons(child, reasons);
+    }
+}
+
+/// Convert parsed lsblk output into our disk model. Partition numbers come
+/// from sysfs under `root` when it is readable, otherwise from the kernel
+/// partition name. Mount points are measured and the udev database is read
+/// relative to `root` as well.
+pub fn disks_from_lsblk(lsblk: LsblkOutput, root: &SysfsRoot) -> Vec<DiskInfo> {
+    let mut disks = Vec::new();
+    let mounts = read_mountinfo(&root.mountinfo_path()).unwrap_or_default();
+    let system = SystemDevices::detect(root, &mounts);
+
+    for device in lsblk.blockdevices {
+        // Filter for actual disks
+        if device.device_type.as_deref() != Some("disk") {
+            continue;
+        }
+
+        let id = device.name.clone();
+        let model = device.model.clone().unwrap_or_else(|| format!("Disk {id}"));
+        let size_bytes = device.size.unwrap_or(0);
+
+        // Check state. If state is "offline", then it's offline.
+        let is_online = device.state.as_deref() != Some("offline");
+
+        // Determine disk type based on transport and properties
+        let disk_type = get_disk_type_lsblk(&device);
+        let capabilities = lsblk_capabilities(root, &device, is_online);
+
+        let mut partitions = Vec::new();
+        let mut devs: Vec<String> = device.maj_min.iter().cloned().collect();
+        // Without mountinfo or /proc/swaps, lsblk still marks /, /boot and
+        // swap in its mount column, on whatever is stacked on the disk
+        let mut mount_reasons = Vec::new();
+        mount_column_reasons(&device, &mut mount_reasons);
+
+        let children = device.children.unwrap_or_default();
+        let parts = children
+            .into_iter()
+            .filter(|child| child.device_type.as_deref() == Some("part"));
+        for child in parts {
+            let Some(partition_number) = devnode::read_partition_number(root, &child.name)
+                .or_else(|| devnode::partition_number_from_name(&id, &child.name))
+            else {
+                continue;
+            };
+
+            devs.extend(child.maj_min.iter().cloned());
+            let mount_points = lsblk_mount_points(&child, &mounts);
+            let mounted_at = |paths: &[&str]| {
+                mount_points
+                    .iter()
+                    .any(|m| paths.contains(&m.path.as_str()))
+            };
+            if mounted_at(&["/"]) {
+                mount_reasons.push(ProtectionReason::RootFilesystem);
+            }
+            if mounted_at(&["/boot", "/boot/efi", "/efi"]) {
+                mount_reasons.push(ProtectionReason::Boot);
+            }
+            let usage = mount_points
+                .first()
+                .and_then(|m| filesystem_usage(&root.host_path(&m.path)));
+
+            let mut partition = PartitionInfo {
+                partition_number,
+                size_bytes: child.size.unwrap_or(0),
+                used_bytes: usage.as_ref().map(|u| u.used_bytes),
+                drive_letter: String::new(),
+                partition_id: child.name, // Using partition_id for device name (e.g. sda1)
+                mount_points,
+                usage,
+                fs_type: None,
+                label: None,
+                uuid: None,
+                part_uuid: None,
+                part_type: None,
+                part_flags: Vec::new(),
+            };
+            if let Some(dev) = child.maj_min.as_deref() {
+                sysfs::apply_udev_metadata(&mut partition, &sysfs::read_udev_properties(root, dev));
+            }
+            if partition.fs_type.is_none() {
+                sysfs::probe_partition(root, &mut partition);
+            }
+            if partition.fs_type.is_none() {
+                partition.fs_type = partition
+                    .mount_points
+                    .first()
+                    .map(|m| m.fs_type.clone())
+                    .filter(|t| !t.is_empty());
+            }
+            partitions.push(partition);
+        }
+        partitions.sort_by_key(|p| p.partition_number);
+
+        let mut protection = system.reasons_for(&devs);
+        protection.extend(mount_reasons);
+        protection.sort();
+        protection.dedup();
+        let usage_percentage = crate::utils::usage_percentage(&partitions);
+
+        disks.push(DiskInfo {
+            id,
+            model,
+            size_bytes,
+            is_online,
+            is_system_disk: !protection.is_empty(),
+            protection,
+            partitions,
+            disk_type,
+            serial_number: device.serial,
+            health_percentage: None,
+            usage_percentage,
+            capabilities,
+        });
+    }
+
+    disks
+}
@@ -1,4 +1,10 @@
 // src/disk_operations/mod.rs
+pub mod backend;
+pub mod command;
+pub mod partition_table;
+pub mod probe;
+pub mod protection;
+
 #[cfg(target_os = "windows")]
 mod disk_operations_windows;
 #[cfg(target_os = "windows")]
@@ -8,3 +14,35 @@ pub use disk_operations_windows::*;
 mod disk_operations_linux;
 #[cfg(target_os = "linux")]
 pub use disk_operations_linux::*;
+#[cfg(target_os = "linux")]
+pub mod busy;
+#[cfg(target_os = "linux")]
+pub mod capabilities;
+#[cfg(target_os = "linux")]
+pub mod devnode;
+#[cfg(target_os = "linux")]
+pub mod hotplug;
+#[cfg(target_os = "linux")]
+pub mod lsblk;
+#[cfg(target_os = "linux")]
+pub mod mount;
+#[cfg(target_os = "linux")]
+pub mod mountinfo;
+#[cfg(target_os = "linux")]
+pub mod offline;
+#[cfg(target_os = "linux")]
+pub mod offline_method;
+#[cfg(target_os = "linux")]
+pub mod rescan;
+#[cfg(target_os = "linux")]
+pub mod statvfs;
+#[cfg(target_os = "linux")]
+pub mod swaps;
+#[cfg(target_os = "linux")]
+pub mod sysfs;
+#[cfg(target_os = "linux")]
+pub mod system_devices;
+#[cfg(target_os = "linux")]
+pub mod udisks;
+#[cfg(target_os = "linux")]
+pub mod unmount;
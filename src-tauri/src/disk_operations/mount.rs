@@ -0,0 +1,326 @@
+// src/disk_operations/mount.rs
+// Mounting a partition with options chosen by the caller.
+//
+// Without a target directory udisks mounts the partition below /media, as
+// the desktop would, and applies its own policy to the options. With a
+// target the partition is mounted with mount(2) instead, since udisks does
+// not take one, and gets the nosuid and nodev udisks would have enforced.
+// Either way the options are checked here first, so that a bad request
+// fails with a clear message rather than EINVAL.
+use super::devnode;
+use super::mountinfo::read_mountinfo;
+use super::offline::SystemActions;
+use super::probe;
+use super::sysfs::{read_attr, read_udev_properties, SysfsRoot};
+use super::udisks::UDisksClient;
+use crate::structs::{DiskError, ErrorCode, MountOptions};
+use anyhow::{bail, Context, Result};
+use std::ffi::CString;
+use std::path::{Component, Path};
+
+/// Filesystems that can be asked for by type
+const KNOWN_FS: [&str; 14] = [
+    "btrfs", "exfat", "ext2", "ext3", "ext4", "f2fs", "hfsplus", "iso9660", "msdos", "ntfs",
+    "ntfs3", "udf", "vfat", "xfs",
+];
+
+/// Filesystems without Unix ownership, whose files belong to the uid and gid
+/// given at mount time
+const OWNERLESS_FS: [&str; 5] = ["exfat", "msdos", "ntfs", "ntfs3", "vfat"];
+
+/// Directories a partition must never be mounted over; their
+/// subdirectories are fine, e.g. /home/alice/stick
+const SYSTEM_DIRS: [&str; 5] = ["/", "/home", "/root", "/run", "/tmp"];
+
+/// Directories belonging to the system down to their last subdirectory, so
+/// that nothing is mounted on them or anywhere below
+const SYSTEM_TREES: [&str; 11] = [
+    "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/sbin", "/sys", "/usr", "/var",
+];
+
+/// Options every mount(2) on a caller's target gets, as udisks mounts do
+const ENFORCED_OPTIONS: [&str; 2] = ["nosuid", "nodev"];
+
+/// Side effects of mounting, replaced in tests
+pub trait MountActions {
+    /// Mount `device` through udisks and return where it was mounted
+    ///
+    /// # Errors
+    ///
+    /// Fails when udisks is unreachable or refuses the mount
+    fn udisks_mount(
+        &mut self,
+        device: &str,
+        fs_type: Option<&str>,
+        options: &[String],
+    ) -> Result<String>;
+    /// mount(2) `device` on the existing directory `target`
+    ///
+    /// # Errors
+    ///
+    /// Fails with the errno of mount(2)
+    fn mount(
+        &mut self,
+        device: &str,
+        target: &str,
+        fs_type: &str,
+        options: &[String],
+    ) -> Result<()>;
+}
+
+impl MountActions for SystemActions {
+    fn udisks_mount(
+        &mut self,
+        device: &str,
+        fs_type: Option<&str>,
+        options: &[String],
+    ) -> Result<String> {
+        let client = UDisksClient::system().map_err(DiskError::from)?;
+        Ok(client
+            .mount(device, fs_type, options)
+            .map_err(DiskError::from)?)
+    }
+
+    fn mount(
+        &mut self,
+        device: &str,
+        target: &str,
+        fs_type: &str,
+        options: &[String],
+    ) -> Result<()> {
+        let (flags, data) = mount_arguments(options);
+        let source = CString::new(device)?;
+        let c_target = CString::new(target)?;
+        let c_fs_type = CString::new(fs_type)?;
+        let data = CString::new(data)?;
+        let res = unsafe {
+            libc::mount(
+                source.as_ptr(),
+                c_target.as_ptr(),
+                c_fs_type.as_ptr(),
+                flags,
+                data.as_ptr().cast(),
+            )
+        };
+        if res != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to mount {device} on {target}"));
+        }
+        Ok(())
+    }
+}
+
+/// Options in mount(8) form, e.g. `ro,nosuid,uid=1000`
+pub fn option_list(options: &MountOptions) -> Vec<String> {
+    let flags = [
+        (options.read_only, "ro"),
+        (options.no_exec, "noexec"),
+        (options.no_suid, "nosuid"),
+        (options.no_dev, "nodev"),
+    ];
+    let mut list: Vec<String> = flags
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| (*name).to_string())
+        .collect();
+    if let Some(uid) = options.uid {
+        list.push(format!("uid={uid}"));
+    }
+    if let Some(gid) = options.gid {
+        list.push(format!("gid={gid}"));
+    }
+    list
+}
+
+/// Split mount(8) options into mount(2) flags and filesystem data
+pub fn mount_arguments(options: &[String]) -> (libc::c_ulong, String) {
+    let mut flags = 0;
+    let mut data = Vec::new();
+    for option in options {
+        match option.as_str() {
+            "ro" => flags |= libc::MS_RDONLY,
+            "noexec" => flags |= libc::MS_NOEXEC,
+            "nosuid" => flags |= libc::MS_NOSUID,
+            "nodev" => flags |= libc::MS_NODEV,
+            other => data.push(other),
+        }
+    }
+    (flags, data.join(","))
+}
+
+/// Filesystem on a partition, from udev or else from its superblock
+pub fn detect_fs_type(root: &SysfsRoot, partition: &str) -> Option<String> {
+    let dev = read_attr(&root.class_block_device(partition).join("dev"))?;
+    if let Some(fs_type) = read_udev_properties(root, &dev).remove("ID_FS_TYPE") {
+        return Some(fs_type);
+    }
+    let signature = probe::probe_path(&root.dev_node(partition)).ok()??;
+    Some(signature.fs_type.to_string())
+}
+
+fn validate_target(root: &SysfsRoot, target: &str) -> Result<()> {
+    let path = Path::new(target);
+    if !path.is_absolute() {
+        bail!(DiskError::new(
+            ErrorCode::InvalidArgument,
+            format!("Mount target {target} must be an absolute path")
+        ));
+    }
+    if path
+        .components()
+        .any(|c| matches!(c, Component::CurDir | Component::ParentDir))
+    {
+        bail!(DiskError::new(
+            ErrorCode::InvalidArgument,
+            format!("Mount target {target} must not contain . or ..")
+        ));
+    }
+    // A symlink such as /mnt/x -> /etc is checked as where it leads
+    let resolved = resolve_target(root, target)?;
+    let normalized = resolved.as_str();
+    if SYSTEM_DIRS.contains(&normalized)
+        || SYSTEM_TREES
+            .iter()
+            .any(|tree| Path::new(normalized).starts_with(tree))
+    {
+        bail!(DiskError::new(
+            ErrorCode::InvalidArgument,
+            format!("Refusing to mount over {normalized}")
+        )
+        .with_hint("Pick a directory of your own, e.g. under /mnt"));
+    }
+    let mounts = read_mountinfo(&root.mountinfo_path()).unwrap_or_default();
+    if mounts.iter().any(|m| m.mount_point == normalized) {
+        bail!(DiskError::new(
+            ErrorCode::AlreadyMounted,
+            format!("{normalized} is already a mount point")
+        )
+        .with_hint("Pick another directory, or unmount what is there first"));
+    }
+    let host = root.host_path(normalized);
+    if host.exists() {
+        let empty = std::fs::read_dir(&host).map(|mut entries| entries.next().is_none());
+        if !matches!(empty, Ok(true)) {
+            bail!(DiskError::new(
+                ErrorCode::InvalidArgument,
+                format!("Mount target {normalized} is not an empty directory")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `target` as the host sees it once the symlinks in the part of it that
+/// exists are followed; the rest is kept as given
+fn resolve_target(root: &SysfsRoot, target: &str) -> Result<String> {
+    let mut existing = Path::new(target);
+    let mut missing = Vec::new();
+    // A dangling symlink counts as existing, so that it fails to resolve
+    // rather than have its target created
+    while root
+        .host_path(&existing.to_string_lossy())
+        .symlink_metadata()
+        .is_err()
+    {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            break;
+        };
+        missing.push(name);
+        existing = parent;
+    }
+    let host = root.host_path(&existing.to_string_lossy());
+    let canonical = std::fs::canonicalize(&host).map_err(|e| {
+        DiskError::new(
+            ErrorCode::InvalidArgument,
+            format!("Cannot resolve mount target {target}: {e}"),
+        )
+    })?;
+    // Back to the host's view; a link out of a test tree stays as it is
+    let base = std::fs::canonicalize(root.root()).unwrap_or_else(|_| root.root().to_path_buf());
+    let mut resolved = match canonical.strip_prefix(&base) {
+        Ok(relative) => Path::new("/").join(relative),
+        Err(_) => canonical,
+    };
+    for name in missing.into_iter().rev() {
+        resolved.push(name);
+    }
+    Ok(resolved.to_string_lossy().into_owned())
+}
+
+/// Check `options` before mounting a partition holding `fs_type`
+///
+/// # Errors
+///
+/// Fails for an unknown filesystem type, uid or gid on a filesystem
+/// that keeps its own owners, or a target that is relative, a system
+/// directory, already a mount point or not an empty directory
+pub fn validate(root: &SysfsRoot, options: &MountOptions, fs_type: Option<&str>) -> Result<()> {
+    if let Some(requested) = &options.fs_type {
+        if !KNOWN_FS.contains(&requested.as_str()) {
+            bail!(DiskError::new(
+                ErrorCode::Unsupported,
+                format!("Unsupported filesystem type {requested}")
+            ));
+        }
+    }
+    if options.uid.is_some() || options.gid.is_some() {
+        match fs_type {
+            Some(fs) if OWNERLESS_FS.contains(&fs) => {}
+            Some(fs) => bail!("uid and gid only apply to FAT, exFAT and NTFS, not {fs}"),
+            None => bail!("uid and gid only apply to FAT, exFAT and NTFS filesystems"),
+        }
+    }
+    if let Some(target) = &options.target {
+        validate_target(root, target)?;
+        if fs_type.is_none() {
+            bail!("Cannot tell which filesystem is on the partition, give its type");
+        }
+    }
+    Ok(())
+}
+
+/// Mount partition `number` of `disk` and return its mount point
+///
+/// # Errors
+///
+/// Fails when the partition does not exist, `options` do not pass
+/// `validate`, or the mount fails
+pub fn mount_partition(
+    root: &SysfsRoot,
+    disk: &str,
+    number: u32,
+    options: &MountOptions,
+    actions: &mut impl MountActions,
+) -> Result<String> {
+    let name = devnode::resolve_partition_name(root, disk, number);
+    if !root.class_block_device(&name).exists() {
+        bail!(DiskError::new(
+            ErrorCode::NotFound,
+            format!("Partition {number} of disk {disk} not found")
+        )
+        .on_disk(disk)
+        .on_partition(number.to_string()));
+    }
+    let fs_type = options
+        .fs_type
+        .clone()
+        .or_else(|| detect_fs_type(root, &name));
+    validate(root, options, fs_type.as_deref())?;
+
+    let device = format!("/dev/{name}");
+    let mut list = option_list(options);
+    match (&options.target, fs_type) {
+        (Some(target), Some(fs_type)) => {
+            for option in ENFORCED_OPTIONS {
+                if !list.iter().any(|o| o == option) {
+                    list.push(option.to_string());
+                }
+            }
+            let target = resolve_target(root, target)?;
+            std::fs::create_dir_all(root.host_path(&target))?;
+            actions.mount(&device, &target, &fs_type, &list)?;
+            Ok(target)
+        }
+        _ => actions.udisks_mount(&device, options.fs_type.as_deref(), &list),
+    }
+}
@@ -0,0 +1,113 @@
+// src/disk_operations/mountinfo.rs
+// Parser for /proc/self/mountinfo (see proc(5))
+use crate::structs::MountPoint;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountEntry {
+    pub major: u32,
+    pub minor: u32,
+    /// Root of the mount within the filesystem (e.g. "/@home" for a btrfs subvolume)
+    pub root: String,
+    pub mount_point: String,
+    /// Per-mount options (field 6)
+    pub options: Vec<String>,
+    pub fs_type: String,
+    pub source: String,
+    /// Per-superblock options, shared by every mount of the filesystem
+    pub super_options: Vec<String>,
+}
+
+impl MountEntry {
+    /// "major:minor" as found in sysfs `dev` files
+    pub fn dev(&self) -> String {
+        format!("{}:{}", self.major, self.minor)
+    }
+
+    /// Read-only either for this mount or for the whole filesystem
+    pub fn is_read_only(&self) -> bool {
+        self.options
+            .iter()
+            .chain(&self.super_options)
+            .any(|o| o == "ro")
+    }
+
+    pub fn to_mount_point(&self) -> MountPoint {
+        MountPoint {
+            path: self.mount_point.clone(),
+            fs_type: self.fs_type.clone(),
+            options: self.options.clone(),
+            read_only: self.is_read_only(),
+            root: self.root.clone(),
+        }
+    }
+}
+
+/// # Errors
+///
+/// Fails when `path` cannot be read
+pub fn read_mountinfo(path: &Path) -> Result<Vec<MountEntry>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(parse_mountinfo(&content))
+}
+
+/// Parse mountinfo content. Malformed lines are skipped.
+pub fn parse_mountinfo(content: &str) -> Vec<MountEntry> {
+    content.lines().filter_map(parse_line).collect()
+}
+
+// 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+fn parse_line(line: &str) -> Option<MountEntry> {
+    let (left, right) = line.split_once(" - ")?;
+    let fields: Vec<&str> = left.split(' ').collect();
+    if fields.len() < 6 {
+        return None;
+    }
+
+    let (major, minor) = fields[2].split_once(':')?;
+    let mut right_fields = right.split(' ');
+    let fs_type = right_fields.next()?;
+    let source = right_fields.next().unwrap_or_default();
+    let super_options = right_fields.next().unwrap_or_default();
+
+    Some(MountEntry {
+        major: major.parse().ok()?,
+        minor: minor.parse().ok()?,
+        root: unescape(fields[3]),
+        mount_point: unescape(fields[4]),
+        options: split_options(fields[5]),
+        fs_type: unescape(fs_type),
+        source: unescape(source),
+        super_options: split_options(super_options),
+    })
+}
+
+fn split_options(field: &str) -> Vec<String> {
+    field
+        .split(',')
+        .filter(|o| !o.is_empty())
+        .map(unescape)
+        .collect()
+}
+
+/// The kernel escapes space, tab, newline and backslash as \ooo octal sequences
+pub fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let octal = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or_default();
+            if let Ok(value) = u8::from_str_radix(octal, 8) {
+                out.push(value);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
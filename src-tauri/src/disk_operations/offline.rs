@@ -0,0 +1,608 @@
+// src/disk_operations/offline.rs
+// Guarded offline sequence for Linux disks:
+//   unmount every filesystem on the disk, sync, flush the block device
+//   buffers (BLKFLSBUF), take the disk offline the way its device type
+//   allows (see offline_method) and check that it took.
+// When a step fails, the steps already done are undone in reverse order
+// (remount, bring the disk back) and the report lists what happened.
+// Each offline is recorded with its bring-back writes before it starts, so
+// that `bring_online` can replay them, and the record is dropped again when
+// the sequence is rolled back.
+//
+// Ejecting runs the same preparation and then powers the device off.
+use super::busy;
+use super::mountinfo::{read_mountinfo, MountEntry};
+use super::offline_method::{self, Strategy};
+use super::sysfs::{enumerate_disks, mount_points_for, read_attr, SysfsRoot};
+use super::udisks::UDisksClient;
+use crate::structs::{
+    BlockingProcess, DiskError, DiskInfo, ErrorCode, OfflineMethod, OfflineReport, OfflineStep,
+    StepReport, StepStatus, SysfsWrite,
+};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::path::Path;
+
+/// _IO(0x12, 97) from <linux/fs.h>
+const BLKFLSBUF: libc::Ioctl = 0x1261;
+
+/// Side effects of the offline sequence, replaced by a recorder in tests
+pub trait OfflineActions {
+    /// # Errors
+    ///
+    /// Fails with the errno of umount(2)
+    fn unmount(&mut self, mount: &MountEntry) -> Result<()>;
+    /// # Errors
+    ///
+    /// Fails with the errno of mount(2)
+    fn remount(&mut self, mount: &MountEntry) -> Result<()>;
+    /// # Errors
+    ///
+    /// Fails with the errno of syncfs(2)
+    fn sync(&mut self) -> Result<()>;
+    /// # Errors
+    ///
+    /// Fails when `node` cannot be opened or the ioctl is refused
+    fn flush_buffers(&mut self, node: &Path) -> Result<()>;
+    /// Write a sysfs attribute (device state, PCI remove, driver unbind)
+    ///
+    /// # Errors
+    ///
+    /// Fails when the attribute cannot be written
+    fn write_attr(&mut self, path: &Path, value: &str) -> Result<()>;
+    /// Power a whole disk off, e.g. /dev/sdb
+    ///
+    /// # Errors
+    ///
+    /// Fails when neither udisks nor the SCSI layer can power it off
+    fn power_off(&mut self, node: &Path) -> Result<()>;
+}
+
+/// The real thing: syscalls and sysfs writes
+pub struct SystemActions;
+
+impl OfflineActions for SystemActions {
+    fn unmount(&mut self, mount: &MountEntry) -> Result<()> {
+        let target = CString::new(mount.mount_point.as_str())?;
+        if unsafe { libc::umount2(target.as_ptr(), 0) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to unmount {}", mount.mount_point));
+        }
+        Ok(())
+    }
+
+    fn remount(&mut self, mount: &MountEntry) -> Result<()> {
+        let (flags, data) = mount_flags(mount);
+        if mount.root == "/" {
+            return sys_mount(
+                &mount.source,
+                &mount.mount_point,
+                Some(&mount.fs_type),
+                flags,
+                &data,
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to remount {} on {}",
+                    mount.source, mount.mount_point
+                )
+            });
+        }
+        remount_subtree(mount, flags, &data).with_context(|| {
+            format!(
+                "Failed to remount {}{} on {}",
+                mount.source, mount.root, mount.mount_point
+            )
+        })
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        unsafe { libc::sync() };
+        Ok(())
+    }
+
+    fn flush_buffers(&mut self, node: &Path) -> Result<()> {
+        use std::os::unix::io::AsRawFd;
+        let file = std::fs::File::open(node)?;
+        if unsafe { libc::ioctl(file.as_raw_fd(), BLKFLSBUF, 0) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to flush buffers of {}", node.display()));
+        }
+        Ok(())
+    }
+
+    fn write_attr(&mut self, path: &Path, value: &str) -> Result<()> {
+        std::fs::write(path, value)
+            .with_context(|| format!("Failed to write {} to {}", value, path.display()))
+    }
+
+    fn power_off(&mut self, node: &Path) -> Result<()> {
+        let device = node.to_string_lossy();
+        let client = UDisksClient::system().map_err(DiskError::from)?;
+        Ok(client.power_off(&device).map_err(DiskError::from)?)
+    }
+}
+
+fn sys_mount(
+    source: &str,
+    target: &str,
+    fs_type: Option<&str>,
+    flags: libc::c_ulong,
+    data: &str,
+) -> Result<()> {
+    let source = CString::new(source)?;
+    let target = CString::new(target)?;
+    let fs_type = fs_type.map(CString::new).transpose()?;
+    let data = CString::new(data)?;
+    let res = unsafe {
+        libc::mount(
+            source.as_ptr(),
+            target.as_ptr(),
+            fs_type.as_ref().map_or(std::ptr::null(), |t| t.as_ptr()),
+            flags,
+            data.as_ptr().cast(),
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Re-create a mount of a directory inside a filesystem, a bind mount or a
+/// btrfs subvolume: mount the whole filesystem aside, bind the directory
+/// back where it was and drop the aside mount again
+fn remount_subtree(mount: &MountEntry, flags: libc::c_ulong, data: &str) -> Result<()> {
+    let aside = std::env::temp_dir().join(format!("diskofflaner-remount-{}", std::process::id()));
+    std::fs::create_dir_all(&aside)?;
+    let aside_path = aside.to_string_lossy().into_owned();
+    // Without the subvolume options the top of the filesystem is mounted,
+    // which is what mountinfo's root is relative to
+    let whole: Vec<&str> = data
+        .split(',')
+        .filter(|o| !o.is_empty() && !o.starts_with("subvol=") && !o.starts_with("subvolid="))
+        .collect();
+    // Read-only only if the filesystem was; this mount's own flags are
+    // applied to the bind below
+    let sb_flags = if mount.super_options.iter().any(|o| o == "ro") {
+        libc::MS_RDONLY
+    } else {
+        0
+    };
+    let result = sys_mount(
+        &mount.source,
+        &aside_path,
+        Some(&mount.fs_type),
+        sb_flags,
+        &whole.join(","),
+    )
+    .and_then(|()| {
+        let subtree = aside.join(mount.root.trim_start_matches('/'));
+        sys_mount(
+            &subtree.to_string_lossy(),
+            &mount.mount_point,
+            None,
+            libc::MS_BIND,
+            "",
+        )?;
+        if flags == 0 {
+            return Ok(());
+        }
+        // A bind mount takes its per-mount flags from a remount; without
+        // them it would be laxer than before, so it does not stay
+        let flagged = sys_mount(
+            "none",
+            &mount.mount_point,
+            None,
+            libc::MS_REMOUNT | libc::MS_BIND | flags,
+            "",
+        );
+        if flagged.is_err() {
+            let target = CString::new(mount.mount_point.as_str())?;
+            unsafe { libc::umount2(target.as_ptr(), 0) };
+        }
+        flagged
+    });
+    if let Ok(target) = CString::new(aside_path) {
+        unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) };
+    }
+    let _ = std::fs::remove_dir(&aside);
+    result
+}
+
+/// mount(2) flags and filesystem data for re-creating a mount as it was.
+/// Per-mount options become flags, superblock options are passed as data.
+pub fn mount_flags(mount: &MountEntry) -> (libc::c_ulong, String) {
+    let mut flags = 0;
+    for option in &mount.options {
+        flags |= match option.as_str() {
+            "ro" => libc::MS_RDONLY,
+            "nosuid" => libc::MS_NOSUID,
+            "nodev" => libc::MS_NODEV,
+            "noexec" => libc::MS_NOEXEC,
+            "sync" => libc::MS_SYNCHRONOUS,
+            "noatime" => libc::MS_NOATIME,
+            "nodiratime" => libc::MS_NODIRATIME,
+            "relatime" => libc::MS_RELATIME,
+            "strictatime" => libc::MS_STRICTATIME,
+            _ => 0,
+        };
+    }
+    let data: Vec<&str> = mount
+        .super_options
+        .iter()
+        .map(String::as_str)
+        .filter(|o| *o != "ro" && *o != "rw")
+        .collect();
+    (flags, data.join(","))
+}
+
+/// Kernel names of `disk` and its partitions
+fn disk_and_partitions(root: &SysfsRoot, disk: &str) -> Vec<String> {
+    let mut names = vec![disk.to_string()];
+    let mut partitions: Vec<String> = std::fs::read_dir(root.block_device(disk))
+        .into_iter()
+        .flatten()
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.path().join("partition").exists())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    partitions.sort();
+    names.extend(partitions);
+    names
+}
+
+/// Every mount of the disk or one of its partitions, deepest first so that
+/// nested mounts come off before the mounts they sit on
+pub fn mounts_on_disk(root: &SysfsRoot, disk: &str, mounts: &[MountEntry]) -> Vec<MountEntry> {
+    let mut on_disk: Vec<MountEntry> = disk_and_partitions(root, disk)
+        .iter()
+        .filter_map(|name| read_attr(&root.class_block_device(name).join("dev")))
+        .flat_map(|dev| mount_points_for(root, mounts, Some(&dev)))
+        .cloned()
+        .collect();
+    // Later mounts may be stacked on earlier ones: undo them in reverse order
+    on_disk.sort_by_key(|m| mounts.iter().position(|e| e == m));
+    on_disk.reverse();
+    on_disk.sort_by_key(|m| Reverse(m.mount_point.trim_end_matches('/').split('/').count()));
+    on_disk
+}
+
+/// A disk taken offline by this app, with what brings it back. Detached
+/// disks vanish from sysfs, the snapshot keeps them listed meanwhile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OfflineRecord {
+    method: OfflineMethod,
+    bring_back: Vec<SysfsWrite>,
+    snapshot: Option<DiskInfo>,
+}
+
+type OfflineRecords = BTreeMap<String, OfflineRecord>;
+
+fn load_records(root: &SysfsRoot) -> OfflineRecords {
+    std::fs::read_to_string(root.offline_records_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_records(root: &SysfsRoot, records: &OfflineRecords) -> Result<()> {
+    let path = root.offline_records_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(records)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Run the guarded offline sequence for `disk` under `root`
+///
+/// # Errors
+///
+/// Fails when `disk` does not exist, has no offline method or its record
+/// cannot be saved, before anything is done. A sequence that stopped part
+/// way is a report with `completed` unset.
+pub fn take_offline(
+    root: &SysfsRoot,
+    disk: &str,
+    actions: &mut impl OfflineActions,
+) -> Result<OfflineReport> {
+    let strategy = offline_method::select(root, disk)?;
+    let mounts = read_mountinfo(&root.mountinfo_path()).unwrap_or_default();
+    // Detached disks are gone from sysfs afterwards, remember what they were
+    let snapshot = enumerate_disks(root)
+        .ok()
+        .and_then(|disks| disks.into_iter().find(|d| d.id == disk));
+
+    // Saved before anything is done: a disk that went offline without its
+    // record could not be brought back
+    let mut records = load_records(root);
+    let previous = records.insert(
+        disk.to_string(),
+        OfflineRecord {
+            method: strategy.method,
+            bring_back: strategy.bring_back.clone(),
+            snapshot,
+        },
+    );
+    save_records(root, &records)?;
+
+    let mut run = Sequence::new(actions);
+    let completed = run.prepare(root, disk, &mounts) && run.offline(root, disk, &strategy);
+    if !completed {
+        run.roll_back(root);
+        match previous {
+            Some(record) => records.insert(disk.to_string(), record),
+            None => records.remove(disk),
+        };
+        // The disk is back online; a stale record would only make a later
+        // bring_online replay writes that do nothing
+        let _ = save_records(root, &records);
+    }
+
+    Ok(OfflineReport {
+        disk_id: disk.to_string(),
+        method: Some(strategy.method),
+        steps: run.steps,
+        completed,
+        bring_back: strategy.bring_back,
+        blockers: run.blockers,
+    })
+}
+
+/// Unmount, sync and flush `disk`, then power it off. Devices udisks cannot
+/// power off are deleted from the SCSI layer instead.
+///
+/// # Errors
+///
+/// Fails when `disk` does not exist; a failed step is in the report
+pub fn eject(
+    root: &SysfsRoot,
+    disk: &str,
+    actions: &mut impl OfflineActions,
+) -> Result<OfflineReport> {
+    if !root.block_device(disk).exists() {
+        bail!(DiskError::new(ErrorCode::NotFound, format!("Disk {disk} not found")).on_disk(disk));
+    }
+    let mounts = read_mountinfo(&root.mountinfo_path()).unwrap_or_default();
+
+    let mut run = Sequence::new(actions);
+    let completed = run.prepare(root, disk, &mounts) && run.power_off(root, disk);
+    if !completed {
+        run.roll_back(root);
+    }
+
+    Ok(OfflineReport {
+        disk_id: disk.to_string(),
+        method: None,
+        steps: run.steps,
+        completed,
+        bring_back: Vec::new(),
+        blockers: run.blockers,
+    })
+}
+
+/// Replay the bring-back writes recorded when `disk` went offline. Disks
+/// offlined by something else only have their SCSI state to restore.
+///
+/// # Errors
+///
+/// Fails when `disk` has neither a record nor a SCSI state, or a write
+/// fails; the record is then kept for another attempt
+pub fn bring_online(root: &SysfsRoot, disk: &str, actions: &mut impl OfflineActions) -> Result<()> {
+    let mut records = load_records(root);
+    let Some(record) = records.get(disk) else {
+        let state = root.block_device(disk).join("device/state");
+        if !state.exists() {
+            bail!(DiskError::new(
+                ErrorCode::Unsupported,
+                format!("Cannot change state for {disk}")
+            )
+            .on_disk(disk));
+        }
+        return actions.write_attr(&state, "running");
+    };
+
+    for write in &record.bring_back {
+        actions.write_attr(&root.host_path(&write.path), &write.value)?;
+    }
+    records.remove(disk);
+    save_records(root, &records)
+}
+
+/// Drop the records of disks that came back by other means, such as a rescan
+///
+/// # Errors
+///
+/// Fails when the records cannot be saved
+pub fn forget_offline(root: &SysfsRoot, disks: &[String]) -> Result<()> {
+    let mut records = load_records(root);
+    let before = records.len();
+    records.retain(|id, _| !disks.contains(id));
+    if records.len() == before {
+        return Ok(());
+    }
+    save_records(root, &records)
+}
+
+/// Add the detached disks that are still offline to an enumeration, so that
+/// they can be brought back online from the list
+pub fn with_offline_disks(root: &SysfsRoot, mut disks: Vec<DiskInfo>) -> Vec<DiskInfo> {
+    for (id, record) in load_records(root) {
+        if disks.iter().any(|d| d.id == id) {
+            continue;
+        }
+        if let Some(mut disk) = record.snapshot {
+            disk.is_online = false;
+            for partition in &mut disk.partitions {
+                partition.mount_points.clear();
+                partition.usage = None;
+                partition.used_bytes = None;
+            }
+            disk.usage_percentage = None;
+            disk.capabilities.can_offline = false;
+            disk.capabilities.can_online = true;
+            disk.capabilities.can_eject = false;
+            disks.push(disk);
+        }
+    }
+    disks.sort_by(|a, b| a.id.cmp(&b.id));
+    disks
+}
+
+enum Undo {
+    Remount(MountEntry),
+    BringBack(Vec<SysfsWrite>),
+}
+
+struct Sequence<'a, A: OfflineActions> {
+    actions: &'a mut A,
+    steps: Vec<StepReport>,
+    /// Index into `steps` and how to undo it, in the order done
+    undo: Vec<(usize, Undo)>,
+    blockers: Vec<BlockingProcess>,
+}
+
+impl<'a, A: OfflineActions> Sequence<'a, A> {
+    fn new(actions: &'a mut A) -> Self {
+        Self {
+            actions,
+            steps: Vec::new(),
+            undo: Vec::new(),
+            blockers: Vec::new(),
+        }
+    }
+
+    /// Record the outcome of a step. Returns false if it failed.
+    fn record(&mut self, step: OfflineStep, target: String, result: Result<()>) -> bool {
+        let (status, message) = match result {
+            Ok(()) => (StepStatus::Done, None),
+            Err(err) => (StepStatus::Failed, Some(format!("{err:#}"))),
+        };
+        self.steps.push(StepReport {
+            step,
+            target,
+            status,
+            message,
+        });
+        status == StepStatus::Done
+    }
+
+    /// Unmount everything on the disk, sync and flush its buffers
+    fn prepare(&mut self, root: &SysfsRoot, disk: &str, mounts: &[MountEntry]) -> bool {
+        for mount in mounts_on_disk(root, disk, mounts) {
+            let result = self.actions.unmount(&mount);
+            if !self.record(OfflineStep::Unmount, mount.mount_point.clone(), result) {
+                self.blockers = busy::find_blockers(root, &[mount.mount_point]);
+                return false;
+            }
+            self.undo.push((self.steps.len() - 1, Undo::Remount(mount)));
+        }
+
+        let result = self.actions.sync();
+        if !self.record(OfflineStep::Sync, String::new(), result) {
+            return false;
+        }
+
+        for name in disk_and_partitions(root, disk) {
+            let node = root.dev_node(&name);
+            let result = self.actions.flush_buffers(&node);
+            if !self.record(
+                OfflineStep::FlushBuffers,
+                node.display().to_string(),
+                result,
+            ) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Take the prepared disk offline and check that it took
+    fn offline(&mut self, root: &SysfsRoot, disk: &str, strategy: &Strategy) -> bool {
+        let offline = &strategy.offline;
+        let result = self
+            .actions
+            .write_attr(&root.host_path(&offline.path), &offline.value);
+        if !self.record(OfflineStep::WriteState, offline.path.clone(), result) {
+            return false;
+        }
+        self.undo.push((
+            self.steps.len() - 1,
+            Undo::BringBack(strategy.bring_back.clone()),
+        ));
+
+        let result = if strategy.detaches() {
+            detached(root, disk)
+        } else {
+            match read_attr(&root.host_path(&offline.path)) {
+                Some(state) if state == "offline" => Ok(()),
+                Some(state) => Err(anyhow::anyhow!("Disk {disk} reports state \"{state}\"")),
+                None => Err(anyhow::anyhow!("Cannot read back the state of {disk}")),
+            }
+        };
+        self.record(
+            OfflineStep::VerifyState,
+            format!("/sys/block/{disk}"),
+            result,
+        )
+    }
+
+    /// Power the prepared disk off and check that it went away
+    fn power_off(&mut self, root: &SysfsRoot, disk: &str) -> bool {
+        let node = root.dev_node(disk);
+        let delete = root.block_device(disk).join("device/delete");
+        let (target, result) = match self.actions.power_off(&node) {
+            Ok(()) => (format!("/dev/{disk}"), Ok(())),
+            Err(err) if delete.exists() => (
+                format!("/sys/block/{disk}/device/delete"),
+                self.actions
+                    .write_attr(&delete, "1")
+                    .map_err(|delete_err| anyhow::anyhow!("{err}; {delete_err}")),
+            ),
+            Err(err) => (format!("/dev/{disk}"), Err(err)),
+        };
+        if !self.record(OfflineStep::PowerOff, target, result) {
+            return false;
+        }
+        let result = detached(root, disk);
+        self.record(
+            OfflineStep::VerifyState,
+            format!("/sys/block/{disk}"),
+            result,
+        )
+    }
+
+    fn roll_back(&mut self, root: &SysfsRoot) {
+        while let Some((index, undo)) = self.undo.pop() {
+            let result = match &undo {
+                Undo::Remount(mount) => self.actions.remount(mount),
+                Undo::BringBack(writes) => writes
+                    .iter()
+                    .try_for_each(|w| self.actions.write_attr(&root.host_path(&w.path), &w.value)),
+            };
+            let step = &mut self.steps[index];
+            match result {
+                Ok(()) => step.status = StepStatus::RolledBack,
+                Err(err) => {
+                    step.status = StepStatus::RollbackFailed;
+                    step.message = Some(format!("{err:#}"));
+                }
+            }
+        }
+    }
+}
+
+/// Detached and powered off disks disappear from /sys/block
+fn detached(root: &SysfsRoot, disk: &str) -> Result<()> {
+    if root.block_device(disk).exists() {
+        bail!(
+            DiskError::new(ErrorCode::Failed, format!("Disk {disk} is still present"))
+                .on_disk(disk)
+        );
+    }
+    Ok(())
+}
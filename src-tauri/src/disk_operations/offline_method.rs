@@ -0,0 +1,152 @@
+// src/disk_operations/offline_method.rs
+// How to take a particular disk offline and how to bring it back.
+//
+// Only SCSI disks (SATA, SAS, USB mass storage) have a device/state
+// attribute. Other disks are detached from their driver instead:
+//   NVMe    remove the controller's PCI function, then rescan the PCI bus
+//   SCSI    without state: device/delete, then scan the host for H:C:T:L
+//   others  (virtio, MMC) unbind the device from its driver, then bind it
+// The detached disk disappears from /sys/block until it is brought back.
+use super::sysfs::SysfsRoot;
+use crate::structs::{DiskError, ErrorCode, OfflineMethod, SysfsWrite};
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Strategy {
+    pub method: OfflineMethod,
+    pub offline: SysfsWrite,
+    pub bring_back: Vec<SysfsWrite>,
+}
+
+impl Strategy {
+    /// True when the disk is expected to vanish from /sys/block
+    pub fn detaches(&self) -> bool {
+        self.method != OfflineMethod::ScsiState
+    }
+}
+
+fn write(path: impl Into<String>, value: impl Into<String>) -> SysfsWrite {
+    SysfsWrite {
+        path: path.into(),
+        value: value.into(),
+    }
+}
+
+/// A PCI function directory name such as "0000:3d:00.0"
+fn is_pci_address(name: &str) -> bool {
+    name.len() == 12
+        && name.chars().enumerate().all(|(i, c)| match i {
+            4 | 7 => c == ':',
+            10 => c == '.',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// Canonical path of a sysfs entry as the host sees it, e.g. /sys/bus/...
+fn host_path(root: &SysfsRoot, path: &Path) -> Option<(PathBuf, String)> {
+    let canonical = std::fs::canonicalize(path).ok()?;
+    let base = std::fs::canonicalize(root.root()).ok()?;
+    let rel = canonical.strip_prefix(&base).ok()?;
+    let host = format!("/{}", rel.display());
+    Some((canonical, host))
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+/// The PCI function of the controller behind an nvme namespace
+fn nvme_pci_function(root: &SysfsRoot, disk: &str) -> Option<String> {
+    let (canonical, _) = host_path(root, &root.block_device(disk))?;
+    canonical
+        .ancestors()
+        .filter_map(file_name)
+        .find(|name| is_pci_address(name))
+}
+
+/// Other disks that would go away with the PCI function
+fn disks_sharing(root: &SysfsRoot, disk: &str, function: &str) -> Vec<String> {
+    let mut shared: Vec<String> = std::fs::read_dir(root.block_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(std::result::Result::ok)
+        .filter_map(|e| file_name(&e.path()))
+        .filter(|name| name != disk)
+        .filter(|name| nvme_pci_function(root, name).as_deref() == Some(function))
+        .collect();
+    shared.sort();
+    shared
+}
+
+/// Pick the offline method for `disk`
+///
+/// # Errors
+///
+/// Fails with `NotFound` when `disk` does not exist, and when none of
+/// the methods applies to it or one would take other disks along
+pub fn select(root: &SysfsRoot, disk: &str) -> Result<Strategy> {
+    let device = root.block_device(disk).join("device");
+    if !root.block_device(disk).exists() {
+        bail!(DiskError::new(ErrorCode::NotFound, format!("Disk {disk} not found")).on_disk(disk));
+    }
+
+    let state = device.join("state");
+    if state.exists() {
+        let path = format!("/sys/block/{disk}/device/state");
+        return Ok(Strategy {
+            method: OfflineMethod::ScsiState,
+            offline: write(&path, "offline"),
+            bring_back: vec![write(path, "running")],
+        });
+    }
+
+    if disk.starts_with("nvme") {
+        if let Some(function) = nvme_pci_function(root, disk) {
+            let shared = disks_sharing(root, disk, &function);
+            if !shared.is_empty() {
+                bail!(
+                    "Removing PCI device {function} would also take {} offline",
+                    shared.join(", ")
+                );
+            }
+            return Ok(Strategy {
+                method: OfflineMethod::PciRemove,
+                offline: write(format!("/sys/bus/pci/devices/{function}/remove"), "1"),
+                bring_back: vec![write("/sys/bus/pci/rescan", "1")],
+            });
+        }
+    }
+
+    // SCSI devices are named after their host:channel:target:lun
+    if device.join("delete").exists() {
+        if let Some(address) = host_path(root, &device).and_then(|(c, _)| file_name(&c)) {
+            let parts: Vec<&str> = address.split(':').collect();
+            if let [host, channel, target, lun] = parts[..] {
+                return Ok(Strategy {
+                    method: OfflineMethod::ScsiDelete,
+                    offline: write(format!("/sys/block/{disk}/device/delete"), "1"),
+                    bring_back: vec![write(
+                        format!("/sys/class/scsi_host/host{host}/scan"),
+                        format!("{channel} {target} {lun}"),
+                    )],
+                });
+            }
+        }
+    }
+
+    if let (Some((device, _)), Some((_, driver))) = (
+        host_path(root, &device),
+        host_path(root, &device.join("driver")),
+    ) {
+        if let Some(name) = file_name(&device) {
+            return Ok(Strategy {
+                method: OfflineMethod::DriverUnbind,
+                offline: write(format!("{driver}/unbind"), &name),
+                bring_back: vec![write(format!("{driver}/bind"), name)],
+            });
+        }
+    }
+
+    bail!("Cannot change state for {disk}: no offline method for this device")
+}
@@ -0,0 +1,465 @@
+// src/disk_operations/partition_table.rs
+// MBR and GPT partition table reader. Works on anything readable and
+// seekable, so disk images can be inspected the same way as devices.
+//
+// - MBR: the four primary entries, plus logical partitions found by walking
+//   the EBR chain of an extended partition. A GPT disk carries a protective
+//   MBR (a single 0xEE entry) or a hybrid one (0xEE next to real entries).
+// - GPT: the primary header at LBA 1 and the backup header at the last LBA,
+//   each checked against its CRC32 and the CRC32 of its entry array. The
+//   first consistent copy provides the partitions.
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+const MBR_EXTENDED_TYPES: [u8; 3] = [0x05, 0x0F, 0x85];
+// Guard against EBR chains that loop or never end
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_MIN_HEADER_SIZE: usize = 92;
+const GPT_MIN_ENTRY_SIZE: usize = 128;
+const GPT_MAX_ENTRY_SIZE: usize = 4096;
+const GPT_MAX_ENTRIES: u32 = 4096;
+// Real arrays are 16 KiB; a header asking for more than this is not read
+const GPT_MAX_ARRAY_LEN: usize = 1024 * 1024;
+// Logical sector sizes tried when looking for a GPT header at LBA 1
+const SECTOR_SIZES: [u64; 2] = [512, 4096];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MbrKind {
+    /// A classic DOS partition table
+    Classic,
+    /// Only a 0xEE entry covering the disk, in front of a GPT
+    Protective,
+    /// A 0xEE entry alongside real MBR partitions mirroring part of the GPT
+    Hybrid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MbrEntry {
+    /// 1 to 4 for primary entries, 5 and up for logical partitions
+    pub number: u32,
+    pub bootable: bool,
+    pub type_id: u8,
+    pub first_lba: u64,
+    pub last_lba: u64,
+    /// The extended partition container itself
+    pub extended: bool,
+    pub logical: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mbr {
+    pub kind: MbrKind,
+    pub disk_signature: u32,
+    pub entries: Vec<MbrEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GptHeader {
+    pub current_lba: u64,
+    pub backup_lba: u64,
+    pub first_usable_lba: u64,
+    pub last_usable_lba: u64,
+    pub disk_guid: String,
+    pub entries_lba: u64,
+    pub entry_count: u32,
+    pub entry_size: u32,
+    pub header_crc_valid: bool,
+    pub entries_crc_valid: bool,
+}
+
+impl GptHeader {
+    pub fn is_valid(&self) -> bool {
+        self.header_crc_valid && self.entries_crc_valid
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GptEntry {
+    /// 1-based index in the entry array, the kernel partition number
+    pub number: u32,
+    pub type_guid: String,
+    pub unique_guid: String,
+    pub first_lba: u64,
+    pub last_lba: u64,
+    pub attributes: u64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Gpt {
+    pub disk_guid: String,
+    pub primary: Option<GptHeader>,
+    pub backup: Option<GptHeader>,
+    pub entries: Vec<GptEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PartitionTable {
+    pub sector_size: u64,
+    pub mbr: Option<Mbr>,
+    pub gpt: Option<Gpt>,
+}
+
+/// Read the partition table of a device node or image file, see
+/// `read_partition_table_from`
+///
+/// # Errors
+///
+/// Fails when `path` cannot be opened or read, or holds a corrupt table
+pub fn read_partition_table(path: &Path, sector_size: Option<u64>) -> Result<PartitionTable> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    read_partition_table_from(&mut file, sector_size)
+        .with_context(|| format!("Failed to read partition table of {}", path.display()))
+}
+
+/// Read the MBR and GPT of a disk or image. `sector_size` is the logical
+/// sector size of a device, from `queue/logical_block_size` in sysfs.
+/// Images don't have one: it is then detected from the position of the
+/// GPT header, and taken as 512 for MBR-only images.
+///
+/// # Errors
+///
+/// Fails on read errors, on a sector size that is not a power of two of
+/// at least 512, and on GPT headers or entry arrays that fail their checks
+/// or sizes
+pub fn read_partition_table_from<R: Read + Seek>(
+    reader: &mut R,
+    sector_size: Option<u64>,
+) -> Result<PartitionTable> {
+    let sector_sizes = match &sector_size {
+        Some(size) if *size < 512 || !size.is_power_of_two() => {
+            bail!("Invalid logical sector size {size}")
+        }
+        Some(size) => std::slice::from_ref(size),
+        None => &SECTOR_SIZES[..],
+    };
+    let size = reader.seek(SeekFrom::End(0))?;
+    let mut disk = Disk {
+        reader,
+        size,
+        sector_size: sector_sizes[0],
+    };
+
+    // EBRs are addressed in sectors, so the MBR is read with the first size
+    let mbr = read_mbr(&mut disk)?;
+    let mut gpt = None;
+    for &sector_size in sector_sizes {
+        disk.sector_size = sector_size;
+        if let Some(found) = read_gpt(&mut disk)? {
+            gpt = Some(found);
+            break;
+        }
+    }
+    if gpt.is_none() {
+        disk.sector_size = sector_sizes[0];
+    }
+
+    if mbr.is_none() && gpt.is_none() {
+        bail!("No partition table found");
+    }
+    Ok(PartitionTable {
+        sector_size: disk.sector_size,
+        mbr,
+        gpt,
+    })
+}
+
+struct Disk<'a, R> {
+    reader: &'a mut R,
+    size: u64,
+    sector_size: u64,
+}
+
+impl<R: Read + Seek> Disk<'_, R> {
+    fn sectors(&self) -> u64 {
+        self.size / self.sector_size
+    }
+
+    /// `len` bytes from `lba`, None when past the end of the disk
+    fn read(&mut self, lba: u64, len: usize) -> Result<Option<Vec<u8>>> {
+        let Some(offset) = lba.checked_mul(self.sector_size) else {
+            return Ok(None);
+        };
+        if offset.saturating_add(len as u64) > self.size {
+            return Ok(None);
+        }
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0; len];
+        self.reader
+            .read_exact(&mut buf)
+            .with_context(|| format!("Failed to read {len} bytes at LBA {lba}"))?;
+        Ok(Some(buf))
+    }
+
+    fn read_sector(&mut self, lba: u64) -> Result<Option<Vec<u8>>> {
+        let len = usize::try_from(self.sector_size)?;
+        self.read(lba, len)
+    }
+}
+
+fn le16(b: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([b[offset], b[offset + 1]])
+}
+
+fn le32(b: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&b[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn le64(b: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&b[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// CRC-32 (IEEE 802.3), as used by the GPT header and entry array
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+        }
+    }
+    !crc
+}
+
+/// GUIDs are stored with the first three groups little endian
+pub fn format_guid(b: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        le32(b, 0),
+        le16(b, 4),
+        le16(b, 6),
+        b[8],
+        b[9],
+        b[10],
+        b[11],
+        b[12],
+        b[13],
+        b[14],
+        b[15]
+    )
+}
+
+/// The four slots as (number, raw entry)
+fn mbr_entries(sector: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    (1..=4u32).map(move |number| {
+        let offset = MBR_ENTRIES_OFFSET + 16 * (number as usize - 1);
+        (number, &sector[offset..offset + 16])
+    })
+}
+
+fn mbr_entry(number: u32, raw: &[u8], base_lba: u64, logical: bool) -> Option<MbrEntry> {
+    let type_id = raw[4];
+    let sectors = u64::from(le32(raw, 12));
+    if type_id == 0 || sectors == 0 {
+        return None;
+    }
+    let first_lba = base_lba + u64::from(le32(raw, 8));
+    Some(MbrEntry {
+        number,
+        bootable: raw[0] == 0x80,
+        type_id,
+        first_lba,
+        last_lba: first_lba + sectors - 1,
+        extended: MBR_EXTENDED_TYPES.contains(&type_id),
+        logical,
+    })
+}
+
+fn read_mbr<R: Read + Seek>(disk: &mut Disk<'_, R>) -> Result<Option<Mbr>> {
+    let Some(sector) = disk.read(0, 512)? else {
+        return Ok(None);
+    };
+    if sector[510..512] != MBR_SIGNATURE {
+        return Ok(None);
+    }
+
+    // A filesystem boot sector (FAT, NTFS) also ends in 55 AA, but its bytes
+    // don't make valid boot indicators
+    if mbr_entries(&sector).any(|(_, raw)| raw[0] != 0x00 && raw[0] != 0x80) {
+        return Ok(None);
+    }
+
+    let mut entries: Vec<MbrEntry> = mbr_entries(&sector)
+        .filter_map(|(number, raw)| mbr_entry(number, raw, 0, false))
+        .collect();
+    let has_protective = entries.iter().any(|e| e.type_id == MBR_TYPE_GPT_PROTECTIVE);
+    let kind = match (has_protective, entries.len()) {
+        (true, 1) => MbrKind::Protective,
+        (true, _) => MbrKind::Hybrid,
+        (false, _) => MbrKind::Classic,
+    };
+
+    if let Some(extended) = entries.iter().find(|e| e.extended).cloned() {
+        entries.extend(read_logical_partitions(disk, extended.first_lba)?);
+    }
+
+    Ok(Some(Mbr {
+        kind,
+        disk_signature: le32(&sector, 440),
+        entries,
+    }))
+}
+
+/// Walk the EBR chain. Each EBR describes one logical partition relative to
+/// itself and links to the next EBR relative to the start of the extended
+/// partition.
+fn read_logical_partitions<R: Read + Seek>(
+    disk: &mut Disk<'_, R>,
+    extended_start: u64,
+) -> Result<Vec<MbrEntry>> {
+    let mut logical = Vec::new();
+    let mut visited = HashSet::new();
+    let mut ebr_lba = extended_start;
+    let mut number = 5;
+
+    while logical.len() < MAX_LOGICAL_PARTITIONS && visited.insert(ebr_lba) {
+        let Some(ebr) = disk.read(ebr_lba, 512)? else {
+            break;
+        };
+        if ebr[510..512] != MBR_SIGNATURE {
+            break;
+        }
+        let raw: Vec<&[u8]> = mbr_entries(&ebr).map(|(_, raw)| raw).collect();
+        if let Some(entry) = mbr_entry(number, raw[0], ebr_lba, true) {
+            logical.push(entry);
+            number += 1;
+        }
+        match mbr_entry(0, raw[1], extended_start, false) {
+            Some(next) if next.extended => ebr_lba = next.first_lba,
+            _ => break,
+        }
+    }
+    Ok(logical)
+}
+
+fn read_gpt<R: Read + Seek>(disk: &mut Disk<'_, R>) -> Result<Option<Gpt>> {
+    let primary = read_gpt_header(disk, 1)?;
+    // A damaged primary header can't be trusted to point at the backup
+    let backup_lba = primary
+        .as_ref()
+        .filter(|h| h.header_crc_valid)
+        .map_or_else(|| disk.sectors().saturating_sub(1), |h| h.backup_lba);
+    let backup = if backup_lba > 1 {
+        read_gpt_header(disk, backup_lba)?
+    } else {
+        None
+    };
+    if primary.is_none() && backup.is_none() {
+        return Ok(None);
+    }
+
+    let Some(source) = [&primary, &backup]
+        .into_iter()
+        .flatten()
+        .find(|h| h.is_valid())
+        .cloned()
+    else {
+        bail!("Both GPT headers are corrupt (CRC32 mismatch)");
+    };
+    let entries = read_gpt_entries(disk, &source)?;
+
+    Ok(Some(Gpt {
+        disk_guid: source.disk_guid,
+        primary,
+        backup,
+        entries,
+    }))
+}
+
+fn entry_array_len(entry_count: u32, entry_size: u32) -> usize {
+    entry_count as usize * entry_size as usize
+}
+
+fn read_gpt_header<R: Read + Seek>(disk: &mut Disk<'_, R>, lba: u64) -> Result<Option<GptHeader>> {
+    let Some(sector) = disk.read_sector(lba)? else {
+        return Ok(None);
+    };
+    if &sector[0..8] != GPT_SIGNATURE {
+        return Ok(None);
+    }
+    let header_size = le32(&sector, 12) as usize;
+    let entry_count = le32(&sector, 80);
+    let entry_size = le32(&sector, 84);
+    if !(GPT_MIN_HEADER_SIZE..=sector.len()).contains(&header_size)
+        || !(GPT_MIN_ENTRY_SIZE..=GPT_MAX_ENTRY_SIZE).contains(&(entry_size as usize))
+        || !entry_size.is_power_of_two()
+        || entry_count > GPT_MAX_ENTRIES
+        || entry_array_len(entry_count, entry_size) > GPT_MAX_ARRAY_LEN
+    {
+        return Ok(None);
+    }
+
+    // The header CRC is computed with its own field zeroed
+    let mut header = sector[..header_size].to_vec();
+    header[16..20].fill(0);
+    let header_crc_valid = crc32(&header) == le32(&sector, 16);
+
+    let entries_lba = le64(&sector, 72);
+    let entries_crc_valid = disk
+        .read(entries_lba, entry_array_len(entry_count, entry_size))?
+        .is_some_and(|array| crc32(&array) == le32(&sector, 88));
+
+    Ok(Some(GptHeader {
+        current_lba: le64(&sector, 24),
+        backup_lba: le64(&sector, 32),
+        first_usable_lba: le64(&sector, 40),
+        last_usable_lba: le64(&sector, 48),
+        disk_guid: format_guid(&sector[56..72]),
+        entries_lba,
+        entry_count,
+        entry_size,
+        header_crc_valid,
+        entries_crc_valid,
+    }))
+}
+
+fn read_gpt_entries<R: Read + Seek>(
+    disk: &mut Disk<'_, R>,
+    header: &GptHeader,
+) -> Result<Vec<GptEntry>> {
+    let len = entry_array_len(header.entry_count, header.entry_size);
+    let array = disk
+        .read(header.entries_lba, len)?
+        .context("GPT entry array is past the end of the disk")?;
+
+    let entries = array
+        .chunks_exact(header.entry_size as usize)
+        .zip(1..)
+        .filter(|(raw, _)| raw[0..16].iter().any(|&b| b != 0))
+        .map(|(raw, number)| {
+            let name: Vec<u16> = raw[56..128]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&u| u != 0)
+                .collect();
+            GptEntry {
+                number,
+                type_guid: format_guid(&raw[0..16]),
+                unique_guid: format_guid(&raw[16..32]),
+                first_lba: le64(raw, 32),
+                last_lba: le64(raw, 40),
+                attributes: le64(raw, 48),
+                name: String::from_utf16_lossy(&name),
+            }
+        })
+        .collect();
+    Ok(entries)
+}
@@ -0,0 +1,762 @@
+// src/disk_operations/probe.rs
+// Filesystem and volume signature detection, a small subset of what blkid
+// does. Only the structures needed to recognise the content and read its
+// label and UUID are parsed. Anything readable and seekable can be probed:
+// partitions, whole disks, image files or in-memory buffers.
+//
+// Type names and UUID formats follow blkid so the results can be mixed with
+// the udev database (ID_FS_TYPE, ID_FS_LABEL, ID_FS_UUID).
+use crate::structs::PartitionInfo;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// blkid type name: "ext4", "vfat", `crypto_LUKS`, `LVM2_member`, ...
+    pub fs_type: &'static str,
+    /// Format variant, e.g. "FAT32" for vfat, "2" for LUKS or "1.2" for mdraid
+    pub version: Option<String>,
+    pub label: Option<String>,
+    pub uuid: Option<String>,
+}
+
+impl Signature {
+    fn new(fs_type: &'static str) -> Self {
+        Self {
+            fs_type,
+            version: None,
+            label: None,
+            uuid: None,
+        }
+    }
+}
+
+trait Source: Read + Seek {}
+impl<T: Read + Seek> Source for T {}
+
+struct Device<'a> {
+    source: &'a mut dyn Source,
+    size: u64,
+}
+
+impl Device<'_> {
+    /// Exactly `len` bytes at `offset`, or None if they are out of range or
+    /// unreadable
+    fn read(&mut self, offset: u64, len: usize) -> Option<Vec<u8>> {
+        let end = offset.checked_add(u64::try_from(len).ok()?)?;
+        if end > self.size {
+            return None;
+        }
+        self.source.seek(SeekFrom::Start(offset)).ok()?;
+        let mut buf = vec![0; len];
+        self.source.read_exact(&mut buf).ok()?;
+        Some(buf)
+    }
+}
+
+type Prober = fn(&mut Device<'_>) -> Option<Signature>;
+
+// Containers first: a RAID member or LUKS volume may carry what looks like a
+// filesystem, and mdraid 1.0 / ZFS keep their metadata at the end of the
+// device. FAT goes last because its boot sector is the least distinctive.
+const PROBERS: [Prober; 12] = [
+    probe_luks,
+    probe_lvm2,
+    probe_mdraid,
+    probe_zfs,
+    probe_swap,
+    probe_iso9660,
+    probe_xfs,
+    probe_btrfs,
+    probe_ext,
+    probe_exfat,
+    probe_ntfs,
+    probe_fat,
+];
+
+/// # Errors
+///
+/// Fails when `path` cannot be opened or read
+pub fn probe_path(path: &Path) -> Result<Option<Signature>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    probe_reader(&mut file).with_context(|| format!("Failed to probe {}", path.display()))
+}
+
+/// Identify the content of `reader`. Ok(None) means no known signature.
+///
+/// # Errors
+///
+/// Fails only on read errors; a short or unknown device is Ok(None)
+pub fn probe_reader<R: Read + Seek>(reader: &mut R) -> Result<Option<Signature>> {
+    let size = reader.seek(SeekFrom::End(0))?;
+    let mut device = Device {
+        source: reader,
+        size,
+    };
+    Ok(PROBERS.iter().find_map(|probe| probe(&mut device)))
+}
+
+/// Fill the metadata udev did not provide from a probed signature
+pub fn apply_signature(partition: &mut PartitionInfo, signature: &Signature) {
+    if partition.fs_type.is_none() {
+        partition.fs_type = Some(signature.fs_type.to_string());
+    }
+    if partition.label.is_none() {
+        partition.label.clone_from(&signature.label);
+    }
+    if partition.uuid.is_none() {
+        partition.uuid.clone_from(&signature.uuid);
+    }
+}
+
+fn le16(b: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        b.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn be16(b: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        b.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le32(b: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        b.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn be32(b: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        b.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn le64(b: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        b.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn be64(b: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        b.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// NUL terminated or space padded text field
+fn text(bytes: &[u8]) -> Option<String> {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let s = String::from_utf8_lossy(&bytes[..end]).trim().to_string();
+    (!s.is_empty()).then_some(s)
+}
+
+fn utf16_text(bytes: &[u8]) -> Option<String> {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    let s = String::from_utf16_lossy(&units).trim().to_string();
+    (!s.is_empty()).then_some(s)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+/// 16 raw bytes as 8-4-4-4-12, None when unset (all zero)
+fn uuid(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 16 || bytes.iter().all(|&b| b == 0) {
+        return None;
+    }
+    let h = hex(bytes);
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &h[0..8],
+        &h[8..12],
+        &h[12..16],
+        &h[16..20],
+        &h[20..32]
+    ))
+}
+
+/// FAT and exFAT volume serial numbers are shown as XXXX-XXXX
+fn volume_serial(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF)
+}
+
+// ext2/3/4: superblock at 1 KiB
+const EXT_MAGIC: u16 = 0xEF53;
+const EXT_COMPAT_HAS_JOURNAL: u32 = 0x0004;
+const EXT_INCOMPAT_JOURNAL_DEV: u32 = 0x0008;
+// extents, 64bit, mmp, flex_bg, ea_inode, dirdata, csum_seed, large_dir,
+// inline_data, encrypt, casefold
+const EXT4_INCOMPAT: u32 = 0x0040
+    | 0x0080
+    | 0x0100
+    | 0x0200
+    | 0x0400
+    | 0x1000
+    | 0x2000
+    | 0x4000
+    | 0x8000
+    | 0x1_0000
+    | 0x2_0000;
+// huge_file, gdt_csum, dir_nlink, extra_isize, quota, bigalloc, metadata_csum
+const EXT4_RO_COMPAT: u32 = 0x0008 | 0x0010 | 0x0020 | 0x0040 | 0x0100 | 0x0200 | 0x0400;
+
+fn probe_ext(dev: &mut Device<'_>) -> Option<Signature> {
+    let sb = dev.read(1024, 256)?;
+    if le16(&sb, 0x38)? != EXT_MAGIC {
+        return None;
+    }
+    let compat = le32(&sb, 0x5C)?;
+    let incompat = le32(&sb, 0x60)?;
+    let ro_compat = le32(&sb, 0x64)?;
+
+    let fs_type = if incompat & EXT_INCOMPAT_JOURNAL_DEV != 0 {
+        "jbd"
+    } else if incompat & EXT4_INCOMPAT != 0 || ro_compat & EXT4_RO_COMPAT != 0 {
+        "ext4"
+    } else if compat & EXT_COMPAT_HAS_JOURNAL != 0 {
+        "ext3"
+    } else {
+        "ext2"
+    };
+    Some(Signature {
+        label: text(&sb[0x78..0x88]),
+        uuid: uuid(&sb[0x68..0x78]),
+        ..Signature::new(fs_type)
+    })
+}
+
+fn probe_xfs(dev: &mut Device<'_>) -> Option<Signature> {
+    let sb = dev.read(0, 512)?;
+    if &sb[0..4] != b"XFSB" {
+        return None;
+    }
+    let block_size = be32(&sb, 4)?;
+    if !(512..=65536).contains(&block_size) || !block_size.is_power_of_two() {
+        return None;
+    }
+    Some(Signature {
+        label: text(&sb[108..120]),
+        uuid: uuid(&sb[32..48]),
+        ..Signature::new("xfs")
+    })
+}
+
+// Btrfs: primary superblock at 64 KiB
+const BTRFS_SUPERBLOCK: u64 = 0x1_0000;
+
+fn probe_btrfs(dev: &mut Device<'_>) -> Option<Signature> {
+    let sb = dev.read(BTRFS_SUPERBLOCK, 0x22B)?;
+    if &sb[0x40..0x48] != b"_BHRfS_M" {
+        return None;
+    }
+    Some(Signature {
+        label: text(&sb[0x12B..0x22B]),
+        uuid: uuid(&sb[0x20..0x30]),
+        ..Signature::new("btrfs")
+    })
+}
+
+// NTFS keeps the volume label in the $VOLUME_NAME attribute of MFT record 3
+const NTFS_VOLUME_RECORD: u64 = 3;
+const NTFS_ATTR_VOLUME_NAME: u32 = 0x60;
+const NTFS_ATTR_END: u32 = 0xFFFF_FFFF;
+// Records are 1 KiB in practice; anything outside this is a corrupt boot
+// sector, not something to allocate for
+const NTFS_MIN_RECORD: u64 = 256;
+const NTFS_MAX_RECORD: u64 = 64 * 1024;
+
+fn probe_ntfs(dev: &mut Device<'_>) -> Option<Signature> {
+    let bs = dev.read(0, 512)?;
+    if &bs[3..11] != b"NTFS    " {
+        return None;
+    }
+    let sector_size = le16(&bs, 0x0B)?;
+    if !(256..=4096).contains(&sector_size) || !sector_size.is_power_of_two() {
+        return None;
+    }
+    if bs[0x0D] == 0 {
+        return None;
+    }
+
+    Some(Signature {
+        label: ntfs_volume_record(&bs, sector_size).and_then(|(offset, record_size)| {
+            ntfs_volume_name(dev, offset, record_size, usize::from(sector_size))
+        }),
+        uuid: Some(format!("{:016X}", le64(&bs, 0x48)?)),
+        ..Signature::new("ntfs")
+    })
+}
+
+/// Offset and size of the MFT record holding the volume name, None when
+/// the boot sector's geometry does not add up
+fn ntfs_volume_record(bs: &[u8], sector_size: u16) -> Option<(u64, u64)> {
+    // Values above 0x80 encode 2^(256 - n) sectors
+    let sectors_per_cluster = match bs[0x0D] {
+        n if n > 0x80 => 1u64.checked_shl(256 - u32::from(n))?,
+        n => u64::from(n),
+    };
+    let cluster_size = u64::from(sector_size).checked_mul(sectors_per_cluster)?;
+    // Positive: clusters per record, negative: 2^-n bytes
+    #[allow(clippy::cast_possible_wrap)]
+    let record_size = match bs[0x40] as i8 {
+        n if n > 0 => u64::from(n.unsigned_abs()).checked_mul(cluster_size)?,
+        n => 1u64.checked_shl(u32::from(n.unsigned_abs()))?,
+    };
+    if !(NTFS_MIN_RECORD..=NTFS_MAX_RECORD).contains(&record_size) {
+        return None;
+    }
+    let mft = le64(bs, 0x30)?.checked_mul(cluster_size)?;
+    let offset = mft.checked_add(NTFS_VOLUME_RECORD.checked_mul(record_size)?)?;
+    Some((offset, record_size))
+}
+
+fn ntfs_volume_name(
+    dev: &mut Device<'_>,
+    offset: u64,
+    record_size: u64,
+    sector_size: usize,
+) -> Option<String> {
+    let mut rec = dev.read(offset, usize::try_from(record_size).ok()?)?;
+    if rec.get(0..4)? != b"FILE" {
+        return None;
+    }
+
+    // Update sequence: the last two bytes of every sector were swapped out
+    // for the sequence number and saved in the array
+    let usa_offset = usize::from(le16(&rec, 4)?);
+    let usa_count = usize::from(le16(&rec, 6)?);
+    let usa = rec.get(usa_offset..usa_offset + 2 * usa_count)?.to_vec();
+    for i in 1..usa_count {
+        let end = i * sector_size - 2;
+        let tail = rec.get_mut(end..end + 2)?;
+        if tail != &usa[0..2] {
+            return None;
+        }
+        tail.copy_from_slice(&usa[2 * i..2 * i + 2]);
+    }
+
+    let mut attr = usize::from(le16(&rec, 0x14)?);
+    loop {
+        let kind = le32(&rec, attr)?;
+        let len = usize::try_from(le32(&rec, attr + 4)?).ok()?;
+        if kind == NTFS_ATTR_END || len == 0 {
+            return None;
+        }
+        // Resident attributes only, the volume name always is
+        if kind == NTFS_ATTR_VOLUME_NAME && rec.get(attr + 8) == Some(&0) {
+            let value_len = usize::try_from(le32(&rec, attr + 0x10)?).ok()?;
+            let value = attr + usize::from(le16(&rec, attr + 0x14)?);
+            return utf16_text(rec.get(value..)?.get(..value_len)?);
+        }
+        attr = attr.checked_add(len)?;
+    }
+}
+
+// FAT: the type is decided by the cluster count, as in the Microsoft spec
+const FAT12_MAX_CLUSTERS: u64 = 4085;
+const FAT_DIR_ENTRY: usize = 32;
+const FAT_ATTR_VOLUME_ID: u8 = 0x08;
+const FAT_ATTR_DIRECTORY: u8 = 0x10;
+const FAT_ATTR_LONG_NAME: u8 = 0x0F;
+
+fn probe_fat(dev: &mut Device<'_>) -> Option<Signature> {
+    let bs = dev.read(0, 512)?;
+    if bs[0] != 0xEB && bs[0] != 0xE9 {
+        return None;
+    }
+    let sector_size = u64::from(le16(&bs, 0x0B)?);
+    let sectors_per_cluster = u64::from(bs[0x0D]);
+    let reserved = u64::from(le16(&bs, 0x0E)?);
+    let fats = u64::from(bs[0x10]);
+    let root_entries = u64::from(le16(&bs, 0x11)?);
+    let media = bs[0x15];
+    if !matches!(sector_size, 512 | 1024 | 2048 | 4096)
+        || !sectors_per_cluster.is_power_of_two()
+        || reserved == 0
+        || !(1..=2).contains(&fats)
+        || (media != 0xF0 && media < 0xF8)
+    {
+        return None;
+    }
+
+    let fat16_size = u64::from(le16(&bs, 0x16)?);
+    let is_fat32 = fat16_size == 0;
+    let fat_size = if is_fat32 {
+        u64::from(le32(&bs, 0x24)?)
+    } else {
+        fat16_size
+    };
+    let total = match le16(&bs, 0x13)? {
+        0 => u64::from(le32(&bs, 0x20)?),
+        n => u64::from(n),
+    };
+    if fat_size == 0 || total == 0 {
+        return None;
+    }
+    let root_dir_sectors = (root_entries * FAT_DIR_ENTRY as u64).div_ceil(sector_size);
+    let metadata = reserved + fats * fat_size + root_dir_sectors;
+    let clusters = total.checked_sub(metadata)? / sectors_per_cluster;
+    let version = if is_fat32 {
+        "FAT32"
+    } else if clusters < FAT12_MAX_CLUSTERS {
+        "FAT12"
+    } else {
+        "FAT16"
+    };
+
+    // Extended BPB: signature 0x29 has serial and label, 0x28 only the serial
+    let ebpb = if is_fat32 { 0x40 } else { 0x24 };
+    let (serial, boot_label) = match bs[ebpb + 2] {
+        0x29 => (le32(&bs, ebpb + 3), text(&bs[ebpb + 7..ebpb + 18])),
+        0x28 => (le32(&bs, ebpb + 3), None),
+        _ => (None, None),
+    };
+
+    // The label in the root directory is the one tools update, the boot
+    // sector copy is often stale. Only the first cluster of a FAT32 root
+    // directory is looked at.
+    let (root_start, root_len) = if is_fat32 {
+        let root_cluster = u64::from(le32(&bs, 0x2C)?).checked_sub(2)?;
+        (
+            metadata + root_cluster * sectors_per_cluster,
+            sectors_per_cluster * sector_size,
+        )
+    } else {
+        (
+            reserved + fats * fat_size,
+            root_entries * FAT_DIR_ENTRY as u64,
+        )
+    };
+    let root_label = usize::try_from(root_len)
+        .ok()
+        .and_then(|len| dev.read(root_start * sector_size, len))
+        .and_then(|dir| fat_root_label(&dir));
+
+    Some(Signature {
+        version: Some(version.to_string()),
+        label: root_label.or(boot_label).filter(|l| l != "NO NAME"),
+        uuid: serial.map(volume_serial),
+        ..Signature::new("vfat")
+    })
+}
+
+fn fat_root_label(dir: &[u8]) -> Option<String> {
+    for entry in dir.chunks_exact(FAT_DIR_ENTRY) {
+        match entry[0] {
+            0x00 => break,
+            0xE5 => continue,
+            _ => {}
+        }
+        let attr = entry[11];
+        if attr & FAT_ATTR_LONG_NAME == FAT_ATTR_LONG_NAME {
+            continue;
+        }
+        if attr & FAT_ATTR_VOLUME_ID != 0 && attr & FAT_ATTR_DIRECTORY == 0 {
+            return text(&entry[0..11]);
+        }
+    }
+    None
+}
+
+// exFAT: the label is a directory entry in the root directory
+const EXFAT_ENTRY_LABEL: u8 = 0x83;
+const EXFAT_LABEL_SCAN: u64 = 64 * 1024;
+
+fn probe_exfat(dev: &mut Device<'_>) -> Option<Signature> {
+    let bs = dev.read(0, 512)?;
+    if &bs[3..11] != b"EXFAT   " {
+        return None;
+    }
+    let sector_shift = u32::from(bs[0x6C]);
+    let cluster_shift = u32::from(bs[0x6D]);
+    if !(9..=12).contains(&sector_shift) || sector_shift + cluster_shift > 25 {
+        return None;
+    }
+    let cluster_size = 1u64 << (sector_shift + cluster_shift);
+    let heap = u64::from(le32(&bs, 0x58)?) << sector_shift;
+    let root_cluster = u64::from(le32(&bs, 0x60)?).checked_sub(2)?;
+    let root = heap + root_cluster * cluster_size;
+
+    let scan = usize::try_from(cluster_size.min(EXFAT_LABEL_SCAN)).ok()?;
+    let label = dev.read(root, scan).and_then(|dir| {
+        dir.chunks_exact(32)
+            .take_while(|entry| entry[0] != 0x00)
+            .find(|entry| entry[0] == EXFAT_ENTRY_LABEL)
+            .and_then(|entry| utf16_text(&entry[2..2 + 2 * usize::from(entry[1].min(11))]))
+    });
+
+    Some(Signature {
+        label,
+        uuid: Some(volume_serial(le32(&bs, 0x64)?)),
+        ..Signature::new("exfat")
+    })
+}
+
+// ISO9660: volume descriptors from sector 16, 2 KiB each
+const ISO_SECTOR: u64 = 2048;
+const ISO_FIRST_DESCRIPTOR: u64 = 16;
+const ISO_MAX_DESCRIPTORS: u64 = 32;
+
+fn probe_iso9660(dev: &mut Device<'_>) -> Option<Signature> {
+    for n in ISO_FIRST_DESCRIPTOR..ISO_FIRST_DESCRIPTOR + ISO_MAX_DESCRIPTORS {
+        let vd = dev.read(n * ISO_SECTOR, 2048)?;
+        if &vd[1..6] != b"CD001" {
+            return None;
+        }
+        match vd[0] {
+            // Primary volume descriptor
+            1 => {
+                return Some(Signature {
+                    label: text(&vd[40..72]),
+                    uuid: iso_date_uuid(&vd[813..830]),
+                    ..Signature::new("iso9660")
+                })
+            }
+            // Set terminator
+            255 => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// blkid uses the volume creation time `YYYYMMDDHHMMSScc` as the UUID,
+/// formatted YYYY-MM-DD-HH-MM-SS-cc
+fn iso_date_uuid(date: &[u8]) -> Option<String> {
+    let digits = std::str::from_utf8(date.get(..16)?).ok()?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || digits.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}-{}-{}-{}-{}",
+        &digits[0..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        &digits[14..16]
+    ))
+}
+
+// Swap: the magic ends the first page, whatever the page size is
+const SWAP_PAGE_SIZES: [u64; 4] = [4096, 8192, 16384, 65536];
+
+fn probe_swap(dev: &mut Device<'_>) -> Option<Signature> {
+    for page in SWAP_PAGE_SIZES {
+        let Some(magic) = dev.read(page - 10, 10) else {
+            break;
+        };
+        match &magic[..] {
+            b"SWAPSPACE2" => {
+                // version, last_page, nr_badpages, uuid, volume name
+                let header = dev.read(1024, 44)?;
+                return Some(Signature {
+                    version: Some(le32(&header, 0)?.to_string()),
+                    label: text(&header[28..44]),
+                    uuid: uuid(&header[12..28]),
+                    ..Signature::new("swap")
+                });
+            }
+            b"SWAP-SPACE" => {
+                return Some(Signature {
+                    version: Some("0".to_string()),
+                    ..Signature::new("swap")
+                })
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+const LUKS_MAGIC: &[u8] = b"LUKS\xba\xbe";
+
+fn probe_luks(dev: &mut Device<'_>) -> Option<Signature> {
+    let header = dev.read(0, 208)?;
+    if &header[0..6] != LUKS_MAGIC {
+        return None;
+    }
+    let version = be16(&header, 6)?;
+    Some(Signature {
+        version: Some(version.to_string()),
+        // Only LUKS2 has a label field
+        label: if version == 2 {
+            text(&header[24..72])
+        } else {
+            None
+        },
+        uuid: text(&header[168..208]),
+        ..Signature::new("crypto_LUKS")
+    })
+}
+
+// LVM2: label header in one of the first four sectors
+const LVM_LABEL_SECTORS: u64 = 4;
+const LVM_UUID_GROUPS: [usize; 7] = [6, 4, 4, 4, 4, 4, 6];
+
+fn probe_lvm2(dev: &mut Device<'_>) -> Option<Signature> {
+    for sector in 0..LVM_LABEL_SECTORS {
+        let label = dev.read(sector * 512, 512)?;
+        if &label[0..8] != b"LABELONE" || &label[24..32] != b"LVM2 001" {
+            continue;
+        }
+        let pv_header = usize::try_from(le32(&label, 20)?).ok()?;
+        // The UUID is ASCII; anything else would not split into groups
+        let id = std::str::from_utf8(label.get(pv_header..)?.get(..32)?).ok()?;
+        if !id.is_ascii() {
+            return None;
+        }
+
+        let mut groups = Vec::new();
+        let mut start = 0;
+        for len in LVM_UUID_GROUPS {
+            groups.push(&id[start..start + len]);
+            start += len;
+        }
+        return Some(Signature {
+            version: Some("LVM2 001".to_string()),
+            uuid: Some(groups.join("-")),
+            ..Signature::new("LVM2_member")
+        });
+    }
+    None
+}
+
+// mdraid: 1.1 at the start, 1.2 at 4 KiB, 1.0 and 0.90 near the end
+const MD_MAGIC: u32 = 0xA92B_4EFC;
+const MD_0_90_RESERVED: u64 = 64 * 1024;
+
+fn probe_mdraid(dev: &mut Device<'_>) -> Option<Signature> {
+    let size = dev.size;
+    let mut candidates = vec![(0, "1.1"), (4096, "1.2")];
+    if size >= 8192 {
+        candidates.push(((size - 8192) & !4095, "1.0"));
+    }
+    for (offset, version) in candidates {
+        let Some(sb) = dev.read(offset, 256) else {
+            continue;
+        };
+        if le32(&sb, 0)? == MD_MAGIC && le32(&sb, 4)? == 1 {
+            return Some(Signature {
+                version: Some(version.to_string()),
+                label: text(&sb[32..64]),
+                uuid: uuid(&sb[16..32]),
+                ..Signature::new("linux_raid_member")
+            });
+        }
+    }
+
+    if size < 2 * MD_0_90_RESERVED {
+        return None;
+    }
+    let sb = dev.read((size & !(MD_0_90_RESERVED - 1)) - MD_0_90_RESERVED, 128)?;
+    if le32(&sb, 0)? != MD_MAGIC || le32(&sb, 4)? != 0 {
+        return None;
+    }
+    // set_uuid0 is word 5, set_uuid1..3 are words 13 to 15
+    let mut id = sb[0x14..0x18].to_vec();
+    id.extend_from_slice(&sb[0x34..0x40]);
+    Some(Signature {
+        version: Some("0.90.0".to_string()),
+        uuid: uuid(&id),
+        ..Signature::new("linux_raid_member")
+    })
+}
+
+// ZFS: four 256 KiB labels, two at each end of the device. Each has an XDR
+// nvlist with the pool configuration at 16 KiB and the uberblock ring at
+// 128 KiB.
+const ZFS_LABEL_SIZE: u64 = 256 * 1024;
+const ZFS_NVLIST_OFFSET: u64 = 16 * 1024;
+const ZFS_NVLIST_SIZE: usize = 112 * 1024;
+const ZFS_UBERBLOCK_OFFSET: u64 = 128 * 1024;
+const ZFS_UBERBLOCK_RING: usize = 128 * 1024;
+const ZFS_UBERBLOCK_MAGIC: u64 = 0x00BA_B10C;
+const NV_TYPE_UINT64: u32 = 8;
+const NV_TYPE_STRING: u32 = 9;
+
+fn probe_zfs(dev: &mut Device<'_>) -> Option<Signature> {
+    let size = dev.size;
+    let mut labels = vec![0, ZFS_LABEL_SIZE];
+    if size >= 4 * ZFS_LABEL_SIZE {
+        labels.extend([size - 2 * ZFS_LABEL_SIZE, size - ZFS_LABEL_SIZE]);
+    }
+    for label in labels {
+        let Some(ring) = dev.read(label + ZFS_UBERBLOCK_OFFSET, ZFS_UBERBLOCK_RING) else {
+            continue;
+        };
+        // Uberblocks are at least 1 KiB apart, in the pool's byte order
+        let found = ring.chunks_exact(1024).any(|ub| {
+            le64(ub, 0) == Some(ZFS_UBERBLOCK_MAGIC) || be64(ub, 0) == Some(ZFS_UBERBLOCK_MAGIC)
+        });
+        if !found {
+            continue;
+        }
+        let (name, guid) = dev
+            .read(label + ZFS_NVLIST_OFFSET, ZFS_NVLIST_SIZE)
+            .map(|nvlist| zfs_pool_config(&nvlist))
+            .unwrap_or_default();
+        return Some(Signature {
+            label: name,
+            uuid: guid.map(|g| g.to_string()),
+            ..Signature::new("zfs_member")
+        });
+    }
+    None
+}
+
+/// Pool name and GUID from an XDR encoded nvlist
+fn zfs_pool_config(buf: &[u8]) -> (Option<String>, Option<u64>) {
+    let mut name = None;
+    let mut guid = None;
+    // encoding and endianness bytes, then nvl_version and nvl_nvflag
+    if buf.first() != Some(&1) {
+        return (name, guid);
+    }
+    let mut pos = 12;
+    while let Some(encoded) = be32(buf, pos).and_then(|n| usize::try_from(n).ok()) {
+        if encoded == 0 {
+            break;
+        }
+        let Some(pair) = buf.get(pos..pos + encoded) else {
+            break;
+        };
+        if let Some((key, kind, value)) = nvpair(pair) {
+            match (key, kind) {
+                ("name", NV_TYPE_STRING) => {
+                    name = be32(value, 0)
+                        .and_then(|len| value.get(4..4 + usize::try_from(len).ok()?))
+                        .and_then(text);
+                }
+                ("pool_guid", NV_TYPE_UINT64) => guid = be64(value, 0),
+                _ => {}
+            }
+        }
+        pos += encoded;
+    }
+    (name, guid)
+}
+
+// encoded size, decoded size, name, type, element count, value
+fn nvpair(pair: &[u8]) -> Option<(&str, u32, &[u8])> {
+    let key_len = usize::try_from(be32(pair, 8)?).ok()?;
+    let key = std::str::from_utf8(pair.get(12..12 + key_len)?).ok()?;
+    let offset = 12 + key_len.next_multiple_of(4);
+    let kind = be32(pair, offset)?;
+    Some((key, kind, pair.get(offset + 8..)?))
+}
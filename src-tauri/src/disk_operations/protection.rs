@@ -0,0 +1,74 @@
+// src/disk_operations/protection.rs
+// System disk protection, enforced by the command layer rather than left to
+// the UI. A disk with any protection reason (see `DiskInfo::protection`)
+// can only be taken offline, unmounted or ejected with an explicit override.
+use crate::structs::{DiskInfo, ProtectedOperation, ProtectionReason, ProtectionRefusal};
+
+/// Refuse `operation` on a protected disk unless `override_protection` is set
+///
+/// # Errors
+///
+/// Fails with the refusal to show the user when `disk` is protected
+pub fn check(
+    disk: &DiskInfo,
+    operation: ProtectedOperation,
+    override_protection: bool,
+) -> Result<(), ProtectionRefusal> {
+    refuse(
+        &disk.id,
+        &format!("Disk {}", disk.id),
+        &disk.protection,
+        operation,
+        override_protection,
+    )
+}
+
+/// Refuse `operation` on a mount point or device that no disk in the
+/// inventory holds, such as / on an LVM volume, given the reasons the
+/// backend found for protecting it
+///
+/// # Errors
+///
+/// Fails with the refusal to show the user when `reasons` is not empty
+pub fn check_target(
+    target: &str,
+    reasons: &[ProtectionReason],
+    operation: ProtectedOperation,
+    override_protection: bool,
+) -> Result<(), ProtectionRefusal> {
+    refuse(target, target, reasons, operation, override_protection)
+}
+
+fn refuse(
+    id: &str,
+    subject: &str,
+    protection: &[ProtectionReason],
+    operation: ProtectedOperation,
+    override_protection: bool,
+) -> Result<(), ProtectionRefusal> {
+    if protection.is_empty() || override_protection {
+        return Ok(());
+    }
+    let reasons: Vec<String> = protection.iter().map(ToString::to_string).collect();
+    Err(ProtectionRefusal {
+        disk_id: id.to_string(),
+        operation,
+        reasons: protection.to_vec(),
+        message: format!(
+            "{subject} {}: {operation} refused without an explicit override",
+            reasons.join(" and ")
+        ),
+    })
+}
+
+/// The disk holding a mount target: a Windows drive letter ("E", "E:" or
+/// "E:\") or a Linux mount point
+pub fn disk_for_target<'a>(disks: &'a [DiskInfo], target: &str) -> Option<&'a DiskInfo> {
+    let letter = target.trim_end_matches(['\\', '/']).trim_end_matches(':');
+    disks.iter().find(|disk| {
+        disk.partitions.iter().any(|p| {
+            (!p.drive_letter.is_empty() && p.drive_letter.eq_ignore_ascii_case(letter))
+                || p.mount_points.iter().any(|m| m.path == target)
+        })
+    })
+}
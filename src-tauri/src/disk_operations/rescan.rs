@@ -0,0 +1,89 @@
+// src/disk_operations/rescan.rs
+// Ask the kernel to look for disks again, to bring back disks that were
+// deleted from the SCSI layer, powered off or whose controller was removed:
+//   /sys/bus/pci/rescan                       probe removed PCI functions
+//   /sys/class/scsi_host/hostN/scan           "- - -" scans every channel,
+//                                             target and LUN of the host
+//   /sys/class/nvme/nvmeN/rescan_controller   look for new namespaces
+// Disks are enumerated before and after, the difference is what came back.
+use super::offline::{self, OfflineActions};
+use super::sysfs::{enumerate_disks, SysfsRoot};
+use crate::structs::{RescanReport, SysfsWrite};
+use anyhow::Result;
+use std::path::Path;
+
+/// Entries of a sysfs class directory, sorted by name
+fn class_entries(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(std::result::Result::ok)
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// The scan requests for this machine, as host paths
+pub fn scan_writes(root: &SysfsRoot) -> Vec<SysfsWrite> {
+    let mut writes = Vec::new();
+    let write = |path: String, value: &str| SysfsWrite {
+        path,
+        value: value.to_string(),
+    };
+
+    if root.host_path("/sys/bus/pci/rescan").exists() {
+        writes.push(write("/sys/bus/pci/rescan".to_string(), "1"));
+    }
+    for host in class_entries(&root.host_path("/sys/class/scsi_host")) {
+        let path = format!("/sys/class/scsi_host/{host}/scan");
+        if root.host_path(&path).exists() {
+            writes.push(write(path, "- - -"));
+        }
+    }
+    for controller in class_entries(&root.host_path("/sys/class/nvme")) {
+        let path = format!("/sys/class/nvme/{controller}/rescan_controller");
+        if root.host_path(&path).exists() {
+            writes.push(write(path, "1"));
+        }
+    }
+    writes
+}
+
+/// Rescan every bus and report the disks that appeared. `settle` waits for
+/// the kernel and udev to finish probing before the second enumeration.
+///
+/// # Errors
+///
+/// Fails when the disks cannot be enumerated; scan writes that fail
+/// are reported, not returned
+pub fn rescan(
+    root: &SysfsRoot,
+    actions: &mut impl OfflineActions,
+    settle: impl FnOnce(),
+) -> Result<RescanReport> {
+    let before: Vec<String> = enumerate_disks(root)?.into_iter().map(|d| d.id).collect();
+
+    let mut scanned = Vec::new();
+    let mut errors = Vec::new();
+    for write in scan_writes(root) {
+        match actions.write_attr(&root.host_path(&write.path), &write.value) {
+            Ok(()) => scanned.push(write),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    settle();
+
+    let reappeared: Vec<_> = enumerate_disks(root)?
+        .into_iter()
+        .filter(|d| !before.contains(&d.id))
+        .collect();
+    let ids: Vec<String> = reappeared.iter().map(|d| d.id.clone()).collect();
+    offline::forget_offline(root, &ids)?;
+
+    Ok(RescanReport {
+        scanned,
+        errors,
+        reappeared,
+    })
+}
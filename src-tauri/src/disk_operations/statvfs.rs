@@ -0,0 +1,36 @@
+// src/disk_operations/statvfs.rs
+// Space and inode usage of mounted filesystems via statvfs(3)
+use crate::structs::FilesystemUsage;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Query the filesystem mounted at `mount_point`. None if the path can't be
+/// queried (not mounted, stale network mount, permission denied).
+#[allow(clippy::unnecessary_cast)] // statvfs field widths differ between targets
+pub fn filesystem_usage(mount_point: &Path) -> Option<FilesystemUsage> {
+    let path = CString::new(mount_point.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), std::ptr::addr_of_mut!(stat)) } != 0 {
+        return None;
+    }
+
+    // Block counts are in units of f_frsize, not f_bsize
+    let fragment = stat.f_frsize as u64;
+    let blocks = stat.f_blocks as u64;
+    let free = stat.f_bfree as u64;
+    let available = (stat.f_bavail as u64).min(free);
+    let files = stat.f_files as u64;
+    let free_files = (stat.f_ffree as u64).min(files);
+
+    Some(FilesystemUsage {
+        total_bytes: blocks * fragment,
+        used_bytes: blocks.saturating_sub(free) * fragment,
+        free_bytes: free * fragment,
+        available_bytes: available * fragment,
+        reserved_bytes: (free - available) * fragment,
+        total_inodes: files,
+        used_inodes: files - free_files,
+        free_inodes: free_files,
+    })
+}
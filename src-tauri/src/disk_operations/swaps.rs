@@ -0,0 +1,41 @@
+// src/disk_operations/swaps.rs
+// Parser for /proc/swaps
+use super::mountinfo::unescape;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapEntry {
+    /// Device node or swap file path
+    pub path: String,
+    /// True for a swap partition or device, false for a swap file
+    pub is_partition: bool,
+}
+
+/// # Errors
+///
+/// Fails when `path` cannot be read
+pub fn read_swaps(path: &Path) -> Result<Vec<SwapEntry>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(parse_swaps(&content))
+}
+
+// Filename                Type        Size      Used  Priority
+// /dev/nvme0n1p3          partition   8388604   0     -2
+// /swap\040file           file        2097148   0     -3
+pub fn parse_swaps(content: &str) -> Vec<SwapEntry> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let path = fields.next()?;
+            let kind = fields.next()?;
+            Some(SwapEntry {
+                path: unescape(path),
+                is_partition: kind == "partition",
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,487 @@
+// src/disk_operations/sysfs.rs
+// Native disk enumeration from /sys/block, the udev database and mountinfo.
+// Everything is read relative to a configurable root so that the same code
+// can run against a fake tree in tests.
+use super::capabilities::{self, DiskFacts};
+use super::mountinfo::{read_mountinfo, MountEntry};
+use super::partition_table::{read_partition_table, PartitionTable};
+use super::probe;
+use super::statvfs::filesystem_usage;
+use super::system_devices::{mount_dev, SystemDevices};
+use crate::structs::{DiskInfo, DiskType, MountPoint, PartitionInfo};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+
+// Sector size used by the kernel for the sysfs `size` attribute, regardless of
+// the logical block size of the device.
+const SYSFS_SECTOR_SIZE: u64 = 512;
+
+// SCSI peripheral device type for CD/DVD drives (device/type)
+const SCSI_TYPE_ROM: &str = "5";
+
+#[derive(Debug, Clone)]
+pub struct SysfsRoot {
+    root: PathBuf,
+}
+
+impl SysfsRoot {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The live system, rooted at "/"
+    pub fn system() -> Self {
+        Self::new("/")
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn block_dir(&self) -> PathBuf {
+        self.root.join("sys/block")
+    }
+
+    pub fn block_device(&self, name: &str) -> PathBuf {
+        self.block_dir().join(name)
+    }
+
+    /// /sys/class/block has an entry for every disk and partition
+    pub fn class_block_device(&self, name: &str) -> PathBuf {
+        self.root.join("sys/class/block").join(name)
+    }
+
+    pub fn dev_node(&self, name: &str) -> PathBuf {
+        self.root.join("dev").join(name)
+    }
+
+    /// A path as seen by the host (e.g. a mount point from mountinfo),
+    /// relocated under this root
+    pub fn host_path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    pub fn mountinfo_path(&self) -> PathBuf {
+        self.root.join("proc/self/mountinfo")
+    }
+
+    pub fn swaps_path(&self) -> PathBuf {
+        self.root.join("proc/swaps")
+    }
+
+    /// Disks this app took offline, kept in /run so that a reboot, which
+    /// brings them all back, also forgets them
+    pub fn offline_records_path(&self) -> PathBuf {
+        self.root.join("run/diskofflaner/offline.json")
+    }
+
+    fn udev_data_path(&self, dev: &str) -> PathBuf {
+        self.root.join("run/udev/data").join(format!("b{dev}"))
+    }
+}
+
+impl Default for SysfsRoot {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+/// Read a sysfs attribute, trimmed. Empty values are treated as missing.
+pub fn read_attr(path: &Path) -> Option<String> {
+    let value = std::fs::read_to_string(path).ok()?;
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn read_size_bytes(dir: &Path) -> u64 {
+    read_attr(&dir.join("size"))
+        .and_then(|s| s.parse::<u64>().ok())
+        .map_or(0, |sectors| sectors * SYSFS_SECTOR_SIZE)
+}
+
+fn read_flag(path: &Path) -> Option<bool> {
+    read_attr(path).map(|v| v == "1")
+}
+
+/// Properties udev recorded for a device (E: lines of /run/udev/data/b<maj>:<min>)
+pub fn read_udev_properties(root: &SysfsRoot, dev: &str) -> HashMap<String, String> {
+    let Ok(content) = std::fs::read_to_string(root.udev_data_path(dev)) else {
+        return HashMap::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| line.strip_prefix("E:"))
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.trim().to_string()))
+        .filter(|(_, v)| !v.is_empty())
+        .collect()
+}
+
+/// Decode the \xNN escapes udev uses in *_ENC properties
+fn decode_udev_enc(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x') && i + 4 <= bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 2..i + 4]).unwrap_or_default();
+            if let Ok(value) = u8::from_str_radix(hex, 16) {
+                out.push(value);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// GPT partition attribute bits (UEFI spec 5.3.3) and the MBR boot indicator
+const GPT_FLAGS: [(u32, &str); 6] = [
+    (0, "required"),
+    (1, "no-block-io"),
+    (2, "legacy-bios-bootable"),
+    (60, "read-only"),
+    (62, "hidden"),
+    (63, "no-automount"),
+];
+const MBR_BOOT_FLAG: u64 = 0x80;
+
+/// Turn a partition table flags value (e.g. "0x8000000000000000") into names
+pub fn decode_partition_flags(scheme: Option<&str>, flags: &str) -> Vec<String> {
+    let digits = flags.trim_start_matches("0x").trim_start_matches("0X");
+    let Ok(value) = u64::from_str_radix(digits, 16) else {
+        return vec![];
+    };
+
+    match scheme {
+        Some("gpt") => GPT_FLAGS
+            .iter()
+            .filter(|(bit, _)| value & (1 << bit) != 0)
+            .map(|(_, name)| (*name).to_string())
+            .collect(),
+        Some("dos") if value & MBR_BOOT_FLAG != 0 => vec!["boot".to_string()],
+        _ => vec![],
+    }
+}
+
+/// Fill filesystem metadata by reading the signature from the device node.
+/// Nodes that are missing or not readable by this user are skipped.
+pub fn probe_partition(root: &SysfsRoot, partition: &mut PartitionInfo) {
+    let node = root.dev_node(&partition.partition_id);
+    if let Ok(Some(signature)) = probe::probe_path(&node) {
+        probe::apply_signature(partition, &signature);
+    }
+}
+
+/// Fill filesystem and partition table metadata from udev properties
+pub fn apply_udev_metadata<S: BuildHasher>(
+    partition: &mut PartitionInfo,
+    udev: &HashMap<String, String, S>,
+) {
+    let get = |key: &str| udev.get(key).cloned();
+
+    partition.fs_type = get("ID_FS_TYPE");
+    partition.label = get("ID_FS_LABEL_ENC")
+        .map(|l| decode_udev_enc(&l))
+        .or_else(|| get("ID_FS_LABEL"));
+    partition.uuid = get("ID_FS_UUID");
+    partition.part_uuid = get("ID_PART_ENTRY_UUID");
+    partition.part_type = get("ID_PART_ENTRY_TYPE");
+    partition.part_flags = get("ID_PART_ENTRY_FLAGS")
+        .map(|f| decode_partition_flags(udev.get("ID_PART_ENTRY_SCHEME").map(String::as_str), &f))
+        .unwrap_or_default();
+}
+
+/// Fill the partition table metadata udev would have given from the table
+/// itself: type, unique id and flags of the entry with the partition's
+/// number, in udev's notation. Values already known are kept.
+pub fn apply_partition_table(partition: &mut PartitionInfo, table: &PartitionTable) {
+    let number = partition.partition_number;
+    let (part_type, part_uuid, flags) = if let Some(gpt) = &table.gpt {
+        let Some(entry) = gpt.entries.iter().find(|e| e.number == number) else {
+            return;
+        };
+        let flags = decode_partition_flags(Some("gpt"), &format!("{:#x}", entry.attributes));
+        (entry.type_guid.clone(), entry.unique_guid.clone(), flags)
+    } else if let Some(mbr) = &table.mbr {
+        let Some(entry) = mbr.entries.iter().find(|e| e.number == number) else {
+            return;
+        };
+        let flags = if entry.bootable {
+            vec!["boot".to_string()]
+        } else {
+            vec![]
+        };
+        let part_uuid = format!("{:08x}-{number:02x}", mbr.disk_signature);
+        (format!("{:#04x}", entry.type_id), part_uuid, flags)
+    } else {
+        return;
+    };
+
+    partition.part_type.get_or_insert(part_type);
+    partition.part_uuid.get_or_insert(part_uuid);
+    if partition.part_flags.is_empty() {
+        partition.part_flags = flags;
+    }
+}
+
+/// A whole disk is a /sys/block entry backed by a real device. Virtual devices
+/// (loop, dm-*, md*, zram, nbd) have no `device` link, optical drives are
+/// filtered by their SCSI type.
+fn is_physical_disk(dir: &Path) -> bool {
+    let device = dir.join("device");
+    if !device.exists() {
+        return false;
+    }
+    read_attr(&device.join("type")).as_deref() != Some(SCSI_TYPE_ROM)
+}
+
+/// Work out the transport from where the device sits in the sysfs device tree,
+/// e.g. /sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/.../block/sdb
+fn detect_transport(dir: &Path, name: &str) -> Option<String> {
+    if name.starts_with("nvme") {
+        return Some("nvme".to_string());
+    }
+
+    let resolved = std::fs::canonicalize(dir).ok()?;
+    let components: Vec<String> = resolved
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    let has = |prefix: &str| components.iter().any(|c| c.starts_with(prefix));
+    let transport = if has("usb") {
+        "usb"
+    } else if has("virtio") {
+        "virtio"
+    } else if has("mmc_host") || name.starts_with("mmcblk") {
+        "mmc"
+    } else if has("ata") {
+        "sata"
+    } else {
+        return None;
+    };
+    Some(transport.to_string())
+}
+
+pub fn classify_disk(
+    transport: Option<&str>,
+    removable: bool,
+    rotational: Option<bool>,
+) -> DiskType {
+    // Check transport type first
+    match transport {
+        Some("nvme") => return DiskType::NVMe,
+        Some("usb") => {
+            return if removable {
+                DiskType::USBFlash
+            } else {
+                DiskType::ExtHDD
+            };
+        }
+        _ => {}
+    }
+
+    // Check rotational flag (SSD vs HDD)
+    match rotational {
+        Some(false) => DiskType::SSD,
+        // Default to HDD
+        _ => DiskType::HDD,
+    }
+}
+
+/// Mounts of the device `dev`. btrfs mounts carry an anonymous device number
+/// and are matched through their source instead.
+pub fn mount_points_for<'a>(
+    root: &SysfsRoot,
+    mounts: &'a [MountEntry],
+    dev: Option<&str>,
+) -> Vec<&'a MountEntry> {
+    match dev {
+        Some(dev) => mounts
+            .iter()
+            .filter(|m| mount_dev(root, m).as_deref() == Some(dev))
+            .collect(),
+        None => vec![],
+    }
+}
+
+fn read_partitions(
+    root: &SysfsRoot,
+    disk_dir: &Path,
+    mounts: &[MountEntry],
+) -> Result<Vec<PartitionInfo>> {
+    let mut partitions = Vec::new();
+
+    for entry in std::fs::read_dir(disk_dir)? {
+        let entry = entry?;
+        let part_dir = entry.path();
+        let Some(number) = read_attr(&part_dir.join("partition")).and_then(|n| n.parse().ok())
+        else {
+            continue;
+        };
+
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let dev = read_attr(&part_dir.join("dev"));
+        let mount_points: Vec<MountPoint> = mount_points_for(root, mounts, dev.as_deref())
+            .into_iter()
+            .map(MountEntry::to_mount_point)
+            .collect();
+        // Unmounted partitions have no filesystem to ask, their usage is unknown.
+        // All mounts of a device share one filesystem, so any of them will do.
+        let usage = mount_points
+            .first()
+            .and_then(|m| filesystem_usage(&root.host_path(&m.path)));
+
+        let mut partition = PartitionInfo {
+            partition_number: number,
+            size_bytes: read_size_bytes(&part_dir),
+            used_bytes: usage.as_ref().map(|u| u.used_bytes),
+            drive_letter: String::new(),
+            partition_id: name, // Using partition_id for device name (e.g. sda1)
+            mount_points,
+            usage,
+            fs_type: None,
+            label: None,
+            uuid: None,
+            part_uuid: None,
+            part_type: None,
+            part_flags: Vec::new(),
+        };
+        if let Some(dev) = dev.as_deref() {
+            apply_udev_metadata(&mut partition, &read_udev_properties(root, dev));
+        }
+        // No udev record (containers, minimal systems): look at the content
+        if partition.fs_type.is_none() {
+            probe_partition(root, &mut partition);
+        }
+        // Without a udev database the kernel still knows what it mounted
+        if partition.fs_type.is_none() {
+            partition.fs_type = partition.mount_points.first().map(|m| m.fs_type.clone());
+        }
+        partitions.push(partition);
+    }
+
+    partitions.sort_by_key(|p| p.partition_number);
+    Ok(partitions)
+}
+
+fn read_disk(
+    root: &SysfsRoot,
+    name: &str,
+    mounts: &[MountEntry],
+    system: &SystemDevices,
+) -> Result<DiskInfo> {
+    let dir = root.block_device(name);
+    let device = dir.join("device");
+    let dev = read_attr(&dir.join("dev"));
+    let udev = dev
+        .as_deref()
+        .map(|d| read_udev_properties(root, d))
+        .unwrap_or_default();
+    let udev = |key: &str| udev.get(key).cloned();
+
+    let model = read_attr(&device.join("model"))
+        .or_else(|| read_attr(&device.join("name"))) // MMC cards
+        .or_else(|| udev("ID_MODEL").map(|m| m.replace('_', " ")))
+        .unwrap_or_else(|| format!("Disk {name}"));
+    let serial_number = read_attr(&device.join("serial")).or_else(|| udev("ID_SERIAL_SHORT"));
+
+    // Check state. If state is "offline", then it's offline.
+    let is_online = read_attr(&device.join("state")).as_deref() != Some("offline");
+
+    let transport = detect_transport(&dir, name).or_else(|| udev("ID_BUS"));
+    let removable = read_flag(&dir.join("removable")).unwrap_or(false);
+    let rotational = read_flag(&dir.join("queue/rotational"));
+    let disk_type = classify_disk(transport.as_deref(), removable, rotational);
+    let capabilities = capabilities::detect(
+        root,
+        name,
+        &DiskFacts {
+            transport: transport.as_deref(),
+            removable,
+            rotational,
+            is_online,
+        },
+    );
+
+    let mut partitions = read_partitions(root, &dir, mounts)?;
+    // Without a udev record the partition table itself says what each
+    // partition is. The node is only readable with enough privileges.
+    if partitions.iter().any(|p| p.part_type.is_none()) {
+        let sector_size =
+            read_attr(&dir.join("queue/logical_block_size")).and_then(|s| s.parse().ok());
+        if let Ok(table) = read_partition_table(&root.dev_node(name), sector_size) {
+            for partition in &mut partitions {
+                apply_partition_table(partition, &table);
+            }
+        }
+    }
+    let usage_percentage = crate::utils::usage_percentage(&partitions);
+
+    // The disk itself may carry a filesystem or swap, without partitions
+    let devs: Vec<String> = dev
+        .into_iter()
+        .chain(
+            partitions
+                .iter()
+                .filter_map(|p| read_attr(&root.class_block_device(&p.partition_id).join("dev"))),
+        )
+        .collect();
+    let protection = system.reasons_for(&devs);
+
+    Ok(DiskInfo {
+        id: name.to_string(),
+        model,
+        size_bytes: read_size_bytes(&dir),
+        is_online,
+        is_system_disk: !protection.is_empty(),
+        protection,
+        partitions,
+        disk_type,
+        serial_number,
+        health_percentage: None,
+        usage_percentage,
+        capabilities,
+    })
+}
+
+/// Kernel name of the block device with the given numbers, via /sys/dev/block
+pub fn device_name_for_dev(root: &SysfsRoot, major: u32, minor: u32) -> Option<String> {
+    let link = root.root().join(format!("sys/dev/block/{major}:{minor}"));
+    let target = std::fs::read_link(link).ok()?;
+    Some(target.file_name()?.to_string_lossy().into_owned())
+}
+
+/// # Errors
+///
+/// Fails when the block directory cannot be read
+pub fn enumerate_disks(root: &SysfsRoot) -> Result<Vec<DiskInfo>> {
+    let block_dir = root.block_dir();
+    let entries = std::fs::read_dir(&block_dir)
+        .with_context(|| format!("Cannot read {}", block_dir.display()))?;
+
+    // A missing mountinfo only means we cannot report mount points
+    let mounts = read_mountinfo(&root.mountinfo_path()).unwrap_or_default();
+    let system = SystemDevices::detect(root, &mounts);
+
+    let mut names: Vec<String> = entries
+        .filter_map(std::result::Result::ok)
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| is_physical_disk(&root.block_device(name)))
+        .collect();
+    names.sort();
+
+    names
+        .iter()
+        .map(|name| read_disk(root, name, &mounts, &system))
+        .collect()
+}
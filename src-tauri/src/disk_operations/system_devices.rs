@@ -0,0 +1,225 @@
+// src/disk_operations/system_devices.rs
+// Which block devices the running system depends on: the root filesystem,
+// /boot and the EFI system partition, and active swap. Disks holding any of
+// them are protected from offline, unmount and eject.
+//
+// Volumes on LVM, LUKS, mdraid or other device-mapper targets are followed
+// through `slaves` in sysfs down to the partitions and disks underneath, so
+// that every disk in the stack is protected.
+use super::mountinfo::{read_mountinfo, MountEntry};
+use super::swaps::{read_swaps, SwapEntry};
+use super::sysfs::{device_name_for_dev, read_attr, SysfsRoot};
+use crate::structs::{DiskError, ErrorCode, ProtectionReason};
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+const BOOT_MOUNT_POINTS: [&str; 3] = ["/boot", "/boot/efi", "/efi"];
+
+/// Device numbers ("major:minor") backing the running system. Each list
+/// holds the mounted device and everything it is stacked on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemDevices {
+    pub root: Vec<String>,
+    pub boot: Vec<String>,
+    pub swap: Vec<String>,
+    /// Devices reached only through the stack, e.g. the partitions under an
+    /// LVM volume group or the members of a RAID array
+    pub stacked: Vec<String>,
+}
+
+impl SystemDevices {
+    pub fn detect(root: &SysfsRoot, mounts: &[MountEntry]) -> Self {
+        let swaps = read_swaps(&root.swaps_path()).unwrap_or_default();
+        Self::from_mounts(root, mounts, &swaps)
+    }
+
+    pub fn from_mounts(root: &SysfsRoot, mounts: &[MountEntry], swaps: &[SwapEntry]) -> Self {
+        let devs_at = |paths: &[&str]| -> Vec<String> {
+            mounts
+                .iter()
+                .filter(|m| paths.contains(&m.mount_point.as_str()))
+                .filter_map(|m| mount_dev(root, m))
+                .collect()
+        };
+
+        let swap = swaps
+            .iter()
+            .filter_map(|s| {
+                if s.is_partition {
+                    dev_of_node(root, &s.path)
+                } else {
+                    file_dev(root, mounts, &s.path)
+                }
+            })
+            .collect();
+
+        let mut stacked = BTreeSet::new();
+        let mut expand = |devs: Vec<String>| -> Vec<String> {
+            let mut chain = BTreeSet::new();
+            for dev in devs {
+                let below = lower_devices(root, &dev);
+                stacked.extend(below.iter().cloned());
+                chain.insert(dev);
+                chain.extend(below);
+            }
+            chain.into_iter().collect()
+        };
+
+        Self {
+            root: expand(devs_at(&["/"])),
+            boot: expand(devs_at(&BOOT_MOUNT_POINTS)),
+            swap: expand(swap),
+            stacked: stacked.into_iter().collect(),
+        }
+    }
+
+    /// Reasons to protect a disk whose own device and partitions have the
+    /// given device numbers
+    pub fn reasons_for(&self, devs: &[String]) -> Vec<ProtectionReason> {
+        let hit = |set: &[String]| devs.iter().any(|d| set.contains(d));
+        let mut reasons = Vec::new();
+        if hit(&self.root) {
+            reasons.push(ProtectionReason::RootFilesystem);
+        }
+        if hit(&self.boot) {
+            reasons.push(ProtectionReason::Boot);
+        }
+        if hit(&self.swap) {
+            reasons.push(ProtectionReason::Swap);
+        }
+        if hit(&self.stacked) {
+            reasons.push(ProtectionReason::RootStack);
+        }
+        reasons
+    }
+}
+
+/// Reasons to protect the filesystem mounted at `target`, or the device
+/// node `target`, when no partition in the inventory holds it: / on an LVM
+/// volume, swap on a LUKS mapping. The device it is mounted from and
+/// everything below it are checked against the system's devices, and the
+/// system mount points themselves are protected whatever they are backed by.
+///
+/// # Errors
+///
+/// Fails with `NotMounted` when `target` is neither mounted nor a device
+/// node, so that an unknown target is refused rather than let through
+pub fn target_protection(root: &SysfsRoot, target: &str) -> Result<Vec<ProtectionReason>> {
+    let mounts = read_mountinfo(&root.mountinfo_path())?;
+    // With stacked mounts on the same path the last entry is the visible one
+    let dev = match mounts.iter().rev().find(|m| m.mount_point == target) {
+        Some(mount) => mount_dev(root, mount),
+        None => match dev_of_node(root, target) {
+            Some(dev) => Some(dev),
+            None => bail!(DiskError::new(
+                ErrorCode::NotMounted,
+                format!("{target} is neither a mount point nor a device")
+            )
+            .on_partition(target)),
+        },
+    };
+
+    let mut chain: Vec<String> = dev.iter().flat_map(|d| lower_devices(root, d)).collect();
+    chain.extend(dev);
+    let mut reasons = SystemDevices::detect(root, &mounts).reasons_for(&chain);
+    if target == "/" && !reasons.contains(&ProtectionReason::RootFilesystem) {
+        reasons.insert(0, ProtectionReason::RootFilesystem);
+    }
+    if BOOT_MOUNT_POINTS.contains(&target) && !reasons.contains(&ProtectionReason::Boot) {
+        reasons.push(ProtectionReason::Boot);
+    }
+    Ok(reasons)
+}
+
+/// Everything `dev` is built on, found by following `slaves` in sysfs: a
+/// dm-crypt volume to its partition, an LVM volume to its physical volumes,
+/// an md array to its members. A partition of a stacked device (md0p1) is
+/// followed to the device itself. `dev` itself is not included.
+pub fn lower_devices(root: &SysfsRoot, dev: &str) -> Vec<String> {
+    let mut found = BTreeSet::new();
+    let mut pending: Vec<String> = dev_name(root, dev).into_iter().collect();
+    let mut seen = BTreeSet::new();
+
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let dir = root.class_block_device(&name);
+        let mut below = list_dir(&dir.join("slaves"));
+        if below.is_empty() && dir.join("partition").exists() {
+            // The parent of a partition is the directory above it. Only a
+            // stacked parent leads anywhere, a physical disk is the bottom.
+            let parent = std::fs::canonicalize(&dir)
+                .ok()
+                .and_then(|p| Some(p.parent()?.file_name()?.to_string_lossy().into_owned()));
+            below.extend(parent.filter(|parent| {
+                !list_dir(&root.class_block_device(parent).join("slaves")).is_empty()
+            }));
+        }
+        for lower in below {
+            if let Some(lower_dev) = read_attr(&root.class_block_device(&lower).join("dev")) {
+                found.insert(lower_dev);
+            }
+            pending.push(lower);
+        }
+    }
+    found.into_iter().collect()
+}
+
+/// Kernel name of a "major:minor" device number
+fn dev_name(root: &SysfsRoot, dev: &str) -> Option<String> {
+    let (major, minor) = dev.split_once(':')?;
+    device_name_for_dev(root, major.parse().ok()?, minor.parse().ok()?)
+}
+
+fn list_dir(dir: &Path) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(std::result::Result::ok)
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Device number of a mount. Filesystems such as btrfs report an anonymous
+/// device (major 0) in mountinfo, their block device is the mount source.
+pub fn mount_dev(root: &SysfsRoot, mount: &MountEntry) -> Option<String> {
+    if mount.major == 0 {
+        dev_of_node(root, &mount.source)
+    } else {
+        Some(mount.dev())
+    }
+}
+
+/// Device number of a node such as /dev/sda2 or /dev/mapper/vg-swap, which
+/// is a symlink to the dm-N node
+pub fn dev_of_node(root: &SysfsRoot, node: &str) -> Option<String> {
+    if !node.starts_with("/dev/") {
+        return None;
+    }
+    let name = std::fs::canonicalize(root.host_path(node))
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .or_else(|| Some(Path::new(node).file_name()?.to_string_lossy().into_owned()))?;
+    read_attr(&root.class_block_device(&name).join("dev"))
+}
+
+/// Device of the filesystem holding `path`: the longest mount point that
+/// contains it, the last one mounted if several are stacked
+fn file_dev(root: &SysfsRoot, mounts: &[MountEntry], path: &str) -> Option<String> {
+    let contains = |mount_point: &str| {
+        mount_point == "/"
+            || path == mount_point
+            || path
+                .strip_prefix(mount_point)
+                .is_some_and(|rest| rest.starts_with('/'))
+    };
+    let mount = mounts
+        .iter()
+        .filter(|m| contains(&m.mount_point))
+        .max_by_key(|m| m.mount_point.len())?;
+    mount_dev(root, mount)
+}
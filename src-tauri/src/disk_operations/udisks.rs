@@ -0,0 +1,292 @@
+// src/disk_operations/udisks.rs
+// Client for the UDisks2 daemon on the system bus (org.freedesktop.UDisks2).
+//
+// Block devices live at /org/freedesktop/UDisks2/block_devices/<name>, with
+// every byte of the name other than [A-Za-z0-9] written as _xx in hex, e.g.
+// dm-0 becomes dm_2d0. A block device has a Filesystem interface when it
+// holds one, and points at its Drive, which is what gets powered off.
+//
+// Errors from the daemon are mapped to `UDisksError` by their D-Bus name,
+// so callers can tell a busy device from a missing permission, and from
+// there to a `DiskError` for the frontend. Changes come
+// in as InterfacesAdded / InterfacesRemoved from the object manager and
+// PropertiesChanged on the objects, see `UDisksClient::events`.
+use crate::structs::{DiskError, ErrorCode, UDisksChange, UDisksEvent};
+use std::collections::HashMap;
+use std::fmt;
+use zbus::blocking::{Connection, MessageIterator, Proxy};
+use zbus::message::Type as MessageType;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::MatchRule;
+
+const SERVICE: &str = "org.freedesktop.UDisks2";
+const ROOT: &str = "/org/freedesktop/UDisks2";
+const BLOCK_DEVICES: &str = "/org/freedesktop/UDisks2/block_devices/";
+const FILESYSTEM: &str = "org.freedesktop.UDisks2.Filesystem";
+const BLOCK: &str = "org.freedesktop.UDisks2.Block";
+const DRIVE: &str = "org.freedesktop.UDisks2.Drive";
+
+/// Errors from udisks, by D-Bus error name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UDisksError {
+    /// `NotAuthorized`, `NotAuthorizedCanObtain` or `NotAuthorizedDismissed`
+    NotAuthorized(String),
+    DeviceBusy(String),
+    AlreadyMounted(String),
+    NotMounted(String),
+    /// The daemon has no object for the device, or no such interface on it
+    NoSuchDevice(String),
+    /// The daemon is not running or the bus cannot be reached
+    Unavailable(String),
+    Failed(String),
+}
+
+impl From<zbus::Error> for UDisksError {
+    fn from(err: zbus::Error) -> Self {
+        let zbus::Error::MethodError(name, description, _) = &err else {
+            return Self::Unavailable(format!("Cannot talk to UDisks2: {err}"));
+        };
+        let message = description.clone().unwrap_or_else(|| name.to_string());
+        match name.as_str() {
+            "org.freedesktop.UDisks2.Error.NotAuthorized"
+            | "org.freedesktop.UDisks2.Error.NotAuthorizedCanObtain"
+            | "org.freedesktop.UDisks2.Error.NotAuthorizedDismissed" => {
+                Self::NotAuthorized(message)
+            }
+            "org.freedesktop.UDisks2.Error.DeviceBusy" => Self::DeviceBusy(message),
+            "org.freedesktop.UDisks2.Error.AlreadyMounted" => Self::AlreadyMounted(message),
+            "org.freedesktop.UDisks2.Error.NotMounted" => Self::NotMounted(message),
+            "org.freedesktop.DBus.Error.UnknownObject"
+            | "org.freedesktop.DBus.Error.UnknownInterface"
+            | "org.freedesktop.DBus.Error.UnknownMethod" => Self::NoSuchDevice(message),
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+            | "org.freedesktop.DBus.Error.NameHasNoOwner" => Self::Unavailable(message),
+            _ => Self::Failed(message),
+        }
+    }
+}
+
+impl fmt::Display for UDisksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAuthorized(message)
+            | Self::DeviceBusy(message)
+            | Self::AlreadyMounted(message)
+            | Self::NotMounted(message)
+            | Self::NoSuchDevice(message)
+            | Self::Unavailable(message)
+            | Self::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for UDisksError {}
+
+impl From<UDisksError> for DiskError {
+    fn from(err: UDisksError) -> Self {
+        let code = match &err {
+            UDisksError::NotAuthorized(_) => ErrorCode::PermissionDenied,
+            UDisksError::DeviceBusy(_) => ErrorCode::Busy,
+            UDisksError::AlreadyMounted(_) => ErrorCode::AlreadyMounted,
+            UDisksError::NotMounted(_) => ErrorCode::NotMounted,
+            UDisksError::NoSuchDevice(_) => ErrorCode::NotFound,
+            UDisksError::Unavailable(_) => ErrorCode::Unavailable,
+            UDisksError::Failed(_) => ErrorCode::Failed,
+        };
+        let disk_error = DiskError::new(code, err.to_string());
+        match err {
+            UDisksError::NotAuthorized(_) => disk_error
+                .with_hint("Allow the operation when asked for a password, or run as root"),
+            UDisksError::Unavailable(_) => {
+                disk_error.with_hint("Start udisks2, e.g. systemctl start udisks2")
+            }
+            _ => disk_error,
+        }
+    }
+}
+
+pub type UDisksResult<T> = std::result::Result<T, UDisksError>;
+
+/// Object path part for a device name, e.g. `dm-0` -> `dm_2d0`
+pub fn escape(name: &str) -> String {
+    name.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() {
+                char::from(b).to_string()
+            } else {
+                format!("_{b:02x}")
+            }
+        })
+        .collect()
+}
+
+/// Device name for an escaped object path part, e.g. `dm_2d0` -> `dm-0`
+pub fn unescape(part: &str) -> String {
+    let bytes = part.as_bytes();
+    let mut name = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match hex.map(|h| u8::from_str_radix(h, 16)) {
+            Some(Ok(b)) if bytes[i] == b'_' => {
+                name.push(b);
+                i += 3;
+            }
+            _ => {
+                name.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+/// Object path of a block device, from its name or /dev node
+pub fn block_object_path(device: &str) -> String {
+    let name = device.strip_prefix("/dev/").unwrap_or(device);
+    format!("{BLOCK_DEVICES}{}", escape(name))
+}
+
+/// Options argument (a{sv}) of the udisks methods
+type Options<'a> = HashMap<&'a str, Value<'a>>;
+
+pub struct UDisksClient {
+    connection: Connection,
+}
+
+impl UDisksClient {
+    /// Connect to the daemon on the system bus
+    ///
+    /// # Errors
+    ///
+    /// Fails when the system bus cannot be reached
+    pub fn system() -> UDisksResult<Self> {
+        Connection::system()
+            .map(Self::with_connection)
+            .map_err(UDisksError::from)
+    }
+
+    /// Talk to whichever udisks service owns the name on `connection`
+    pub fn with_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    fn proxy(&self, path: &str, interface: &'static str) -> UDisksResult<Proxy<'_>> {
+        Proxy::new(&self.connection, SERVICE, path.to_string(), interface)
+            .map_err(UDisksError::from)
+    }
+
+    /// Mount the filesystem on `device` where udisks chooses and return
+    /// the mount point. `options` are in mount(8) form.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the udisks error, e.g. `NotAuthorized` or `AlreadyMounted`
+    pub fn mount(
+        &self,
+        device: &str,
+        fs_type: Option<&str>,
+        options: &[String],
+    ) -> UDisksResult<String> {
+        let joined = options.join(",");
+        let mut args = Options::new();
+        if let Some(fs_type) = fs_type {
+            args.insert("fstype", Value::from(fs_type));
+        }
+        if !joined.is_empty() {
+            args.insert("options", Value::from(joined.as_str()));
+        }
+        self.proxy(&block_object_path(device), FILESYSTEM)?
+            .call("Mount", &(args,))
+            .map_err(UDisksError::from)
+    }
+
+    /// Unmount the filesystem on `device` from wherever udisks mounted it
+    ///
+    /// # Errors
+    ///
+    /// Fails with the udisks error, e.g. `DeviceBusy` or `NotMounted`
+    pub fn unmount(&self, device: &str) -> UDisksResult<()> {
+        self.proxy(&block_object_path(device), FILESYSTEM)?
+            .call("Unmount", &(Options::new(),))
+            .map_err(UDisksError::from)
+    }
+
+    /// Power off the drive behind the whole disk `device`
+    ///
+    /// # Errors
+    ///
+    /// Fails when `device` has no drive or the drive cannot be powered off
+    pub fn power_off(&self, device: &str) -> UDisksResult<()> {
+        let drive: OwnedObjectPath = self
+            .proxy(&block_object_path(device), BLOCK)?
+            .get_property("Drive")
+            .map_err(UDisksError::from)?;
+        if drive.as_str() == "/" {
+            return Err(UDisksError::NoSuchDevice(format!(
+                "{device} has no drive to power off"
+            )));
+        }
+        self.proxy(drive.as_str(), DRIVE)?
+            .call("PowerOff", &(Options::new(),))
+            .map_err(UDisksError::from)
+    }
+
+    /// Changes to udisks objects as they happen. Subscribes before
+    /// returning, so nothing that happens afterwards is missed; the
+    /// iterator ends when the connection closes.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the signal subscription is refused
+    pub fn events(&self) -> UDisksResult<impl Iterator<Item = UDisksEvent>> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(SERVICE)
+            .and_then(|b| b.path_namespace(ROOT))
+            .map_err(UDisksError::from)?
+            .build();
+        let messages = MessageIterator::for_match_rule(rule, &self.connection, None)
+            .map_err(UDisksError::from)?;
+        Ok(messages.filter_map(|message| parse_event(&message.ok()?)))
+    }
+}
+
+fn event(change: UDisksChange, object: &str, interfaces: Vec<String>) -> UDisksEvent {
+    let device = object.strip_prefix(BLOCK_DEVICES).map(unescape);
+    UDisksEvent {
+        change,
+        object: object.to_string(),
+        device,
+        interfaces,
+    }
+}
+
+fn parse_event(message: &zbus::Message) -> Option<UDisksEvent> {
+    let header = message.header();
+    let body = message.body();
+    match header.member()?.as_str() {
+        "InterfacesAdded" => {
+            let (object, interfaces): (
+                OwnedObjectPath,
+                HashMap<String, HashMap<String, OwnedValue>>,
+            ) = body.deserialize().ok()?;
+            let mut interfaces: Vec<String> = interfaces.into_keys().collect();
+            interfaces.sort();
+            Some(event(UDisksChange::Added, object.as_str(), interfaces))
+        }
+        "InterfacesRemoved" => {
+            let (object, interfaces): (OwnedObjectPath, Vec<String>) = body.deserialize().ok()?;
+            Some(event(UDisksChange::Removed, object.as_str(), interfaces))
+        }
+        "PropertiesChanged" => {
+            let (interface, _, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                body.deserialize().ok()?;
+            let object = header.path()?.to_string();
+            Some(event(UDisksChange::Changed, &object, vec![interface]))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,181 @@
+// src/disk_operations/unmount.rs
+// Unmounting one mount point or every filesystem of a disk, in one of the
+// `UnmountMode`s:
+//   Normal           udisks, so that it cleans up the directory it created
+//                    under /media; plain umount2(2) for bind mounts and
+//                    subvolumes, since udisks unmounts a device, not a path
+//   Lazy             umount2(MNT_DETACH)
+//   Force            umount2(MNT_FORCE)
+//   RemountReadOnly  mount(MS_REMOUNT | MS_RDONLY)
+// Each mount gets a `StepReport`.
+use super::busy::is_inside;
+use super::mountinfo::{read_mountinfo, MountEntry};
+use super::offline::{mount_flags, mounts_on_disk, SystemActions};
+use super::sysfs::{device_name_for_dev, SysfsRoot};
+use super::udisks::UDisksClient;
+use crate::structs::{DiskError, ErrorCode, OfflineStep, StepReport, StepStatus, UnmountMode};
+use anyhow::{bail, Context, Result};
+use std::ffi::CString;
+
+/// Side effects of unmounting, replaced in tests
+pub trait UnmountActions {
+    /// Unmount a device through udisks, e.g. /dev/sdb1
+    ///
+    /// # Errors
+    ///
+    /// Fails when udisks is unreachable or refuses
+    fn udisks_unmount(&mut self, device: &str) -> Result<()>;
+    /// umount2(2) with 0, `MNT_DETACH` or `MNT_FORCE`
+    ///
+    /// # Errors
+    ///
+    /// Fails with the errno of umount2(2), e.g. `EBUSY`
+    fn umount(&mut self, mount_point: &str, flags: libc::c_int) -> Result<()>;
+    /// Switch the filesystem to read-only where it is mounted
+    ///
+    /// # Errors
+    ///
+    /// Fails with the errno of mount(2)
+    fn remount_read_only(&mut self, mount: &MountEntry) -> Result<()>;
+}
+
+impl UnmountActions for SystemActions {
+    fn udisks_unmount(&mut self, device: &str) -> Result<()> {
+        let client = UDisksClient::system().map_err(DiskError::from)?;
+        Ok(client.unmount(device).map_err(DiskError::from)?)
+    }
+
+    fn umount(&mut self, mount_point: &str, flags: libc::c_int) -> Result<()> {
+        let path = CString::new(mount_point)?;
+        if unsafe { libc::umount2(path.as_ptr(), flags) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to unmount {mount_point}"));
+        }
+        Ok(())
+    }
+
+    fn remount_read_only(&mut self, mount: &MountEntry) -> Result<()> {
+        let source = CString::new(mount.source.as_str())?;
+        let target = CString::new(mount.mount_point.as_str())?;
+        let (flags, data) = mount_flags(mount);
+        let data = CString::new(data)?;
+        let res = unsafe {
+            libc::mount(
+                source.as_ptr(),
+                target.as_ptr(),
+                std::ptr::null(),
+                flags | libc::MS_REMOUNT | libc::MS_RDONLY,
+                data.as_ptr().cast(),
+            )
+        };
+        if res != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to remount {} read-only", mount.mount_point));
+        }
+        Ok(())
+    }
+}
+
+fn unmount_one(
+    root: &SysfsRoot,
+    mounts: &[MountEntry],
+    mount: &MountEntry,
+    mode: UnmountMode,
+    actions: &mut impl UnmountActions,
+) -> Result<()> {
+    match mode {
+        UnmountMode::Normal => {
+            let shared = mounts
+                .iter()
+                .any(|m| m.dev() == mount.dev() && m.mount_point != mount.mount_point);
+            if shared {
+                return actions.umount(&mount.mount_point, 0);
+            }
+            let device = device_name_for_dev(root, mount.major, mount.minor)
+                .map_or_else(|| mount.source.clone(), |name| format!("/dev/{name}"));
+            actions.udisks_unmount(&device)
+        }
+        UnmountMode::Lazy => actions.umount(&mount.mount_point, libc::MNT_DETACH),
+        UnmountMode::Force => actions.umount(&mount.mount_point, libc::MNT_FORCE),
+        UnmountMode::RemountReadOnly => actions.remount_read_only(mount),
+    }
+}
+
+fn report(mount: &MountEntry, mode: UnmountMode, result: Result<()>) -> StepReport {
+    let step = if mode == UnmountMode::RemountReadOnly {
+        OfflineStep::RemountReadOnly
+    } else {
+        OfflineStep::Unmount
+    };
+    let (status, message) = match result {
+        Ok(()) => (StepStatus::Done, None),
+        Err(e) => (StepStatus::Failed, Some(format!("{e:#}"))),
+    };
+    StepReport {
+        step,
+        target: mount.mount_point.clone(),
+        status,
+        message,
+    }
+}
+
+/// Unmount the filesystem at `mount_point`
+///
+/// # Errors
+///
+/// Fails with `NotMounted` when nothing is mounted at `mount_point`; a
+/// failed unmount is in the report
+pub fn unmount_mount_point(
+    root: &SysfsRoot,
+    mount_point: &str,
+    mode: UnmountMode,
+    actions: &mut impl UnmountActions,
+) -> Result<Vec<StepReport>> {
+    let mounts = read_mountinfo(&root.mountinfo_path())?;
+    // With stacked mounts on the same path the last entry is the visible one
+    let Some(mount) = mounts.iter().rev().find(|m| m.mount_point == mount_point) else {
+        bail!(DiskError::new(
+            ErrorCode::NotMounted,
+            format!("{mount_point} is not a mount point")
+        )
+        .on_partition(mount_point));
+    };
+    let result = unmount_one(root, &mounts, mount, mode, actions);
+    Ok(vec![report(mount, mode, result)])
+}
+
+/// Unmount every filesystem on `disk` and its partitions, deepest first.
+/// A mount whose nested mount stayed behind is skipped rather than tried.
+///
+/// # Errors
+///
+/// Fails when `disk` does not exist or the mount table is unreadable;
+/// failed unmounts are in the reports
+pub fn unmount_disk(
+    root: &SysfsRoot,
+    disk: &str,
+    mode: UnmountMode,
+    actions: &mut impl UnmountActions,
+) -> Result<Vec<StepReport>> {
+    if !root.block_device(disk).exists() {
+        bail!(DiskError::new(ErrorCode::NotFound, format!("Disk {disk} not found")).on_disk(disk));
+    }
+    let mounts = read_mountinfo(&root.mountinfo_path())?;
+    let mut results: Vec<StepReport> = Vec::new();
+    for mount in mounts_on_disk(root, disk, &mounts) {
+        let still_mounted = results.iter().find(|r| {
+            r.status == StepStatus::Failed
+                && r.target != mount.mount_point
+                && is_inside(&r.target, &mount.mount_point)
+        });
+        let result = match still_mounted {
+            Some(nested) if mode != UnmountMode::RemountReadOnly => Err(anyhow::anyhow!(
+                "Skipped, {} is still mounted",
+                nested.target
+            )),
+            _ => unmount_one(root, &mounts, &mount, mode, actions),
+        };
+        results.push(report(&mount, mode, result));
+    }
+    Ok(results)
+}
@@ -0,0 +1,96 @@
+// src/inventory.rs
+// The disk inventory shared by the commands that only read it.
+//
+// Enumerating disks is slow on Windows (PowerShell) and not free on Linux,
+// so the list is kept for a configurable time. Anything that knows better
+// drops it early: the commands that change disks, once they finish, and
+// the hotplug listener, on every change it sees. Commands that act on a
+// disk still enumerate afresh for their safety checks.
+use crate::structs::DiskInfo;
+use anyhow::Result;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Linux drops the inventory on every hotplug event, so it can be kept for
+/// long; elsewhere only the expiry notices a disk being plugged in
+#[cfg(target_os = "linux")]
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+#[cfg(not(target_os = "linux"))]
+pub const DEFAULT_TTL: Duration = Duration::from_secs(10);
+
+pub struct InventoryCache {
+    ttl: Mutex<Duration>,
+    disks: Mutex<Option<(Vec<DiskInfo>, Instant)>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // The cached values stay consistent even if a holder panicked
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl InventoryCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl: Mutex::new(ttl),
+            disks: Mutex::new(None),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        *lock(&self.ttl)
+    }
+
+    /// How long an inventory stays valid; zero disables caching
+    pub fn set_ttl(&self, ttl: Duration) {
+        *lock(&self.ttl) = ttl;
+    }
+
+    /// The cached inventory, or a new one from `enumerate` once it expired
+    /// or was invalidated. Failures are not cached. Concurrent callers wait
+    /// for a single enumeration.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `enumerate` does
+    pub fn disks(
+        &self,
+        enumerate: impl FnOnce() -> Result<Vec<DiskInfo>>,
+    ) -> Result<Vec<DiskInfo>> {
+        let ttl = self.ttl();
+        let mut cached = lock(&self.disks);
+        if let Some((disks, taken)) = &*cached {
+            if taken.elapsed() < ttl {
+                return Ok(disks.clone());
+            }
+        }
+        let disks = enumerate()?;
+        *cached = Some((disks.clone(), Instant::now()));
+        Ok(disks)
+    }
+
+    pub fn invalidate(&self) {
+        *lock(&self.disks) = None;
+    }
+
+    /// Invalidate when the returned guard goes out of scope, however the
+    /// command holding it returns
+    pub fn invalidate_on_drop(&self) -> Invalidation<'_> {
+        Invalidation { cache: self }
+    }
+}
+
+impl Default for InventoryCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+pub struct Invalidation<'a> {
+    cache: &'a InventoryCache,
+}
+
+impl Drop for Invalidation<'_> {
+    fn drop(&mut self) {
+        self.cache.invalidate();
+    }
+}
@@ -2,75 +2,134 @@ pub mod disk_operations;
 pub mod structs;
 pub mod utils;
 pub mod logger;
+pub mod inventory;
+pub mod commands;
 
-use crate::disk_operations::*;
-use crate::structs::{DiskInfo, SystemInfo};
-use std::sync::Mutex;
-use std::time::{Duration, Instant};
+#[cfg(test)]
+mod tests;
 
-struct CacheState {
-    system_info: Mutex<Option<(SystemInfo, Instant)>>,
+use crate::commands::AppState;
+use crate::disk_operations::backend;
+#[cfg(target_os = "windows")]
+use crate::disk_operations::get_available_drive_letters;
+#[cfg(target_os = "linux")]
+use crate::disk_operations::watch_disks;
+use crate::structs::{
+    BusyReport, DiskError, DiskInfo, MountOptions, MountedPartition, OfflineReport, RescanReport,
+    StepReport, SystemInfo, UnmountMode,
+};
+use crate::inventory::DEFAULT_TTL;
+use std::time::Duration;
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+fn get_system_info_command(state: tauri::State<AppState>) -> Result<SystemInfo, DiskError> {
+    state.system_info()
 }
 
+/// The disk inventory, from the cache unless `refresh` is set
 #[tauri::command]
-fn get_system_info_command(state: tauri::State<CacheState>) -> Result<SystemInfo, String> {
-    let mut cache = state.system_info.lock().map_err(|e| e.to_string())?;
+#[allow(clippy::needless_pass_by_value)]
+fn enumerate_disks_command(
+    state: tauri::State<AppState>,
+    refresh: Option<bool>,
+) -> Result<Vec<DiskInfo>, DiskError> {
+    state.enumerate_disks(refresh.unwrap_or(false))
+}
 
-    if let Some((info, timestamp)) = &*cache {
-        if timestamp.elapsed() < Duration::from_secs(30 * 60) {
-            return Ok(info.clone());
-        }
-    }
+/// How long the disk inventory is cached; None restores the default
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+fn set_cache_ttl_command(state: tauri::State<AppState>, seconds: Option<u64>) {
+    let ttl = seconds.map_or(DEFAULT_TTL, Duration::from_secs);
+    state.inventory.set_ttl(ttl);
+}
 
-    let info = get_system_info().map_err(|e| e.to_string())?;
-    *cache = Some((info.clone(), Instant::now()));
-    Ok(info)
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+fn set_disk_online_command(
+    state: tauri::State<AppState>,
+    disk_id: String,
+) -> Result<(), DiskError> {
+    state.set_disk_online(&disk_id)
 }
 
 #[tauri::command]
-fn enumerate_disks_command() -> Result<Vec<DiskInfo>, String> {
-    enumerate_disks().map_err(|e| e.to_string())
+#[allow(clippy::needless_pass_by_value)]
+fn set_disk_offline_command(
+    state: tauri::State<AppState>,
+    disk_id: String,
+    override_protection: Option<bool>,
+) -> Result<OfflineReport, DiskError> {
+    state.set_disk_offline(&disk_id, override_protection.unwrap_or(false))
 }
 
 #[tauri::command]
-fn set_disk_online_command(disk_id: String) -> Result<(), String> {
-    let res = set_disk_online(disk_id.clone()).map_err(|e| e.to_string());
-    if res.is_ok() {
-        logger::log_activity(&format!("Set Disk {} Online", disk_id));
-    }
-    res
+#[allow(clippy::needless_pass_by_value)]
+fn eject_disk_command(
+    state: tauri::State<AppState>,
+    disk_id: String,
+    override_protection: Option<bool>,
+) -> Result<OfflineReport, DiskError> {
+    state.eject_disk(&disk_id, override_protection.unwrap_or(false))
 }
 
 #[tauri::command]
-fn set_disk_offline_command(disk_id: String) -> Result<(), String> {
-    let res = set_disk_offline(disk_id.clone()).map_err(|e| e.to_string());
-    if res.is_ok() {
-        logger::log_activity(&format!("Set Disk {} Offline", disk_id));
-    }
-    res
+#[allow(clippy::needless_pass_by_value)]
+fn rescan_storage_command(state: tauri::State<AppState>) -> Result<RescanReport, DiskError> {
+    state.rescan_storage()
 }
 
 #[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
 fn mount_partition_command(
+    state: tauri::State<AppState>,
     disk_id: String,
     partition_number: u32,
     letter: Option<char>,
-) -> Result<Option<char>, String> {
-    let res = mount_partition(disk_id.clone(), partition_number, letter).map_err(|e| e.to_string());
-    if let Ok(assigned) = &res {
-        let l_str = assigned.map(|c| format!(" to {}:\\", c)).unwrap_or_default();
-        logger::log_activity(&format!("Mounted Partition {} on Disk {}{}", partition_number, disk_id, l_str));
-    }
-    res
+    options: Option<MountOptions>,
+) -> Result<MountedPartition, DiskError> {
+    state.mount_partition(&disk_id, partition_number, letter, &options.unwrap_or_default())
 }
 
 #[tauri::command]
-fn unmount_partition_command(volume_or_letter: String) -> Result<(), String> {
-    let res = unmount_partition(volume_or_letter.clone()).map_err(|e| e.to_string());
-    if res.is_ok() {
-        logger::log_activity(&format!("Unmounted/Ejected Partition {}", volume_or_letter));
-    }
-    res
+#[allow(clippy::needless_pass_by_value)]
+fn unmount_partition_command(
+    state: tauri::State<AppState>,
+    volume_or_letter: String,
+    override_protection: Option<bool>,
+    mode: Option<UnmountMode>,
+) -> Result<Vec<StepReport>, DiskError> {
+    state.unmount_partition(
+        &volume_or_letter,
+        override_protection.unwrap_or(false),
+        mode.unwrap_or_default(),
+    )
+}
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+fn unmount_disk_command(
+    state: tauri::State<AppState>,
+    disk_id: String,
+    override_protection: Option<bool>,
+    mode: Option<UnmountMode>,
+) -> Result<Vec<StepReport>, DiskError> {
+    state.unmount_disk(
+        &disk_id,
+        override_protection.unwrap_or(false),
+        mode.unwrap_or_default(),
+    )
+}
+
+/// List the processes and swap areas keeping a mount point or device busy
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+fn diagnose_busy_command(
+    state: tauri::State<AppState>,
+    target: String,
+) -> Result<BusyReport, DiskError> {
+    state.diagnose_busy(&target)
 }
 
 #[tauri::command]
@@ -80,7 +139,7 @@ fn get_logs_command() -> Vec<String> {
 
 #[tauri::command]
 fn clear_logs_command() {
-    logger::clear_logs()
+    logger::clear_logs();
 }
 
 #[tauri::command]
@@ -96,21 +155,67 @@ fn get_available_drive_letters_command() -> Vec<String> {
 }
 
 #[tauri::command]
-fn open_file_explorer_command(path: String) -> Result<(), String> {
+#[allow(clippy::needless_pass_by_value)]
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
+fn open_file_explorer_command(path: String) -> Result<(), DiskError> {
     #[cfg(target_os = "windows")]
     {
-        std::process::Command::new("explorer")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+        std::process::Command::new("explorer").arg(&path).spawn()?;
         Ok(())
     }
     #[cfg(not(target_os = "windows"))]
     {
-        Err("Not supported on this OS".to_string())
+        Err(DiskError::new(
+            crate::structs::ErrorCode::Unsupported,
+            "Not supported on this OS",
+        ))
     }
 }
 
+/// Forward `UDisks2` changes (disks plugged or removed, filesystems mounted
+/// elsewhere) to the frontend as "udisks-changed", dropping the cached
+/// inventory first. Without a reachable daemon there is nothing to forward
+/// and the periodic refresh remains.
+#[cfg(target_os = "linux")]
+fn watch_udisks(app: tauri::AppHandle) {
+    use crate::disk_operations::udisks::UDisksClient;
+    use tauri::{Emitter, Manager};
+
+    std::thread::spawn(move || {
+        let events = match UDisksClient::system().and_then(|client| client.events()) {
+            Ok(events) => events,
+            Err(e) => {
+                // A diagnostic, not an operation: keep it out of the activity log
+                eprintln!("Not watching UDisks2: {e}");
+                return;
+            }
+        };
+        for event in events {
+            app.state::<AppState>().inventory.invalidate();
+            let _ = app.emit("udisks-changed", &event);
+        }
+    });
+}
+
+/// Emit disk-added, disk-removed, disk-changed and partition-mounted as the
+/// kernel reports them, dropping the cached inventory on every change.
+#[cfg(target_os = "linux")]
+fn watch_hotplug(app: tauri::AppHandle) {
+    use tauri::{Emitter, Manager};
+
+    std::thread::spawn(move || {
+        watch_disks(|event| {
+            app.state::<AppState>().inventory.invalidate();
+            let _ = app.emit(event.name(), &event);
+        });
+    });
+}
+
+/// Start the app
+///
+/// # Panics
+///
+/// Panics when Tauri cannot start the event loop or create the window
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Check elevation
@@ -123,15 +228,28 @@ pub fn run() {
     }
 
     tauri::Builder::default()
-        .manage(CacheState {
-            system_info: Mutex::new(None),
+        .manage(AppState::new(backend::platform()))
+        .setup(|app| {
+            #[cfg(target_os = "linux")]
+            {
+                watch_udisks(app.handle().clone());
+                watch_hotplug(app.handle().clone());
+            }
+            #[cfg(not(target_os = "linux"))]
+            let _ = app;
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             enumerate_disks_command,
+            set_cache_ttl_command,
             set_disk_online_command,
             set_disk_offline_command,
+            eject_disk_command,
+            rescan_storage_command,
             mount_partition_command,
             unmount_partition_command,
+            unmount_disk_command,
+            diagnose_busy_command,
             get_available_drive_letters_command,
             get_system_info_command,
             open_file_explorer_command,
@@ -6,14 +6,21 @@ use chrono::Local;
 
 fn get_log_path() -> PathBuf {
     let mut path = env::temp_dir();
-    path.push("diskofflaner_history.log");
+    // Tests run the commands, which log; keep that out of the real history
+    if cfg!(test) {
+        path.push("diskofflaner_test_history.log");
+    } else {
+        path.push("diskofflaner_history.log");
+    }
     path
 }
 
+/// # Panics
+/// Panics if the log file in the temp directory cannot be opened.
 pub fn log_activity(message: &str) {
     let path = get_log_path();
     let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let log_entry = format!("[{}] {}\n", timestamp, message);
+    let log_entry = format!("[{timestamp}] {message}\n");
 
     let mut file = OpenOptions::new()
         .create(true)
@@ -33,7 +40,7 @@ pub fn get_logs() -> Vec<String> {
     read_to_string(path)
         .unwrap_or_default()
         .lines()
-        .map(|s| s.to_string())
+        .map(ToString::to_string)
         .collect()
 }
 
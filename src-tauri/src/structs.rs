@@ -32,12 +32,31 @@ pub struct DiskInfo {
     pub model: String,
     pub size_bytes: u64,
     pub is_online: bool,
+    /// True whenever `protection` is not empty
     pub is_system_disk: bool,
+    /// Why offline, unmount and eject are refused without an override
+    pub protection: Vec<ProtectionReason>,
     pub partitions: Vec<PartitionInfo>,
     pub disk_type: DiskType,
     pub serial_number: Option<String>,
     pub health_percentage: Option<u8>,
     pub usage_percentage: Option<f64>,
+    /// What the backend can do with this disk. Commands refuse the rest.
+    pub capabilities: DiskCapabilities,
+}
+
+/// Operations and features a disk supports, as far as the platform can tell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct DiskCapabilities {
+    pub can_offline: bool,
+    pub can_online: bool,
+    pub can_eject: bool,
+    pub can_power_off: bool,
+    pub can_spin_down: bool,
+    pub supports_smart: bool,
+    pub supports_trim: bool,
+    pub is_read_only: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,8 +64,525 @@ pub struct PartitionInfo {
     pub partition_number: u32,
     pub size_bytes: u64,
     pub used_bytes: Option<u64>,
+    /// Windows drive letter ("C"), empty when unassigned and on Linux
     pub drive_letter: String,
     pub partition_id: String,
+    /// Every place the partition is mounted (Linux). Bind mounts and btrfs
+    /// subvolumes show up as separate entries. Empty on Windows, which uses
+    /// `drive_letter` instead.
+    pub mount_points: Vec<MountPoint>,
+    /// Filesystem statistics of a mounted partition; None means unknown
+    /// (not mounted, or the filesystem could not be queried)
+    pub usage: Option<FilesystemUsage>,
+    /// Filesystem or content signature: "ext4", "vfat", "swap", `crypto_LUKS`, ...
+    pub fs_type: Option<String>,
+    pub label: Option<String>,
+    /// Filesystem UUID
+    pub uuid: Option<String>,
+    /// Partition table entry UUID (GPT PARTUUID, or the MBR disk signature form)
+    pub part_uuid: Option<String>,
+    /// GPT type GUID or MBR type byte ("0x83")
+    pub part_type: Option<String>,
+    /// Partition table attributes, e.g. "boot", "hidden", "no-automount"
+    pub part_flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MountPoint {
+    pub path: String,
+    pub fs_type: String,
+    /// Per-mount options, e.g. `rw`, `nosuid`, `relatime`
+    pub options: Vec<String>,
+    pub read_only: bool,
+    /// Directory of the filesystem mounted here; "/" for a regular mount
+    /// (`\` on Windows), the source directory of a bind mount or the btrfs
+    /// subvolume path
+    pub root: String,
+}
+
+/// How to mount a partition. The defaults let udisks mount it below /media,
+/// as the desktop would. On Linux every mount is nosuid and nodev, whether
+/// or not `no_suid` and `no_dev` are set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct MountOptions {
+    /// Directory to mount on (Linux) or folder to mount in (Windows)
+    pub target: Option<String>,
+    pub read_only: bool,
+    pub no_exec: bool,
+    pub no_suid: bool,
+    pub no_dev: bool,
+    /// Owner of the files on FAT, exFAT and NTFS, which have none of their own
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Filesystem type, detected when not given
+    pub fs_type: Option<String>,
+}
+
+/// Where a partition was mounted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountedPartition {
+    /// Windows drive letter, when one was assigned
+    pub letter: Option<char>,
+    /// Mount point (Linux), or drive root or folder (Windows)
+    pub mount_point: Option<String>,
+}
+
+/// A partition mounted by anyone, as noticed by the hotplug listener
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionMounted {
+    pub disk: DiskInfo,
+    pub partition_number: u32,
+    pub mount_point: String,
+}
+
+/// What happened to a udisks object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UDisksChange {
+    Added,
+    Removed,
+    /// Properties changed, e.g. a filesystem's mount points
+    Changed,
+}
+
+/// A change reported by udisks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UDisksEvent {
+    pub change: UDisksChange,
+    /// Object path, e.g. `/org/freedesktop/UDisks2/drives/Kingston_DataTraveler`
+    pub object: String,
+    /// Block device name for block device objects, e.g. "sdb1"
+    pub device: Option<String>,
+    /// Interfaces added, removed or whose properties changed
+    pub interfaces: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilesystemUsage {
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    /// Free space including blocks reserved for root
+    pub free_bytes: u64,
+    /// Free space available to unprivileged users
+    pub available_bytes: u64,
+    pub reserved_bytes: u64,
+    pub total_inodes: u64,
+    pub used_inodes: u64,
+    pub free_inodes: u64,
+}
+
+/// What makes a disk part of the running system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectionReason {
+    /// Holds the Windows system or boot volume
+    SystemDisk,
+    /// Holds the root filesystem
+    RootFilesystem,
+    /// Holds /boot or the EFI system partition
+    Boot,
+    /// Hosts active swap
+    Swap,
+    /// Backs the root, boot or swap device through LVM, LUKS, mdraid or
+    /// another device-mapper stack
+    RootStack,
+}
+
+impl std::fmt::Display for ProtectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtectionReason::SystemDisk => write!(f, "holds the system volume"),
+            ProtectionReason::RootFilesystem => write!(f, "holds the root filesystem"),
+            ProtectionReason::Boot => write!(f, "holds the boot partition"),
+            ProtectionReason::Swap => write!(f, "hosts active swap"),
+            ProtectionReason::RootStack => write!(f, "backs a system volume"),
+        }
+    }
+}
+
+/// Operations refused on protected disks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectedOperation {
+    Offline,
+    Unmount,
+    Eject,
+}
+
+impl std::fmt::Display for ProtectedOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtectedOperation::Offline => write!(f, "offline"),
+            ProtectedOperation::Unmount => write!(f, "unmount"),
+            ProtectedOperation::Eject => write!(f, "eject"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProtectionRefusal {
+    pub disk_id: String,
+    pub operation: ProtectedOperation,
+    pub reasons: Vec<ProtectionReason>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ProtectionRefusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ProtectionRefusal {}
+
+/// How to take a filesystem off its mount point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmountMode {
+    /// A plain unmount, refused while the filesystem is busy
+    #[default]
+    Normal,
+    /// Detach it now and let the kernel finish once it is no longer busy
+    Lazy,
+    /// Abort outstanding requests first, for unreachable network filesystems
+    Force,
+    /// Leave it mounted, read-only
+    RemountReadOnly,
+}
+
+/// One stage of taking a disk offline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfflineStep {
+    Unmount,
+    Sync,
+    FlushBuffers,
+    WriteState,
+    /// Eject: power the device off, or delete it when that is not possible
+    PowerOff,
+    VerifyState,
+    /// Unmount in `UnmountMode::RemountReadOnly`
+    RemountReadOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Done,
+    Failed,
+    /// Done, then undone after a later step failed
+    RolledBack,
+    /// Done, and undoing it failed: manual attention needed
+    RollbackFailed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepReport {
+    pub step: OfflineStep,
+    /// Mount point, device node or sysfs path the step acted on
+    pub target: String,
+    pub status: StepStatus,
+    pub message: Option<String>,
+}
+
+/// How a disk is taken offline, chosen per device type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfflineMethod {
+    /// SCSI, SATA and USB disks: write "offline" to device/state
+    ScsiState,
+    /// nvme: remove the controller's PCI function, brought back by a PCI rescan
+    PciRemove,
+    /// SCSI devices without a state attribute: delete, brought back by a
+    /// host scan
+    ScsiDelete,
+    /// virtio, MMC and other drivers: unbind the device, bound again later
+    DriverUnbind,
+}
+
+/// A process holding files open on a filesystem that is being unmounted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockingProcess {
+    pub pid: u32,
+    pub command: String,
+    /// Owner of the process, or its uid when it has no user name
+    pub user: String,
+    /// Open or mapped files, working directory or root inside the filesystem
+    pub paths: Vec<String>,
+}
+
+/// Why a filesystem cannot be unmounted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BusyReport {
+    /// The mount and any filesystems mounted below it
+    pub mount_points: Vec<String>,
+    pub processes: Vec<BlockingProcess>,
+    /// Swap files or partitions in use there
+    pub swap: Vec<String>,
+}
+
+impl BusyReport {
+    /// True when nothing was found holding the filesystem
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty() && self.swap.is_empty()
+    }
+}
+
+/// A value written to a sysfs attribute, e.g. "1" to /sys/bus/pci/rescan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SysfsWrite {
+    pub path: String,
+    pub value: String,
+}
+
+/// What happened while taking a disk offline or ejecting it, in order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineReport {
+    pub disk_id: String,
+    pub method: Option<OfflineMethod>,
+    pub steps: Vec<StepReport>,
+    /// True when the disk ended up offline
+    pub completed: bool,
+    /// The writes `set_disk_online` will replay to bring the disk back
+    pub bring_back: Vec<SysfsWrite>,
+    /// Processes that kept a filesystem from being unmounted
+    pub blockers: Vec<BlockingProcess>,
+}
+
+impl OfflineReport {
+    /// Message of the step that stopped the sequence
+    pub fn failure(&self) -> Option<&str> {
+        self.steps
+            .iter()
+            .find(|s| s.status == StepStatus::Failed)
+            .and_then(|s| s.message.as_deref())
+    }
+}
+
+/// What a storage rescan asked for and which disks came back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RescanReport {
+    /// Scan requests written, e.g. "- - -" to a SCSI host's scan attribute
+    pub scanned: Vec<SysfsWrite>,
+    /// Scan requests that failed
+    pub errors: Vec<String>,
+    /// Disks present after the rescan that were missing before
+    pub reappeared: Vec<DiskInfo>,
+}
+
+/// What went wrong, as a stable code the frontend can branch on. Codes are
+/// never renamed; new ones may be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The disk is protected and no override was given
+    Refused,
+    /// The disk or platform does not support the operation
+    Unsupported,
+    /// A multi-step operation stopped part way and was rolled back
+    Incomplete,
+    /// The device or filesystem is in use
+    Busy,
+    /// Some of a disk's filesystems could not be unmounted
+    PartlyUnmounted,
+    PermissionDenied,
+    /// No such disk, partition, mount point or device
+    NotFound,
+    AlreadyMounted,
+    NotMounted,
+    /// The request itself is wrong, e.g. a bad mount target
+    InvalidArgument,
+    /// A system service the operation relies on is not running
+    Unavailable,
+    /// A helper program or the device did not answer in time
+    TimedOut,
+    /// Stopped on request before it finished
+    Cancelled,
+    Failed,
+}
+
+/// What an error carries beyond its message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ErrorDetails {
+    Refusal(ProtectionRefusal),
+    /// Every step of an offline or eject that stopped part way
+    Offline {
+        report: OfflineReport,
+    },
+    /// What holds a filesystem that would not unmount
+    Busy {
+        report: BusyReport,
+    },
+    /// The outcome for each filesystem of a disk
+    Unmount {
+        results: Vec<StepReport>,
+    },
+}
+
+/// Error returned by every command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Disk the error is about, e.g. "sdb" or "1"
+    pub disk: Option<String>,
+    /// Partition the error is about: number, mount point or drive letter
+    pub partition: Option<String>,
+    /// errno on Linux, Win32 error code on Windows
+    pub os_error: Option<i32>,
+    /// What the user can do about it
+    pub hint: Option<String>,
+    /// Boxed to keep the error small; most errors have none
+    pub details: Option<Box<ErrorDetails>>,
+}
+
+impl DiskError {
+    /// An error with the usual hint for `code`
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        DiskError {
+            code,
+            message: message.into(),
+            disk: None,
+            partition: None,
+            os_error: None,
+            hint: default_hint(code).map(str::to_string),
+            details: None,
+        }
+    }
+
+    /// Attribute the error to `disk`, unless it already names one
+    #[must_use]
+    pub fn on_disk(mut self, disk: impl Into<String>) -> Self {
+        self.disk.get_or_insert_with(|| disk.into());
+        self
+    }
+
+    /// Attribute the error to `partition`, unless it already names one
+    #[must_use]
+    pub fn on_partition(mut self, partition: impl Into<String>) -> Self {
+        self.partition.get_or_insert_with(|| partition.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_details(mut self, details: ErrorDetails) -> Self {
+        self.details = Some(Box::new(details));
+        self
+    }
+
+    /// An error for an OS error code, classified by what it means
+    pub fn from_os_error(os_error: i32, message: impl Into<String>) -> Self {
+        let mut err = DiskError::new(code_for_os_error(os_error), message);
+        err.os_error = Some(os_error);
+        err
+    }
+}
+
+#[cfg(unix)]
+fn code_for_os_error(os_error: i32) -> ErrorCode {
+    match os_error {
+        libc::EACCES | libc::EPERM | libc::EROFS => ErrorCode::PermissionDenied,
+        libc::EBUSY | libc::ETXTBSY => ErrorCode::Busy,
+        libc::ENOENT | libc::ENODEV | libc::ENXIO => ErrorCode::NotFound,
+        libc::EINVAL | libc::ENOTDIR | libc::ENOTBLK => ErrorCode::InvalidArgument,
+        libc::EOPNOTSUPP | libc::ENOSYS => ErrorCode::Unsupported,
+        libc::ETIMEDOUT => ErrorCode::TimedOut,
+        _ => ErrorCode::Failed,
+    }
+}
+
+#[cfg(windows)]
+fn code_for_os_error(os_error: i32) -> ErrorCode {
+    use winapi::shared::winerror::{
+        ERROR_ACCESS_DENIED, ERROR_BUSY, ERROR_FILE_NOT_FOUND, ERROR_INVALID_DRIVE,
+        ERROR_INVALID_FUNCTION, ERROR_INVALID_PARAMETER, ERROR_LOCK_VIOLATION, ERROR_NOT_READY,
+        ERROR_NOT_SUPPORTED, ERROR_PATH_NOT_FOUND, ERROR_SHARING_VIOLATION, ERROR_WRITE_PROTECT,
+    };
+    match u32::try_from(os_error).unwrap_or_default() {
+        ERROR_ACCESS_DENIED | ERROR_WRITE_PROTECT => ErrorCode::PermissionDenied,
+        ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION | ERROR_BUSY => ErrorCode::Busy,
+        ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND | ERROR_INVALID_DRIVE | ERROR_NOT_READY => {
+            ErrorCode::NotFound
+        }
+        ERROR_INVALID_PARAMETER => ErrorCode::InvalidArgument,
+        ERROR_NOT_SUPPORTED | ERROR_INVALID_FUNCTION => ErrorCode::Unsupported,
+        _ => ErrorCode::Failed,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn code_for_os_error(_os_error: i32) -> ErrorCode {
+    ErrorCode::Failed
+}
+
+fn default_hint(code: ErrorCode) -> Option<&'static str> {
+    match code {
+        ErrorCode::Refused => Some("Confirm the override to go ahead on a system disk"),
+        ErrorCode::Busy => Some("Close the programs using it and try again"),
+        ErrorCode::PermissionDenied if cfg!(windows) => {
+            Some("Restart DiskOfflaner as administrator")
+        }
+        ErrorCode::PermissionDenied => Some("Run DiskOfflaner as root"),
+        ErrorCode::NotFound => Some("Refresh the disk list, the disk may have been removed"),
+        ErrorCode::AlreadyMounted => Some("Unmount it first"),
+        ErrorCode::Incomplete => Some("The steps already done were undone"),
+        ErrorCode::TimedOut => Some("Check that the device still responds and try again"),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for DiskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DiskError {}
+
+impl From<ProtectionRefusal> for DiskError {
+    fn from(refusal: ProtectionRefusal) -> Self {
+        DiskError::new(ErrorCode::Refused, refusal.message.clone())
+            .on_disk(refusal.disk_id.clone())
+            .with_details(ErrorDetails::Refusal(refusal))
+    }
+}
+
+/// A `DiskError` anywhere in the chain is kept, with the context above it
+/// prepended to its message. Otherwise the first OS error in the chain
+/// decides the code.
+impl From<anyhow::Error> for DiskError {
+    fn from(err: anyhow::Error) -> Self {
+        let message = format!("{err:#}");
+        if let Some(inner) = err.chain().find_map(|e| e.downcast_ref::<DiskError>()) {
+            return DiskError {
+                message,
+                ..inner.clone()
+            };
+        }
+        let os_error = err
+            .chain()
+            .find_map(|e| e.downcast_ref::<std::io::Error>())
+            .and_then(std::io::Error::raw_os_error);
+        match os_error {
+            Some(os_error) => DiskError::from_os_error(os_error, message),
+            None => DiskError::new(ErrorCode::Failed, message),
+        }
+    }
+}
+
+impl From<std::io::Error> for DiskError {
+    fn from(err: std::io::Error) -> Self {
+        DiskError::from(anyhow::Error::from(err))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
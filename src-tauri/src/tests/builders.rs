@@ -0,0 +1,68 @@
+// src/tests/builders.rs
+// Disks and partitions for tests that work on an inventory rather than a
+// system; tests change the fields they care about with struct update syntax
+use crate::structs::{
+    DiskCapabilities, DiskInfo, DiskType, MountPoint, PartitionInfo, ProtectionReason,
+};
+
+/// Partition `number` of `disk`, an ext4 filesystem mounted at `mounts`
+pub fn partition(disk: &str, number: u32, mounts: &[&str]) -> PartitionInfo {
+    PartitionInfo {
+        partition_number: number,
+        size_bytes: 1_000_000,
+        used_bytes: None,
+        drive_letter: String::new(),
+        partition_id: format!("{disk}{number}"),
+        mount_points: mounts
+            .iter()
+            .map(|path| MountPoint {
+                path: (*path).to_string(),
+                ..MountPoint::default()
+            })
+            .collect(),
+        usage: None,
+        fs_type: Some("ext4".to_string()),
+        label: None,
+        uuid: None,
+        part_uuid: None,
+        part_type: None,
+        part_flags: vec![],
+    }
+}
+
+/// An unprotected USB stick that can be taken offline, brought back online
+/// and ejected
+pub fn disk(id: &str, partitions: Vec<PartitionInfo>) -> DiskInfo {
+    DiskInfo {
+        id: id.to_string(),
+        model: "Test".to_string(),
+        size_bytes: 2_000_000,
+        is_online: true,
+        is_system_disk: false,
+        protection: vec![],
+        partitions,
+        disk_type: DiskType::USBFlash,
+        serial_number: None,
+        health_percentage: None,
+        usage_percentage: None,
+        capabilities: DiskCapabilities {
+            can_offline: true,
+            can_online: true,
+            can_eject: true,
+            ..DiskCapabilities::default()
+        },
+    }
+}
+
+/// A system disk, protected for `reasons`
+pub fn protected_disk(
+    id: &str,
+    reasons: Vec<ProtectionReason>,
+    partitions: Vec<PartitionInfo>,
+) -> DiskInfo {
+    DiskInfo {
+        is_system_disk: true,
+        protection: reasons,
+        ..disk(id, partitions)
+    }
+}
@@ -0,0 +1,107 @@
+// src/tests/busy_tests.rs
+// Finding what keeps a mount busy, against a fake /proc
+
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::busy::{diagnose, find_blockers};
+
+const USB_PATH: &str = "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+
+const MOUNTINFO: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+40 22 8:17 / /media/stick rw,nosuid,nodev shared:30 - vfat /dev/sdb1 rw,fmask=0022
+41 40 8:18 / /media/stick/data rw,noexec shared:31 - ext4 /dev/sdb2 rw
+";
+
+fn build_tree(name: &str) -> FakeSysfs {
+    let fake = FakeSysfs::new(name);
+    fake.add_disk("sdb", "8:16", USB_PATH, 60_062_500);
+    fake.add_partition("sdb", "sdb1", 1, "8:17", 20_000_000);
+    fake.add_partition("sdb", "sdb2", 2, "8:18", 40_000_000);
+    fake.set_mountinfo(MOUNTINFO);
+    fake.set_swaps("Filename\tType\tSize\tUsed\tPriority");
+    fake.write(
+        "etc/passwd",
+        "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/bash",
+    );
+    fake
+}
+
+#[test]
+fn test_blockers_match_whole_path_components() {
+    let fake = FakeSysfs::new("busy-prefix");
+    fake.add_process(10, &[], &[("cwd", "/media/stick2")]);
+    fake.add_process(11, &[], &[("root", "/media/stick")]);
+    let root = fake.sysfs_root();
+
+    let blockers = find_blockers(&root, &["/media/stick".to_string()]);
+    assert_eq!(blockers.len(), 1);
+    assert_eq!(blockers[0].pid, 11);
+    // Kernel threads have an empty cmdline
+    assert_eq!(blockers[0].command, "kworker");
+}
+
+#[test]
+fn test_mapped_files_and_users_are_reported() {
+    let fake = build_tree("busy-maps");
+    fake.add_process(500, &["/media/stick/tool", "--serve"], &[]);
+    fake.write(
+        "proc/500/maps",
+        "\
+55d0c0000000-55d0c0021000 r-xp 00000000 08:11 1234                       /media/stick/tool
+7f2c4a000000-7f2c4a021000 r-xp 00000000 08:11 1240                       /media/stick/lib/lib a.so (deleted)
+7f2c4b000000-7f2c4b021000 rw-p 00000000 00:00 0                          [heap]
+7f2c4c000000-7f2c4c021000 r-xp 00000000 103:02 99                        /usr/lib/libc.so.6",
+    );
+
+    let blockers = find_blockers(&fake.sysfs_root(), &["/media/stick".to_string()]);
+
+    assert_eq!(blockers.len(), 1);
+    assert_eq!(blockers[0].command, "/media/stick/tool --serve");
+    assert_eq!(blockers[0].user, "alice");
+    assert_eq!(
+        blockers[0].paths,
+        vec!["/media/stick/lib/lib a.so", "/media/stick/tool"]
+    );
+}
+
+#[test]
+fn test_diagnose_includes_nested_mounts_and_swap_files() {
+    let fake = build_tree("busy-diagnose");
+    fake.set_swaps(
+        "Filename\tType\tSize\tUsed\tPriority
+/media/stick/swapfile file 2097148 0 -3",
+    );
+    fake.add_process(4242, &["vim"], &[("fd/3", "/media/stick/data/notes.txt")]);
+    fake.add_process(4300, &["bash"], &[("cwd", "/home/alice")]);
+
+    // By device node, the way udisks refers to it
+    let report = diagnose(&fake.sysfs_root(), "/dev/sdb1").unwrap();
+
+    assert_eq!(
+        report.mount_points,
+        vec!["/media/stick", "/media/stick/data"]
+    );
+    assert_eq!(report.swap, vec!["/media/stick/swapfile"]);
+    let pids: Vec<u32> = report.processes.iter().map(|p| p.pid).collect();
+    assert_eq!(pids, vec![4242]);
+    assert!(!report.is_empty());
+}
+
+#[test]
+fn test_diagnose_idle_mount_is_empty() {
+    let fake = build_tree("busy-idle");
+    fake.add_process(4300, &["bash"], &[("cwd", "/home/alice")]);
+
+    let report = diagnose(&fake.sysfs_root(), "/media/stick/data").unwrap();
+
+    assert_eq!(report.mount_points, vec!["/media/stick/data"]);
+    assert!(report.is_empty());
+}
+
+#[test]
+fn test_diagnose_unmounted_target_is_an_error() {
+    let fake = build_tree("busy-unmounted");
+
+    let err = diagnose(&fake.sysfs_root(), "/mnt/nothing").unwrap_err();
+    assert_eq!(err.to_string(), "/mnt/nothing is not mounted");
+}
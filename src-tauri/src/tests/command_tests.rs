@@ -0,0 +1,135 @@
+// src/tests/command_tests.rs
+// Running helper programs with time limits, and replaying saved transcripts
+use crate::disk_operations::command::{
+    CancelToken, CommandOutput, CommandRunner, Exchange, ExternalCommand, Recorder, Replay,
+    SystemRunner,
+};
+use crate::disk_operations::lsblk::{lsblk_command, read_lsblk};
+use crate::structs::{DiskError, ErrorCode};
+use std::time::{Duration, Instant};
+
+// util-linux 2.17 (CentOS 6) has no JSON output
+const CENTOS6: &str = include_str!("fixtures/commands/lsblk_centos6.json");
+const DEBIAN12: &str = include_str!("fixtures/lsblk/debian12.json");
+
+fn sh(script: &str) -> ExternalCommand {
+    ExternalCommand::new("sh").args(["-c", script])
+}
+
+fn run(command: &ExternalCommand) -> Result<CommandOutput, DiskError> {
+    SystemRunner.run(command).map_err(DiskError::from)
+}
+
+#[test]
+fn test_exit_code_and_output_are_captured() {
+    let command = sh("echo out; echo oops >&2; echo 'Usage: x' >&2; exit 3");
+
+    let output = run(&command).unwrap();
+
+    assert_eq!(output.status, Some(3));
+    assert_eq!(output.stdout, "out\n");
+    assert_eq!(output.stderr, "oops\nUsage: x\n");
+    let err = output.checked(&command.invocation).unwrap_err();
+    assert_eq!(err.to_string(), "sh exited with 3: oops");
+}
+
+#[test]
+fn test_output_is_not_localized() {
+    let output = run(&sh("echo \"$LC_ALL\"")).unwrap();
+
+    assert_eq!(output.stdout, "C\n");
+    assert!(output.success());
+}
+
+#[test]
+fn test_overrunning_program_is_killed() {
+    let command = sh("sleep 30").timeout(Duration::from_millis(100));
+    let started = Instant::now();
+
+    let err = run(&command).unwrap_err();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(err.code, ErrorCode::TimedOut);
+    assert_eq!(err.message, "sh -c sleep 30 did not finish within 0.1 s");
+}
+
+#[test]
+fn test_cancelled_program_is_killed() {
+    let token = CancelToken::default();
+    let command = sh("sleep 30").cancel_with(&token);
+    let canceller = token.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        canceller.cancel();
+    });
+    let started = Instant::now();
+
+    let err = run(&command).unwrap_err();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(err.code, ErrorCode::Cancelled);
+}
+
+#[test]
+fn test_missing_program_is_not_found() {
+    let err = run(&ExternalCommand::new("diskofflaner-no-such-program")).unwrap_err();
+
+    assert_eq!(err.code, ErrorCode::NotFound);
+    assert!(err
+        .message
+        .starts_with("Failed to run diskofflaner-no-such-program"));
+}
+
+#[test]
+fn test_recorded_transcript_replays() {
+    let path = std::env::temp_dir().join(format!(
+        "diskofflaner-transcript-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let recorded = Recorder::new(SystemRunner, &path)
+        .run(&sh("echo one; exit 1"))
+        .unwrap();
+    // A second recorder adds to what the first wrote
+    Recorder::new(SystemRunner, &path)
+        .run(&sh("echo two"))
+        .unwrap();
+
+    let replay = Replay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(replay.remaining().len(), 2);
+    assert_eq!(replay.run(&sh("echo one; exit 1")).unwrap(), recorded);
+    assert_eq!(replay.run(&sh("echo two")).unwrap().stdout, "two\n");
+    let err = replay.run(&sh("echo two")).unwrap_err();
+    assert_eq!(err.to_string(), "No recorded output for sh -c echo two");
+}
+
+#[test]
+fn test_lsblk_from_a_replay() {
+    let replay = Replay::new(vec![Exchange {
+        command: lsblk_command().invocation,
+        output: CommandOutput {
+            status: Some(0),
+            stdout: DEBIAN12.to_string(),
+            stderr: String::new(),
+        },
+    }]);
+
+    let output = read_lsblk(&replay).unwrap();
+
+    assert_eq!(output.blockdevices[0].name, "sda");
+    assert!(replay.remaining().is_empty());
+}
+
+#[test]
+fn test_lsblk_failure_from_a_saved_transcript() {
+    let replay = Replay::parse(CENTOS6).unwrap();
+
+    let err = read_lsblk(&replay).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "lsblk exited with 1: lsblk: invalid option -- 'J'"
+    );
+}
@@ -0,0 +1,247 @@
+// src/tests/commands_tests.rs
+// The commands' checks, logging and error mapping against the mock backend
+use super::builders::{disk, partition, protected_disk};
+use super::mock_backend::MockBackend;
+use crate::commands::AppState;
+use crate::structs::{
+    DiskError, ErrorCode, ErrorDetails, MountOptions, ProtectionReason, StepStatus, UnmountMode,
+};
+
+/// A system disk sda mounted at / and a USB stick sdb with two mounted
+/// partitions
+fn start() -> (MockBackend, AppState) {
+    let mock = MockBackend::new(vec![
+        protected_disk(
+            "sda",
+            vec![ProtectionReason::RootFilesystem],
+            vec![partition("sda", 1, &["/"])],
+        ),
+        disk(
+            "sdb",
+            vec![
+                partition("sdb", 1, &["/media/sdb1"]),
+                partition("sdb", 2, &["/media/sdb2"]),
+            ],
+        ),
+    ]);
+    let state = AppState::new(Box::new(mock.clone()));
+    (mock, state)
+}
+
+#[test]
+fn test_offline_changes_the_disk_and_drops_the_cache() {
+    let (mock, state) = start();
+    assert!(state.enumerate_disks(false).unwrap()[1].is_online);
+
+    let report = state.set_disk_offline("sdb", false).unwrap();
+
+    assert!(report.completed);
+    assert_eq!(mock.calls(), vec!["offline sdb"]);
+    let sdb = &state.enumerate_disks(false).unwrap()[1];
+    assert!(!sdb.is_online);
+    assert!(sdb.partitions.iter().all(|p| p.mount_points.is_empty()));
+
+    state.set_disk_online("sdb").unwrap();
+    assert!(state.enumerate_disks(false).unwrap()[1].is_online);
+}
+
+#[test]
+fn test_reads_are_served_from_the_cache() {
+    let (mock, state) = start();
+
+    state.enumerate_disks(false).unwrap();
+    state.system_info().unwrap();
+    assert_eq!(mock.state().enumerations, 1);
+
+    let info = state.system_info().unwrap();
+    state.enumerate_disks(true).unwrap();
+    assert_eq!(mock.state().enumerations, 2);
+    assert_eq!(info.total_disks, 2);
+    assert_eq!(info.system_disk_id.as_deref(), Some("sda"));
+}
+
+#[test]
+fn test_protected_disk_needs_an_override() {
+    let (mock, state) = start();
+
+    let err = state.set_disk_offline("sda", false).unwrap_err();
+    assert_eq!(err.code, ErrorCode::Refused);
+    assert_eq!(err.disk.as_deref(), Some("sda"));
+    assert!(matches!(
+        err.details.as_deref(),
+        Some(ErrorDetails::Refusal(_))
+    ));
+    let err = state
+        .unmount_partition("/", false, UnmountMode::Normal)
+        .unwrap_err();
+    assert_eq!(err.code, ErrorCode::Refused);
+    assert!(mock.calls().is_empty());
+
+    state.set_disk_offline("sda", true).unwrap();
+    assert_eq!(mock.calls(), vec!["offline sda"]);
+}
+
+#[test]
+fn test_root_on_a_device_mapper_volume_needs_an_override() {
+    let mock = MockBackend::new(vec![disk("sdb", vec![])]);
+    // / is on dm-1, which no partition in the inventory stands for
+    mock.mount_stacked(
+        "/",
+        vec![
+            ProtectionReason::RootFilesystem,
+            ProtectionReason::RootStack,
+        ],
+    );
+    let state = AppState::new(Box::new(mock.clone()));
+
+    let err = state
+        .unmount_partition("/", false, UnmountMode::Lazy)
+        .unwrap_err();
+
+    assert_eq!(err.code, ErrorCode::Refused);
+    assert_eq!(
+        err.message,
+        "/ holds the root filesystem and backs a system volume: unmount refused without an \
+         explicit override"
+    );
+    assert!(mock.calls().is_empty());
+    state
+        .unmount_partition("/", true, UnmountMode::Lazy)
+        .unwrap();
+    assert_eq!(mock.calls(), vec!["unmount / lazy"]);
+}
+
+#[test]
+fn test_unresolved_target_is_refused() {
+    let (mock, state) = start();
+
+    let err = state
+        .unmount_partition("/mnt/elsewhere", false, UnmountMode::Lazy)
+        .unwrap_err();
+
+    assert_eq!(err.code, ErrorCode::NotMounted);
+    assert!(mock.calls().is_empty());
+}
+
+#[test]
+fn test_missing_capability_is_unsupported() {
+    let (mock, state) = start();
+    mock.state().disks[1].capabilities.can_eject = false;
+
+    let err = state.eject_disk("sdb", false).unwrap_err();
+
+    assert_eq!(err.code, ErrorCode::Unsupported);
+    assert_eq!(err.message, "Disk sdb cannot be ejected");
+    assert!(mock.calls().is_empty());
+}
+
+#[test]
+fn test_busy_offline_is_incomplete() {
+    let (mock, state) = start();
+    mock.hold("/media/sdb2", "vim", 4242);
+
+    let err = state.set_disk_offline("sdb", false).unwrap_err();
+
+    assert_eq!(err.code, ErrorCode::Incomplete);
+    assert_eq!(
+        err.message,
+        "Failed to set Disk sdb Offline: target is busy"
+    );
+    let Some(ErrorDetails::Offline { report }) = err.details.as_deref() else {
+        panic!("no offline report in {:?}", err.details);
+    };
+    let statuses: Vec<StepStatus> = report.steps.iter().map(|s| s.status).collect();
+    assert_eq!(statuses, vec![StepStatus::RolledBack, StepStatus::Failed]);
+    assert!(mock.disk("sdb").unwrap().is_online);
+}
+
+#[test]
+fn test_busy_unmount_names_the_holders() {
+    let (mock, state) = start();
+    mock.hold("/media/sdb1", "vim", 4242);
+
+    let err = state
+        .unmount_partition("/media/sdb1", false, UnmountMode::Normal)
+        .unwrap_err();
+
+    assert_eq!(err.code, ErrorCode::Busy);
+    assert_eq!(err.message, "target is busy (in use by vim (4242))");
+    assert_eq!(err.partition.as_deref(), Some("/media/sdb1"));
+    let Some(ErrorDetails::Busy { report }) = err.details.as_deref() else {
+        panic!("no busy report in {:?}", err.details);
+    };
+    assert_eq!(report.processes[0].pid, 4242);
+
+    state
+        .unmount_partition("/media/sdb1", false, UnmountMode::Lazy)
+        .unwrap();
+    assert_eq!(
+        mock.calls(),
+        vec!["unmount /media/sdb1 normal", "unmount /media/sdb1 lazy"]
+    );
+}
+
+#[test]
+fn test_partly_unmounted_disk_lists_each_result() {
+    let (mock, state) = start();
+    mock.hold("/media/sdb2", "bash", 4300);
+
+    let err = state
+        .unmount_disk("sdb", false, UnmountMode::Normal)
+        .unwrap_err();
+
+    assert_eq!(err.code, ErrorCode::PartlyUnmounted);
+    assert_eq!(err.message, "Failed to unmount /media/sdb2 on Disk sdb");
+    let Some(ErrorDetails::Unmount { results }) = err.details.as_deref() else {
+        panic!("no unmount results in {:?}", err.details);
+    };
+    assert_eq!(results[0].status, StepStatus::Done);
+    assert_eq!(results[1].status, StepStatus::Failed);
+}
+
+#[test]
+fn test_backend_errors_name_the_disk() {
+    let (mock, state) = start();
+    let denied = DiskError::new(ErrorCode::PermissionDenied, "Failed to write device/state");
+    mock.fail("online", denied);
+
+    let err = state.set_disk_online("sdb").unwrap_err();
+    assert_eq!(err.code, ErrorCode::PermissionDenied);
+    assert_eq!(err.message, "Failed to write device/state");
+    assert_eq!(err.disk.as_deref(), Some("sdb"));
+
+    let err = state.set_disk_offline("sdz", false).unwrap_err();
+    assert_eq!(err.code, ErrorCode::NotFound);
+    assert_eq!(err.disk.as_deref(), Some("sdz"));
+}
+
+#[test]
+fn test_mount_and_mount_again() {
+    let (_mock, state) = start();
+    state
+        .unmount_partition("/media/sdb1", false, UnmountMode::Normal)
+        .unwrap();
+
+    let mounted = state
+        .mount_partition("sdb", 1, None, &MountOptions::default())
+        .unwrap();
+    assert_eq!(mounted.mount_point.as_deref(), Some("/media/sdb1"));
+
+    let err = state
+        .mount_partition("sdb", 1, None, &MountOptions::default())
+        .unwrap_err();
+    assert_eq!(err.code, ErrorCode::AlreadyMounted);
+    assert_eq!(err.partition.as_deref(), Some("1"));
+}
+
+#[test]
+fn test_ejected_disk_comes_back_on_rescan() {
+    let (_mock, state) = start();
+
+    state.eject_disk("sdb", false).unwrap();
+    assert_eq!(state.enumerate_disks(false).unwrap().len(), 1);
+
+    let report = state.rescan_storage().unwrap();
+    assert_eq!(report.reappeared[0].id, "sdb");
+    assert_eq!(state.enumerate_disks(false).unwrap().len(), 2);
+}
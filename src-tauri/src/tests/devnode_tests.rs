@@ -0,0 +1,91 @@
+// src/tests/devnode_tests.rs
+// Partition device naming for every block device family
+
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::devnode::{
+    partition_name, partition_number_from_name, read_partition_number, resolve_partition_name,
+    resolve_partition_node,
+};
+use crate::disk_operations::sysfs::SysfsRoot;
+
+// (disk, partition number, expected kernel name)
+const FAMILIES: [(&str, u32, &str); 11] = [
+    ("sda", 1, "sda1"),
+    ("sdaa", 12, "sdaa12"),
+    ("hda", 2, "hda2"),
+    ("vda", 3, "vda3"),
+    ("xvda", 1, "xvda1"),
+    ("nvme0n1", 1, "nvme0n1p1"),
+    ("nvme1n2", 14, "nvme1n2p14"),
+    ("mmcblk0", 1, "mmcblk0p1"),
+    ("loop0", 1, "loop0p1"),
+    ("nbd0", 1, "nbd0p1"),
+    ("md127", 1, "md127p1"),
+];
+
+#[test]
+fn test_partition_name_per_family() {
+    for (disk, number, expected) in FAMILIES {
+        assert_eq!(partition_name(disk, number), expected, "{disk} #{number}");
+    }
+}
+
+#[test]
+fn test_partition_number_from_name_per_family() {
+    for (disk, number, name) in FAMILIES {
+        assert_eq!(
+            partition_number_from_name(disk, name),
+            Some(number),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn test_partition_number_from_name_rejects_other_devices() {
+    assert_eq!(partition_number_from_name("sda", "sdb1"), None);
+    assert_eq!(partition_number_from_name("sda", "sda"), None);
+    assert_eq!(partition_number_from_name("nvme0n1", "nvme0n11"), None);
+    assert_eq!(partition_number_from_name("mmcblk0", "mmcblk0boot0"), None);
+    assert_eq!(partition_number_from_name("mmcblk0", "mmcblk0p"), None);
+}
+
+#[test]
+fn test_resolve_uses_sysfs_partition_numbers() {
+    let fake = FakeSysfs::new("devnode-gaps");
+    fake.add_disk(
+        "sdb",
+        "8:16",
+        "pci0000:00/ata2/host1/target1:0:0/1:0:0:0",
+        1000,
+    );
+    fake.add_partition("sdb", "sdb1", 1, "8:17", 100);
+    fake.add_partition("sdb", "sdb5", 5, "8:21", 100);
+    let root = fake.sysfs_root();
+
+    assert_eq!(resolve_partition_name(&root, "sdb", 5), "sdb5");
+    assert_eq!(
+        resolve_partition_node(&root, "sdb", 1),
+        fake.path("dev/sdb1")
+    );
+}
+
+#[test]
+fn test_resolve_falls_back_to_naming_rule() {
+    let root = SysfsRoot::new("/nonexistent");
+    assert_eq!(resolve_partition_name(&root, "mmcblk1", 2), "mmcblk1p2");
+    assert_eq!(
+        resolve_partition_node(&root, "nvme0n1", 3),
+        std::path::Path::new("/nonexistent/dev/nvme0n1p3")
+    );
+}
+
+#[test]
+fn test_read_partition_number_from_class_block() {
+    let fake = FakeSysfs::new("devnode-class");
+    fake.write("sys/class/block/loop0p3/partition", "3");
+    let root = fake.sysfs_root();
+
+    assert_eq!(read_partition_number(&root, "loop0p3"), Some(3));
+    assert_eq!(read_partition_number(&root, "loop0"), None);
+}
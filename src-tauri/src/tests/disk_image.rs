@@ -0,0 +1,252 @@
+// src/tests/disk_image.rs
+// Sparse disk image files with an MBR and ext4 partitions, and loop devices
+// to attach them to when running as root
+use std::cell::Cell;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+pub const SECTOR: u64 = 512;
+const MBR_SIZE: usize = 512;
+const MIB: u64 = 1024 * 1024;
+
+/// Bytes of a partition copied out for probing, enough for every superblock
+/// the probe looks at
+const PROBE_BYTES: u64 = 128 * 1024;
+
+pub struct Partition {
+    pub start: u64,
+    pub sectors: u64,
+    pub label: String,
+}
+
+pub struct DiskImage {
+    path: PathBuf,
+    sectors: u64,
+    partitions: Vec<Partition>,
+    /// Whether mkfs made real filesystems rather than bare superblocks
+    formatted: bool,
+}
+
+impl DiskImage {
+    /// A sparse image of `size_mib` with one ext4 partition per entry of
+    /// `partitions` (label, size in MiB), the first at 1 MiB
+    pub fn new(name: &str, size_mib: u64, partitions: &[(&str, u64)]) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "diskofflaner-image-{}-{}.img",
+            name,
+            std::process::id()
+        ));
+        let file = File::create(&path).unwrap();
+        file.set_len(size_mib * MIB).unwrap();
+
+        let mut start = MIB / SECTOR;
+        let partitions: Vec<Partition> = partitions
+            .iter()
+            .map(|(label, size)| {
+                let partition = Partition {
+                    start,
+                    sectors: size * MIB / SECTOR,
+                    label: (*label).to_string(),
+                };
+                start += partition.sectors;
+                partition
+            })
+            .collect();
+        let mut image = Self {
+            path,
+            sectors: size_mib * MIB / SECTOR,
+            partitions,
+            formatted: true,
+        };
+        image.write_mbr();
+        for partition in &image.partitions {
+            image.formatted &= image.format_ext4(partition);
+        }
+        image
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn sectors(&self) -> u64 {
+        self.sectors
+    }
+
+    pub fn partitions(&self) -> &[Partition] {
+        &self.partitions
+    }
+
+    pub fn partition_count(&self) -> u32 {
+        u32::try_from(self.partitions.len()).unwrap()
+    }
+
+    /// Whether the partitions hold filesystems the kernel can mount
+    pub fn is_formatted(&self) -> bool {
+        self.formatted
+    }
+
+    fn open(&self) -> File {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)
+            .unwrap()
+    }
+
+    /// Linux (0x83) primary partitions
+    fn write_mbr(&self) {
+        let mut mbr = [0u8; MBR_SIZE];
+        for (slot, partition) in self.partitions.iter().enumerate() {
+            let entry = &mut mbr[446 + 16 * slot..446 + 16 * (slot + 1)];
+            entry[4] = 0x83;
+            entry[8..12].copy_from_slice(&u32::try_from(partition.start).unwrap().to_le_bytes());
+            entry[12..16].copy_from_slice(&u32::try_from(partition.sectors).unwrap().to_le_bytes());
+        }
+        mbr[510..].copy_from_slice(&[0x55, 0xAA]);
+        let mut file = self.open();
+        file.write_all(&mbr).unwrap();
+    }
+
+    /// mkfs.ext4 `partition` in place. Without mkfs only an ext4
+    /// superblock is written, which the probe recognises but the kernel
+    /// will not mount; returns whether mkfs ran.
+    fn format_ext4(&self, partition: &Partition) -> bool {
+        let made = Command::new("mkfs.ext4")
+            .args(["-q", "-F", "-L", &partition.label])
+            .arg("-E")
+            .arg(format!("offset={}", partition.start * SECTOR))
+            .arg(&self.path)
+            .arg(format!("{}k", partition.sectors * SECTOR / 1024))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !made {
+            self.write_superblock(partition);
+        }
+        made
+    }
+
+    fn write_superblock(&self, partition: &Partition) {
+        let mut sb = [0u8; 1024];
+        sb[0x38..0x3A].copy_from_slice(&0xEF53u16.to_le_bytes());
+        sb[0x5C..0x60].copy_from_slice(&0x3Cu32.to_le_bytes());
+        sb[0x60..0x64].copy_from_slice(&0x2C2u32.to_le_bytes());
+        sb[0x64..0x68].copy_from_slice(&0x46Bu32.to_le_bytes());
+        let label = partition.label.as_bytes();
+        sb[0x78..0x78 + label.len()].copy_from_slice(label);
+        let mut file = self.open();
+        file.seek(SeekFrom::Start(partition.start * SECTOR + 1024))
+            .unwrap();
+        file.write_all(&sb).unwrap();
+    }
+
+    /// Copy the start of partition `number` to `to`, so that it can stand in
+    /// for the partition's device node
+    pub fn extract(&self, number: u32, to: &Path) {
+        let partition = &self.partitions[number as usize - 1];
+        let mut file = self.open();
+        file.seek(SeekFrom::Start(partition.start * SECTOR))
+            .unwrap();
+        let len = PROBE_BYTES.min(partition.sectors * SECTOR);
+        let mut head = vec![0u8; usize::try_from(len).unwrap()];
+        file.read_exact(&mut head).unwrap();
+        std::fs::write(to, head).unwrap();
+    }
+}
+
+impl Drop for DiskImage {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// An image attached with losetup, detached again on drop
+pub struct LoopDevice {
+    /// Kernel name, e.g. loop0
+    pub name: String,
+    /// Set once detached, so that drop leaves a reused name alone
+    detached: Cell<bool>,
+}
+
+impl LoopDevice {
+    /// Attach `image` and wait for its partitions. None unless running as
+    /// root with losetup available and the partitions showing up, as they
+    /// do not in some containers.
+    pub fn attach(image: &DiskImage) -> Option<Self> {
+        if !crate::utils::is_elevated() || !image.is_formatted() {
+            return None;
+        }
+        let output = Command::new("losetup")
+            .args(["--find", "--show", "--partscan"])
+            .arg(image.path())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let node = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let device = Self {
+            name: node.trim_start_matches("/dev/").to_string(),
+            detached: Cell::new(false),
+        };
+        // Without udev the partition table may not have been read yet
+        if !device.has_partitions(image) {
+            let _ = Command::new("partx")
+                .args(["--add", &node])
+                .stderr(Stdio::null())
+                .status();
+        }
+        for _ in 0..50 {
+            if device.has_partitions(image) {
+                return Some(device);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        None
+    }
+
+    /// Kernel name of partition `number`, e.g. loop0p1
+    pub fn partition(&self, number: u32) -> String {
+        format!("{}p{number}", self.name)
+    }
+
+    /// major:minor of the device or one of its partitions, from sysfs
+    pub fn dev(name: &str) -> String {
+        let path = format!("/sys/class/block/{name}/dev");
+        std::fs::read_to_string(path).unwrap().trim().to_string()
+    }
+
+    fn has_partitions(&self, image: &DiskImage) -> bool {
+        (1..=image.partition_count()).all(|number| {
+            let name = self.partition(number);
+            Path::new("/sys/class/block").join(&name).exists()
+                && Path::new("/dev").join(&name).exists()
+        })
+    }
+
+    /// Detach the device, as powering it off would
+    pub fn detach(&self) -> bool {
+        if self.detached.get() {
+            return true;
+        }
+        let detached = Command::new("losetup")
+            .args(["--detach", &format!("/dev/{}", self.name)])
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        self.detached.set(detached);
+        detached
+    }
+}
+
+impl Drop for LoopDevice {
+    fn drop(&mut self) {
+        self.detach();
+    }
+}
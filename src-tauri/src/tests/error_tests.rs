@@ -0,0 +1,105 @@
+// src/tests/error_tests.rs
+// Turning backend errors into the DiskError every command returns
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::offline_method::select;
+use crate::disk_operations::udisks::UDisksError;
+use crate::structs::{BusyReport, DiskError, ErrorCode, ErrorDetails};
+use anyhow::Context;
+
+fn os_error(errno: i32) -> anyhow::Result<()> {
+    Err(std::io::Error::from_raw_os_error(errno)).context("Failed to unmount /media/stick")
+}
+
+#[test]
+fn test_errno_decides_the_code() {
+    let code = |errno| DiskError::from(os_error(errno).unwrap_err()).code;
+
+    assert_eq!(code(libc::EBUSY), ErrorCode::Busy);
+    assert_eq!(code(libc::EPERM), ErrorCode::PermissionDenied);
+    assert_eq!(code(libc::EACCES), ErrorCode::PermissionDenied);
+    assert_eq!(code(libc::ENOENT), ErrorCode::NotFound);
+    assert_eq!(code(libc::EINVAL), ErrorCode::InvalidArgument);
+    assert_eq!(code(libc::EIO), ErrorCode::Failed);
+}
+
+#[test]
+fn test_os_error_and_context_are_kept() {
+    let err = DiskError::from(os_error(libc::EBUSY).unwrap_err());
+
+    assert_eq!(err.os_error, Some(libc::EBUSY));
+    assert_eq!(
+        err.message,
+        "Failed to unmount /media/stick: Device or resource busy (os error 16)"
+    );
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("Close the programs using it and try again")
+    );
+}
+
+#[test]
+fn test_disk_error_passes_through_context() {
+    let inner = DiskError::new(ErrorCode::NotFound, "Disk sdb not found").on_disk("sdb");
+    let err = Err::<(), _>(inner).context("Failed to eject").unwrap_err();
+
+    let err = DiskError::from(err).on_disk("sdc");
+
+    assert_eq!(err.code, ErrorCode::NotFound);
+    assert_eq!(err.message, "Failed to eject: Disk sdb not found");
+    assert_eq!(err.disk.as_deref(), Some("sdb"));
+    assert_eq!(err.os_error, None);
+}
+
+#[test]
+fn test_plain_errors_are_failures() {
+    let err = DiskError::from(anyhow::anyhow!("Failed to open disk"));
+
+    assert_eq!(err.code, ErrorCode::Failed);
+    assert_eq!(err.message, "Failed to open disk");
+    assert_eq!(err.hint, None);
+}
+
+#[test]
+fn test_backend_reports_missing_disks() {
+    let fake = FakeSysfs::new("error-missing");
+
+    let err = DiskError::from(select(&fake.sysfs_root(), "sdz").unwrap_err());
+
+    assert_eq!(err.code, ErrorCode::NotFound);
+    assert_eq!(err.disk.as_deref(), Some("sdz"));
+}
+
+#[test]
+fn test_udisks_errors_map_to_codes() {
+    let denied = DiskError::from(UDisksError::NotAuthorized("Not authorized".to_string()));
+    let busy = DiskError::from(UDisksError::DeviceBusy("target is busy".to_string()));
+    let down = DiskError::from(UDisksError::Unavailable("no daemon".to_string()));
+
+    assert_eq!(denied.code, ErrorCode::PermissionDenied);
+    assert_eq!(denied.message, "Not authorized");
+    assert_eq!(busy.code, ErrorCode::Busy);
+    assert_eq!(down.code, ErrorCode::Unavailable);
+    assert!(down.hint.unwrap().contains("udisks2"));
+}
+
+#[test]
+fn test_serialization_shape() {
+    let report = BusyReport {
+        mount_points: vec!["/media/stick".to_string()],
+        processes: vec![],
+        swap: vec![],
+    };
+    let err = DiskError::from_os_error(libc::EBUSY, "Failed to unmount /media/stick")
+        .on_partition("/media/stick")
+        .with_details(ErrorDetails::Busy { report });
+
+    let json = serde_json::to_value(&err).unwrap();
+
+    assert_eq!(json["code"], "busy");
+    assert_eq!(json["message"], "Failed to unmount /media/stick");
+    assert_eq!(json["disk"], serde_json::Value::Null);
+    assert_eq!(json["partition"], "/media/stick");
+    assert_eq!(json["os_error"], 16);
+    assert_eq!(json["details"]["type"], "busy");
+    assert_eq!(json["details"]["report"]["mount_points"][0], "/media/stick");
+}
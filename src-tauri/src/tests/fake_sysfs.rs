@@ -0,0 +1,170 @@
+// src/tests/fake_sysfs.rs
+// Builds a throwaway /sys + /proc + /run/udev tree for the sysfs backend
+use crate::disk_operations::sysfs::SysfsRoot;
+use std::path::PathBuf;
+
+pub struct FakeSysfs {
+    root: PathBuf,
+}
+
+impl FakeSysfs {
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "diskofflaner-sysfs-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sys/block")).unwrap();
+        Self { root }
+    }
+
+    pub fn sysfs_root(&self) -> SysfsRoot {
+        SysfsRoot::new(&self.root)
+    }
+
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.root.join(rel)
+    }
+
+    pub fn write(&self, rel: &str, value: &str) {
+        let path = self.path(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, format!("{value}\n")).unwrap();
+    }
+
+    /// Make `rel` a symlink to `target`, e.g. a device node to a real one
+    pub fn link(&self, rel: &str, target: impl AsRef<std::path::Path>) {
+        let path = self.path(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(target, path).unwrap();
+    }
+
+    /// Add a whole disk below `sys/devices/<device_path>` and link it from
+    /// `sys/block`, the way the kernel lays it out.
+    pub fn add_disk(&self, name: &str, dev: &str, device_path: &str, sectors: u64) {
+        let rel = format!("sys/devices/{device_path}/block/{name}");
+        let disk_dir = self.path(&rel);
+        std::fs::create_dir_all(&disk_dir).unwrap();
+        std::os::unix::fs::symlink("../..", disk_dir.join("device")).unwrap();
+        std::os::unix::fs::symlink(
+            format!("../devices/{device_path}/block/{name}"),
+            self.path(&format!("sys/block/{name}")),
+        )
+        .unwrap();
+
+        self.write(&format!("{rel}/dev"), dev);
+        self.write(&format!("{rel}/size"), &sectors.to_string());
+        self.write(&format!("{rel}/removable"), "0");
+        self.link_class_block(name, dev, &format!("devices/{device_path}/block/{name}"));
+    }
+
+    /// Undo `add_disk`, as when the kernel deletes the device
+    pub fn remove_disk(&self, name: &str, dev: &str, device_path: &str) {
+        for link in [
+            format!("sys/block/{name}"),
+            format!("sys/class/block/{name}"),
+            format!("sys/dev/block/{dev}"),
+        ] {
+            std::fs::remove_file(self.path(&link)).unwrap();
+        }
+        std::fs::remove_dir_all(self.path(&format!("sys/devices/{device_path}/block"))).unwrap();
+    }
+
+    /// Add a block device with no backing `device` (loop, dm, md, zram)
+    pub fn add_virtual(&self, name: &str, dev: &str, sectors: u64) {
+        let rel = format!("sys/devices/virtual/block/{name}");
+        std::fs::create_dir_all(self.path(&rel)).unwrap();
+        std::os::unix::fs::symlink(
+            format!("../devices/virtual/block/{name}"),
+            self.path(&format!("sys/block/{name}")),
+        )
+        .unwrap();
+        self.write(&format!("{rel}/dev"), dev);
+        self.write(&format!("{rel}/size"), &sectors.to_string());
+        self.link_class_block(name, dev, &format!("devices/virtual/block/{name}"));
+    }
+
+    pub fn add_partition(&self, disk: &str, name: &str, number: u32, dev: &str, sectors: u64) {
+        let rel = format!("sys/block/{disk}/{name}");
+        self.write(&format!("{rel}/partition"), &number.to_string());
+        self.write(&format!("{rel}/dev"), dev);
+        self.write(&format!("{rel}/size"), &sectors.to_string());
+        self.link_class_block(name, dev, &format!("block/{disk}/{name}"));
+    }
+
+    /// Link `sys/class/block/<name>` and `sys/dev/block/<dev>` to `target`,
+    /// relative to `sys`
+    fn link_class_block(&self, name: &str, dev: &str, target: &str) {
+        for (dir, link) in [("sys/class/block", name), ("sys/dev/block", dev)] {
+            let dir = self.path(dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::os::unix::fs::symlink(format!("../../{target}"), dir.join(link)).unwrap();
+        }
+    }
+
+    /// Stack `holder` on `slave` (dm-crypt, LVM, md), linking both ways
+    pub fn add_slave(&self, holder: &str, slave: &str) {
+        for (from, dir, to) in [(holder, "slaves", slave), (slave, "holders", holder)] {
+            let dir = self.path(&format!("sys/class/block/{from}/{dir}"));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::os::unix::fs::symlink(format!("../../{to}"), dir.join(to)).unwrap();
+        }
+    }
+
+    /// Bind the device at `sys/devices/<device_path>` to `driver` on `bus`
+    pub fn bind_driver(&self, device_path: &str, bus: &str, driver: &str) {
+        let driver_dir = self.path(&format!("sys/bus/{bus}/drivers/{driver}"));
+        std::fs::create_dir_all(&driver_dir).unwrap();
+        let device_dir = self.path(&format!("sys/devices/{device_path}"));
+        std::os::unix::fs::symlink(driver_dir, device_dir.join("driver")).unwrap();
+    }
+
+    /// Set an attribute on a disk, relative to `sys/block/<disk>`
+    pub fn set_disk_attr(&self, disk: &str, attr: &str, value: &str) {
+        self.write(&format!("sys/block/{disk}/{attr}"), value);
+    }
+
+    pub fn set_mountinfo(&self, content: &str) {
+        self.write("proc/self/mountinfo", content.trim_end());
+    }
+
+    pub fn set_swaps(&self, content: &str) {
+        self.write("proc/swaps", content.trim_end());
+    }
+
+    /// Add a process owned by uid 1000 to `proc`. `links` are (name, target)
+    /// pairs such as ("cwd", "/media/stick") or ("fd/3", "/media/stick/a.txt").
+    pub fn add_process(&self, pid: u32, cmdline: &[&str], links: &[(&str, &str)]) {
+        let dir = self.path(&format!("proc/{pid}"));
+        std::fs::create_dir_all(dir.join("fd")).unwrap();
+        let mut content = cmdline.join("\0");
+        if !content.is_empty() {
+            content.push('\0');
+        }
+        std::fs::write(dir.join("cmdline"), content).unwrap();
+        std::fs::write(
+            dir.join("comm"),
+            format!("{}\n", cmdline.first().unwrap_or(&"kworker")),
+        )
+        .unwrap();
+        std::fs::write(dir.join("status"), "Uid:\t1000\t1000\t1000\t1000\n").unwrap();
+        for (name, target) in links {
+            std::os::unix::fs::symlink(target, dir.join(name)).unwrap();
+        }
+    }
+
+    pub fn set_udev(&self, dev: &str, properties: &[(&str, &str)]) {
+        let content: Vec<String> = properties
+            .iter()
+            .map(|(k, v)| format!("E:{k}={v}"))
+            .collect();
+        self.write(&format!("run/udev/data/b{dev}"), &content.join("\n"));
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,138 @@
+// src/tests/hotplug_tests.rs
+// Kernel uevent parsing and turning inventories into hotplug events
+use super::builders::{disk, partition};
+use crate::disk_operations::hotplug::{diff, parse_uevent, HotplugEvent, Tracker, Uevent};
+
+/// "disk-added sdb", "partition-mounted sdb1 /media/stick", ...
+fn summary(events: &[HotplugEvent]) -> Vec<String> {
+    events
+        .iter()
+        .map(|event| match event {
+            HotplugEvent::PartitionMounted(m) => format!(
+                "{} {}{} {}",
+                event.name(),
+                m.disk.id,
+                m.partition_number,
+                m.mount_point
+            ),
+            _ => format!("{} {}", event.name(), event.disk().id),
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_kernel_uevent() {
+    let datagram = b"add@/devices/pci0000:00/usb2/2-1/host6/target6:0:0/6:0:0:0/block/sdb\0\
+ACTION=add\0DEVPATH=/devices/pci0000:00/usb2/2-1/host6/target6:0:0/6:0:0:0/block/sdb\0\
+SUBSYSTEM=block\0MAJOR=8\0MINOR=16\0DEVNAME=sdb\0DEVTYPE=disk\0SEQNUM=4242\0";
+
+    assert_eq!(
+        parse_uevent(datagram),
+        Some(Uevent {
+            action: "add".to_string(),
+            devpath: "/devices/pci0000:00/usb2/2-1/host6/target6:0:0/6:0:0:0/block/sdb".to_string(),
+            subsystem: "block".to_string(),
+            devname: Some("sdb".to_string()),
+            devtype: Some("disk".to_string()),
+        })
+    );
+}
+
+#[test]
+fn test_parse_ignores_non_uevents() {
+    assert_eq!(parse_uevent(b"libudev\0\xfe\xed\xca\xfe"), None);
+    assert_eq!(parse_uevent(b"remove@/devices/x\0SUBSYSTEM=usb\0"), None);
+    assert_eq!(parse_uevent(b""), None);
+
+    let usb = parse_uevent(b"bind@/devices/x\0ACTION=bind\0SUBSYSTEM=usb\0").unwrap();
+    assert_eq!(usb.subsystem, "usb");
+    assert_eq!(usb.devpath, "/devices/x");
+    assert_eq!(usb.devname, None);
+}
+
+#[test]
+fn test_diff_reports_added_and_removed_disks() {
+    let before = vec![
+        disk("sda", vec![]),
+        disk("sdb", vec![partition("sdb", 1, &[])]),
+    ];
+    let after = vec![disk("sda", vec![]), disk("sdc", vec![])];
+
+    assert_eq!(
+        summary(&diff(&before, &after)),
+        vec!["disk-removed sdb", "disk-added sdc"]
+    );
+}
+
+#[test]
+fn test_diff_reports_new_mounts() {
+    let before = vec![disk(
+        "sdb",
+        vec![partition("sdb", 1, &[]), partition("sdb", 2, &["/mnt/b"])],
+    )];
+    let after = vec![disk(
+        "sdb",
+        vec![
+            partition("sdb", 1, &["/media/stick"]),
+            partition("sdb", 2, &["/mnt/b", "/srv/b"]),
+        ],
+    )];
+
+    assert_eq!(
+        summary(&diff(&before, &after)),
+        vec![
+            "disk-changed sdb",
+            "partition-mounted sdb1 /media/stick",
+            "partition-mounted sdb2 /srv/b"
+        ]
+    );
+}
+
+#[test]
+fn test_diff_reports_unmounts_and_state_as_changes() {
+    let before = vec![disk("sdb", vec![partition("sdb", 1, &["/media/stick"])])];
+    let unmounted = vec![disk("sdb", vec![partition("sdb", 1, &[])])];
+    let mut offline = unmounted.clone();
+    offline[0].is_online = false;
+
+    assert_eq!(
+        summary(&diff(&before, &unmounted)),
+        vec!["disk-changed sdb"]
+    );
+    assert_eq!(
+        summary(&diff(&unmounted, &offline)),
+        vec!["disk-changed sdb"]
+    );
+}
+
+#[test]
+fn test_usage_changes_are_not_events() {
+    let before = vec![disk("sdb", vec![partition("sdb", 1, &["/media/stick"])])];
+    let mut after = before.clone();
+    after[0].usage_percentage = Some(42.0);
+    after[0].partitions[0].used_bytes = Some(420_000);
+
+    assert!(diff(&before, &after).is_empty());
+}
+
+#[test]
+fn test_tracker_remembers_the_last_inventory() {
+    let mut tracker = Tracker::new(vec![disk("sda", vec![])]);
+
+    let added = tracker.update(vec![disk("sda", vec![]), disk("sdb", vec![])]);
+    let again = tracker.update(vec![disk("sda", vec![]), disk("sdb", vec![])]);
+    let removed = tracker.update(vec![disk("sda", vec![])]);
+
+    assert_eq!(summary(&added), vec!["disk-added sdb"]);
+    assert!(again.is_empty());
+    assert_eq!(summary(&removed), vec!["disk-removed sdb"]);
+}
+
+#[test]
+fn test_events_serialize_as_their_payload() {
+    let event = HotplugEvent::DiskAdded(disk("sdb", vec![]));
+    let json = serde_json::to_value(&event).unwrap();
+
+    assert_eq!(json["id"], "sdb");
+    assert_eq!(json["is_online"], true);
+}
@@ -0,0 +1,82 @@
+// src/tests/inventory_tests.rs
+// The shared disk inventory cache: expiry, invalidation and failures
+use super::builders::disk;
+use crate::inventory::InventoryCache;
+use crate::structs::DiskInfo;
+use anyhow::{bail, Result};
+use std::cell::Cell;
+use std::time::Duration;
+
+/// Enumerates "disk<n>" on the n-th call
+struct Counter {
+    calls: Cell<usize>,
+}
+
+impl Counter {
+    fn new() -> Self {
+        Self {
+            calls: Cell::new(0),
+        }
+    }
+
+    fn enumerate(&self) -> Vec<DiskInfo> {
+        self.calls.set(self.calls.get() + 1);
+        vec![disk(&format!("disk{}", self.calls.get()), vec![])]
+    }
+
+    fn ids(&self, cache: &InventoryCache) -> String {
+        cache.disks(|| Ok(self.enumerate())).unwrap()[0].id.clone()
+    }
+}
+
+#[test]
+fn test_inventory_is_reused_until_it_expires() {
+    let cache = InventoryCache::new(Duration::from_secs(90));
+    let counter = Counter::new();
+
+    assert_eq!(counter.ids(&cache), "disk1");
+    assert_eq!(counter.ids(&cache), "disk1");
+    assert_eq!(counter.calls.get(), 1);
+
+    cache.set_ttl(Duration::ZERO);
+    assert_eq!(counter.ids(&cache), "disk2");
+    assert_eq!(counter.ids(&cache), "disk3");
+}
+
+#[test]
+fn test_invalidate_forces_a_new_inventory() {
+    let cache = InventoryCache::new(Duration::from_secs(90));
+    let counter = Counter::new();
+
+    assert_eq!(counter.ids(&cache), "disk1");
+    cache.invalidate();
+    assert_eq!(counter.ids(&cache), "disk2");
+    assert_eq!(counter.ids(&cache), "disk2");
+}
+
+#[test]
+fn test_invalidate_on_drop_covers_early_returns() {
+    let cache = InventoryCache::new(Duration::from_secs(90));
+    let counter = Counter::new();
+    counter.ids(&cache);
+
+    let mutate = || -> Result<()> {
+        let _invalidate = cache.invalidate_on_drop();
+        bail!("Failed halfway")
+    };
+    assert!(mutate().is_err());
+
+    assert_eq!(counter.ids(&cache), "disk2");
+}
+
+#[test]
+fn test_failures_are_not_cached() {
+    let cache = InventoryCache::new(Duration::from_secs(90));
+    let counter = Counter::new();
+
+    let failed = cache.disks(|| bail!("lsblk failed"));
+    assert_eq!(failed.unwrap_err().to_string(), "lsblk failed");
+
+    assert_eq!(counter.ids(&cache), "disk1");
+    assert!(cache.disks(|| bail!("not called")).is_ok());
+}
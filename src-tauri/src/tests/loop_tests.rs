@@ -0,0 +1,570 @@
+// src/tests/loop_tests.rs
+// Enumerating, mounting, unmounting and taking offline a partitioned disk
+// image end to end.
+//
+// As root the image is attached to a loop device and mounted for real: the
+// fake tree describes the loop device as a USB disk, since loop devices are
+// not listed as disks, and points at the live /dev and /proc. Otherwise the
+// tree's device nodes are copies of the image and `Kernel` keeps the tree's
+// mountinfo the way the kernel would.
+use super::disk_image::{DiskImage, LoopDevice, SECTOR};
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::busy::{diagnose, find_blockers};
+use crate::disk_operations::devnode::partition_name;
+use crate::disk_operations::mount::{mount_partition, MountActions};
+use crate::disk_operations::mountinfo::{read_mountinfo, MountEntry};
+use crate::disk_operations::offline::{
+    bring_online, eject, take_offline, OfflineActions, SystemActions,
+};
+use crate::disk_operations::sysfs::{enumerate_disks, SysfsRoot};
+use crate::disk_operations::unmount::{unmount_mount_point, UnmountActions};
+use crate::structs::{DiskInfo, DiskType, MountOptions, OfflineStep, StepStatus, UnmountMode};
+use anyhow::{bail, Context, Result};
+use std::ffi::CString;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+const USB_PATH: &str = "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+
+const ROOT_MOUNT: &str = "22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw";
+
+/// A 64 MiB image with ALPHA and BETA, presented as a USB disk
+struct Rig {
+    fake: FakeSysfs,
+    loop_device: Option<LoopDevice>,
+    image: DiskImage,
+    disk: String,
+}
+
+impl Rig {
+    fn new(name: &str) -> Self {
+        let image = DiskImage::new(name, 64, &[("ALPHA", 16), ("BETA", 32)]);
+        let loop_device = LoopDevice::attach(&image);
+        let fake = FakeSysfs::new(&format!("loop-{name}"));
+        let disk = loop_device
+            .as_ref()
+            .map_or_else(|| "sdx".to_string(), |device| device.name.clone());
+        let rig = Self {
+            fake,
+            loop_device,
+            image,
+            disk,
+        };
+        rig.build_tree();
+        rig
+    }
+
+    fn build_tree(&self) {
+        let fake = &self.fake;
+        fake.add_disk(&self.disk, &self.dev(0), USB_PATH, self.image.sectors());
+        fake.write(&format!("sys/devices/{USB_PATH}/state"), "running");
+        fake.set_disk_attr(&self.disk, "removable", "1");
+        for number in 1..=self.image.partition_count() {
+            let sectors = self.image.partitions()[number as usize - 1].sectors;
+            let (name, dev) = (self.partition(number), self.dev(number));
+            fake.add_partition(&self.disk, &name, number, &dev, sectors);
+        }
+        if self.loop_device.is_some() {
+            for number in 0..=self.image.partition_count() {
+                let name = self.name(number);
+                fake.link(&format!("dev/{name}"), Path::new("/dev").join(&name));
+            }
+            fake.link("proc", "/proc");
+        } else {
+            fake.link(&format!("dev/{}", self.disk), self.image.path());
+            for number in 1..=self.image.partition_count() {
+                let node = fake.path(&format!("dev/{}", self.partition(number)));
+                self.image.extract(number, &node);
+            }
+            fake.set_mountinfo(ROOT_MOUNT);
+        }
+    }
+
+    fn root(&self) -> SysfsRoot {
+        self.fake.sysfs_root()
+    }
+
+    fn is_real(&self) -> bool {
+        self.loop_device.is_some()
+    }
+
+    fn partition(&self, number: u32) -> String {
+        partition_name(&self.disk, number)
+    }
+
+    /// The disk for 0, otherwise partition `number`
+    fn name(&self, number: u32) -> String {
+        match number {
+            0 => self.disk.clone(),
+            _ => self.partition(number),
+        }
+    }
+
+    fn dev(&self, number: u32) -> String {
+        match self.loop_device {
+            Some(_) => LoopDevice::dev(&self.name(number)),
+            None => format!("8:{}", 96 + number),
+        }
+    }
+
+    fn state(&self) -> String {
+        let path = self.fake.path(&format!("sys/devices/{USB_PATH}/state"));
+        std::fs::read_to_string(path).unwrap().trim().to_string()
+    }
+
+    fn disks(&self) -> Vec<DiskInfo> {
+        enumerate_disks(&self.root()).unwrap()
+    }
+
+    fn mounts(&self) -> Vec<MountEntry> {
+        read_mountinfo(&self.root().mountinfo_path()).unwrap()
+    }
+
+    /// Mount points of partition `number`, as enumerated
+    fn mount_points(&self, number: u32) -> Vec<String> {
+        let disks = self.disks();
+        let Some(disk) = disks.iter().find(|d| d.id == self.disk) else {
+            return vec![];
+        };
+        disk.partitions
+            .iter()
+            .filter(|p| p.partition_number == number)
+            .flat_map(|p| p.mount_points.iter().map(|m| m.path.clone()))
+            .collect()
+    }
+
+    /// An empty directory to mount on, named after the partition's label
+    fn target(&self, number: u32) -> String {
+        let label = &self.image.partitions()[number as usize - 1].label;
+        let dir = self.fake.path(&format!("mnt/{label}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().into_owned()
+    }
+
+    /// Mount partition `number` on its target
+    fn mount(&self, number: u32) -> String {
+        let options = MountOptions {
+            target: Some(self.target(number)),
+            ..MountOptions::default()
+        };
+        let mut kernel = Kernel { rig: self };
+        mount_partition(&self.root(), &self.disk, number, &options, &mut kernel).unwrap()
+    }
+
+    /// Keep a file open below `mount_point` until the hold is dropped
+    fn hold(&self, mount_point: &str) -> Hold {
+        let path = format!("{mount_point}/notes.txt");
+        if self.is_real() {
+            return Hold {
+                _file: Some(File::create(path).unwrap()),
+                process: None,
+            };
+        }
+        self.fake.add_process(4242, &["vim"], &[("fd/3", &path)]);
+        Hold {
+            _file: None,
+            process: Some(self.fake.path("proc/4242")),
+        }
+    }
+}
+
+impl Rig {
+    /// Bind-mount `dir` of the filesystem mounted at `mount_point` on a new
+    /// target, which then shows only that directory
+    fn bind(&self, mount_point: &str, dir: &str) -> String {
+        let target = self.fake.path(&format!("mnt/{dir}"));
+        std::fs::create_dir_all(&target).unwrap();
+        let target = target.to_string_lossy().into_owned();
+        if self.is_real() {
+            let source = CString::new(format!("{mount_point}/{dir}")).unwrap();
+            let c_target = CString::new(target.as_str()).unwrap();
+            let res = unsafe {
+                libc::mount(
+                    source.as_ptr(),
+                    c_target.as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_BIND,
+                    std::ptr::null(),
+                )
+            };
+            assert_eq!(res, 0, "{}", std::io::Error::last_os_error());
+            return target;
+        }
+        let mounts = self.mounts();
+        let mount = mounts
+            .iter()
+            .find(|m| m.mount_point == mount_point)
+            .unwrap();
+        let kernel = Kernel { rig: self };
+        kernel.add_mount(
+            &mount.source,
+            &format!("/{dir}"),
+            &target,
+            &mount.fs_type,
+            "rw",
+        );
+        target
+    }
+}
+
+impl Drop for Rig {
+    fn drop(&mut self) {
+        // Leftover mounts would keep the loop device busy
+        if self.is_real() {
+            let targets = self.fake.path("mnt");
+            for mount in self.mounts() {
+                if Path::new(&mount.mount_point).starts_with(&targets) {
+                    let _ = UnmountActions::umount(
+                        &mut SystemActions,
+                        &mount.mount_point,
+                        libc::MNT_DETACH,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// An open file on a loop device, or a fake process holding one
+struct Hold {
+    _file: Option<File>,
+    process: Option<PathBuf>,
+}
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.process {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// The system calls on a loop device, or their effect on the tree otherwise.
+/// udisks is never involved: a device it would unmount is unmounted
+/// directly, and powering off detaches the loop device.
+struct Kernel<'a> {
+    rig: &'a Rig,
+}
+
+impl Kernel<'_> {
+    fn write_mounts(&self, mounts: &[String]) {
+        self.rig.fake.set_mountinfo(&mounts.join("\n"));
+    }
+
+    fn mount_lines(&self) -> Vec<String> {
+        let path = self.rig.root().mountinfo_path();
+        let content = std::fs::read_to_string(path).unwrap();
+        content.lines().map(ToString::to_string).collect()
+    }
+
+    /// A mount of `root`, a directory of the filesystem on `source`
+    fn add_mount(&self, source: &str, root: &str, target: &str, fs_type: &str, options: &str) {
+        let name = source.trim_start_matches("/dev/");
+        let number = (0..=self.rig.image.partition_count())
+            .find(|n| self.rig.name(*n) == name)
+            .unwrap();
+        let mut lines = self.mount_lines();
+        lines.push(format!(
+            "{} 22 {} {root} {target} {options},relatime - {fs_type} {source} {options}",
+            100 + lines.len(),
+            self.rig.dev(number)
+        ));
+        self.write_mounts(&lines);
+    }
+}
+
+impl MountActions for Kernel<'_> {
+    fn udisks_mount(&mut self, device: &str, _: Option<&str>, _: &[String]) -> Result<String> {
+        bail!("No udisks here to mount {device}, give a target")
+    }
+
+    fn mount(
+        &mut self,
+        device: &str,
+        target: &str,
+        fs_type: &str,
+        options: &[String],
+    ) -> Result<()> {
+        if self.rig.is_real() {
+            return SystemActions.mount(device, target, fs_type, options);
+        }
+        let mode = if options.iter().any(|o| o == "ro") {
+            "ro"
+        } else {
+            "rw"
+        };
+        self.add_mount(device, "/", target, fs_type, mode);
+        Ok(())
+    }
+}
+
+impl UnmountActions for Kernel<'_> {
+    fn udisks_unmount(&mut self, device: &str) -> Result<()> {
+        let mounts = self.rig.mounts();
+        let Some(mount) = mounts.iter().rev().find(|m| m.source == device) else {
+            bail!("{device} is not mounted");
+        };
+        self.umount(&mount.mount_point, 0)
+    }
+
+    fn umount(&mut self, mount_point: &str, flags: libc::c_int) -> Result<()> {
+        if self.rig.is_real() {
+            return UnmountActions::umount(&mut SystemActions, mount_point, flags);
+        }
+        let busy = !find_blockers(&self.rig.root(), &[mount_point.to_string()]).is_empty();
+        if busy && flags == 0 {
+            return Err(std::io::Error::from_raw_os_error(libc::EBUSY))
+                .with_context(|| format!("Failed to unmount {mount_point}"));
+        }
+        let mut lines = self.mount_lines();
+        let at = format!(" {mount_point} ");
+        let Some(index) = lines.iter().rposition(|line| line.contains(&at)) else {
+            bail!("{mount_point} is not mounted");
+        };
+        lines.remove(index);
+        self.write_mounts(&lines);
+        Ok(())
+    }
+
+    fn remount_read_only(&mut self, mount: &MountEntry) -> Result<()> {
+        if self.rig.is_real() {
+            return SystemActions.remount_read_only(mount);
+        }
+        UnmountActions::umount(self, &mount.mount_point, libc::MNT_DETACH)?;
+        let mount_point = &mount.mount_point;
+        self.add_mount(
+            &mount.source,
+            &mount.root,
+            mount_point,
+            &mount.fs_type,
+            "ro",
+        );
+        Ok(())
+    }
+}
+
+impl OfflineActions for Kernel<'_> {
+    fn unmount(&mut self, mount: &MountEntry) -> Result<()> {
+        UnmountActions::umount(self, &mount.mount_point, 0)
+    }
+
+    fn remount(&mut self, mount: &MountEntry) -> Result<()> {
+        if self.rig.is_real() {
+            return SystemActions.remount(mount);
+        }
+        let mount_point = &mount.mount_point;
+        self.add_mount(
+            &mount.source,
+            &mount.root,
+            mount_point,
+            &mount.fs_type,
+            "rw",
+        );
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        SystemActions.sync()
+    }
+
+    fn flush_buffers(&mut self, node: &Path) -> Result<()> {
+        if self.rig.is_real() {
+            return SystemActions.flush_buffers(node);
+        }
+        File::open(node).with_context(|| format!("Failed to open {}", node.display()))?;
+        Ok(())
+    }
+
+    fn write_attr(&mut self, path: &Path, value: &str) -> Result<()> {
+        SystemActions.write_attr(path, value)
+    }
+
+    fn power_off(&mut self, node: &Path) -> Result<()> {
+        if let Some(device) = &self.rig.loop_device {
+            if !device.detach() {
+                bail!("Failed to detach {}", node.display());
+            }
+        }
+        let rig = self.rig;
+        rig.fake.remove_disk(&rig.disk, &rig.dev(0), USB_PATH);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_image_is_enumerated_as_a_disk() {
+    let rig = Rig::new("enumerate");
+
+    let disks = rig.disks();
+
+    let disk = disks.iter().find(|d| d.id == rig.disk).unwrap();
+    assert_eq!(disk.size_bytes, 64 * 1024 * 1024);
+    assert_eq!(disk.disk_type, DiskType::USBFlash);
+    assert!(disk.protection.is_empty());
+    let partitions: Vec<(u32, u64, Option<&str>, Option<&str>)> = disk
+        .partitions
+        .iter()
+        .map(|p| {
+            (
+                p.partition_number,
+                p.size_bytes,
+                p.fs_type.as_deref(),
+                p.label.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        partitions,
+        vec![
+            (1, 16 * 1024 * 1024, Some("ext4"), Some("ALPHA")),
+            (2, 32 * 1024 * 1024, Some("ext4"), Some("BETA")),
+        ]
+    );
+    assert_eq!(rig.image.partitions()[0].start * SECTOR, 1024 * 1024);
+}
+
+#[test]
+fn test_mount_then_unmount() {
+    let rig = Rig::new("mount");
+
+    let target = rig.mount(1);
+
+    assert_eq!(rig.mount_points(1), vec![target.clone()]);
+    if rig.is_real() {
+        std::fs::write(format!("{target}/hello.txt"), "hello").unwrap();
+    }
+    let mut kernel = Kernel { rig: &rig };
+    let results =
+        unmount_mount_point(&rig.root(), &target, UnmountMode::Normal, &mut kernel).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].status, StepStatus::Done, "{:?}", results[0]);
+    assert!(rig.mount_points(1).is_empty());
+
+    // The file written went to the partition, not the directory under it
+    if rig.is_real() {
+        assert!(!Path::new(&target).join("hello.txt").exists());
+        assert_eq!(rig.mount(1), target);
+        let written = std::fs::read_to_string(format!("{target}/hello.txt")).unwrap();
+        assert_eq!(written, "hello");
+    }
+}
+
+#[test]
+fn test_busy_mount_needs_a_lazy_unmount() {
+    let rig = Rig::new("busy");
+    let target = rig.mount(2);
+    let _hold = rig.hold(&target);
+    let mut kernel = Kernel { rig: &rig };
+
+    let results =
+        unmount_mount_point(&rig.root(), &target, UnmountMode::Normal, &mut kernel).unwrap();
+
+    assert_eq!(results[0].status, StepStatus::Failed);
+    assert!(results[0].message.as_deref().unwrap().contains("busy"));
+    let report = diagnose(&rig.root(), &target).unwrap();
+    assert_eq!(report.mount_points, vec![target.clone()]);
+    assert!(!report.processes.is_empty());
+
+    let results =
+        unmount_mount_point(&rig.root(), &target, UnmountMode::Lazy, &mut kernel).unwrap();
+    assert_eq!(results[0].status, StepStatus::Done);
+    assert!(rig.mount_points(2).is_empty());
+}
+
+#[test]
+fn test_offline_unmounts_and_flushes_first() {
+    let rig = Rig::new("offline");
+    rig.mount(1);
+    rig.mount(2);
+    let mut kernel = Kernel { rig: &rig };
+
+    let report = take_offline(&rig.root(), &rig.disk, &mut kernel).unwrap();
+
+    assert!(report.completed, "{report:?}");
+    let steps: Vec<OfflineStep> = report.steps.iter().map(|s| s.step).collect();
+    assert_eq!(
+        steps,
+        vec![
+            OfflineStep::Unmount,
+            OfflineStep::Unmount,
+            OfflineStep::Sync,
+            OfflineStep::FlushBuffers,
+            OfflineStep::FlushBuffers,
+            OfflineStep::FlushBuffers,
+            OfflineStep::WriteState,
+            OfflineStep::VerifyState,
+        ]
+    );
+    assert_eq!(rig.state(), "offline");
+    assert!(rig.mount_points(1).is_empty());
+    assert!(rig.mount_points(2).is_empty());
+
+    bring_online(&rig.root(), &rig.disk, &mut kernel).unwrap();
+    assert_eq!(rig.state(), "running");
+}
+
+#[test]
+fn test_busy_disk_stays_online_and_mounted() {
+    let rig = Rig::new("offline-busy");
+    let alpha = rig.mount(1);
+    let beta = rig.mount(2);
+    let _hold = rig.hold(&beta);
+    let mut kernel = Kernel { rig: &rig };
+
+    let report = take_offline(&rig.root(), &rig.disk, &mut kernel).unwrap();
+
+    assert!(!report.completed);
+    assert!(!report.blockers.is_empty());
+    assert_eq!(report.steps.last().unwrap().status, StepStatus::Failed);
+    assert_eq!(rig.state(), "running");
+    assert_eq!(rig.mount_points(1), vec![alpha]);
+    assert_eq!(rig.mount_points(2), vec![beta]);
+}
+
+#[test]
+fn test_rolled_back_bind_mount_shows_the_same_directory() {
+    let rig = Rig::new("offline-bind");
+    let beta = rig.mount(2);
+    let alpha = rig.mount(1);
+    if rig.is_real() {
+        std::fs::create_dir(format!("{alpha}/docs")).unwrap();
+        std::fs::write(format!("{alpha}/docs/readme.txt"), "read me").unwrap();
+    }
+    let docs = rig.bind(&alpha, "docs");
+    // The bind and ALPHA come off before BETA refuses
+    let _hold = rig.hold(&beta);
+    let mut kernel = Kernel { rig: &rig };
+
+    let report = take_offline(&rig.root(), &rig.disk, &mut kernel).unwrap();
+
+    assert!(!report.completed);
+    let rolled_back: Vec<&str> = report
+        .steps
+        .iter()
+        .filter(|s| s.status == StepStatus::RolledBack)
+        .map(|s| s.target.as_str())
+        .collect();
+    assert_eq!(rolled_back, vec![docs.as_str(), alpha.as_str()]);
+    let mounts = rig.mounts();
+    let bind = mounts.iter().find(|m| m.mount_point == docs).unwrap();
+    assert_eq!(bind.root, "/docs");
+    if rig.is_real() {
+        let readme = std::fs::read_to_string(format!("{docs}/readme.txt")).unwrap();
+        assert_eq!(readme, "read me");
+    }
+}
+
+#[test]
+fn test_eject_removes_the_disk() {
+    let rig = Rig::new("eject");
+    rig.mount(1);
+    let mut kernel = Kernel { rig: &rig };
+
+    let report = eject(&rig.root(), &rig.disk, &mut kernel).unwrap();
+
+    assert!(report.completed, "{report:?}");
+    assert_eq!(report.steps.last().unwrap().step, OfflineStep::VerifyState);
+    assert!(rig.disks().iter().all(|d| d.id != rig.disk));
+    let source = format!("/dev/{}", rig.partition(1));
+    assert!(rig.mounts().iter().all(|m| m.source != source));
+}
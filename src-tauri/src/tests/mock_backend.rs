@@ -0,0 +1,355 @@
+// src/tests/mock_backend.rs
+// An in-memory DiskBackend for testing the commands. Operations change the
+// disks it holds the way the real backends would; failures are set up per
+// operation, and mount points can be held busy by a process.
+use crate::disk_operations::backend::DiskBackend;
+use crate::structs::{
+    BlockingProcess, BusyReport, DiskError, DiskInfo, ErrorCode, MountOptions, MountPoint,
+    MountedPartition, OfflineReport, OfflineStep, ProtectionReason, RescanReport, StepReport,
+    StepStatus, SystemInfo, UnmountMode,
+};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Default)]
+pub struct MockState {
+    pub disks: Vec<DiskInfo>,
+    /// Ejected disks, back after a rescan
+    pub ejected: Vec<DiskInfo>,
+    /// Operations in order, e.g. "offline sdb" or "unmount /media/sdb1 lazy"
+    pub calls: Vec<String>,
+    pub enumerations: usize,
+    /// Operations that fail, by the first word of their call
+    pub failures: BTreeMap<&'static str, DiskError>,
+    /// Mount points that only a lazy or forced unmount gets past
+    pub busy: BTreeMap<String, BlockingProcess>,
+    /// Mounts from devices outside the inventory, such as / on an LVM
+    /// volume, with the reasons they are protected
+    pub stacked: BTreeMap<String, Vec<ProtectionReason>>,
+}
+
+/// Clones share their state, so a test can keep one after handing the
+/// backend over
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBackend {
+    pub fn new(disks: Vec<DiskInfo>) -> Self {
+        let mock = Self::default();
+        mock.state().disks = disks;
+        mock
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.state().calls.clone()
+    }
+
+    pub fn disk(&self, id: &str) -> Option<DiskInfo> {
+        self.state().disks.iter().find(|d| d.id == id).cloned()
+    }
+
+    /// Make `operation` ("offline", "mount", ...) fail with `error`
+    pub fn fail(&self, operation: &'static str, error: DiskError) {
+        self.state().failures.insert(operation, error);
+    }
+
+    /// Keep `mount_point` busy, held open by `command`
+    pub fn hold(&self, mount_point: &str, command: &str, pid: u32) {
+        let process = BlockingProcess {
+            pid,
+            command: command.to_string(),
+            user: "user".to_string(),
+            paths: vec![mount_point.to_string()],
+        };
+        self.state().busy.insert(mount_point.to_string(), process);
+    }
+
+    /// Mount `mount_point` from a device no disk in the inventory stands
+    /// for, protected for `reasons`
+    pub fn mount_stacked(&self, mount_point: &str, reasons: Vec<ProtectionReason>) {
+        self.state()
+            .stacked
+            .insert(mount_point.to_string(), reasons);
+    }
+
+    /// Record `call` and fail it when its operation was set up to
+    fn begin(&self, call: String) -> Result<MutexGuard<'_, MockState>> {
+        let mut state = self.state();
+        let operation = call.split(' ').next().unwrap_or_default();
+        if let Some(error) = state.failures.get(operation) {
+            let error = error.clone();
+            state.calls.push(call);
+            return Err(error.into());
+        }
+        state.calls.push(call);
+        Ok(state)
+    }
+}
+
+fn not_found(disk_id: &str) -> anyhow::Error {
+    DiskError::new(ErrorCode::NotFound, format!("Disk {disk_id} not found"))
+        .on_disk(disk_id)
+        .into()
+}
+
+fn find_disk<'a>(state: &'a mut MockState, disk_id: &str) -> Result<&'a mut DiskInfo> {
+    state
+        .disks
+        .iter_mut()
+        .find(|d| d.id == disk_id)
+        .ok_or_else(|| not_found(disk_id))
+}
+
+fn mounts_of(disk: &DiskInfo) -> Vec<String> {
+    disk.partitions
+        .iter()
+        .flat_map(|p| p.mount_points.iter().map(|m| m.path.clone()))
+        .collect()
+}
+
+fn step(step: OfflineStep, target: &str, status: StepStatus, message: Option<&str>) -> StepReport {
+    StepReport {
+        step,
+        target: target.to_string(),
+        status,
+        message: message.map(ToString::to_string),
+    }
+}
+
+/// Unmount every filesystem of `disk_id`, then do `last`, the way offline
+/// and eject go. A busy filesystem stops the sequence and rolls back the
+/// unmounts before it.
+fn take_down(state: &mut MockState, disk_id: &str, last: OfflineStep) -> Result<OfflineReport> {
+    let MockState { disks, busy, .. } = state;
+    let disk = disks
+        .iter_mut()
+        .find(|d| d.id == disk_id)
+        .ok_or_else(|| not_found(disk_id))?;
+    let mut report = OfflineReport {
+        disk_id: disk_id.to_string(),
+        method: None,
+        steps: vec![],
+        completed: false,
+        bring_back: vec![],
+        blockers: vec![],
+    };
+    for mount in mounts_of(disk) {
+        if let Some(process) = busy.get(&mount) {
+            for done in &mut report.steps {
+                done.status = StepStatus::RolledBack;
+            }
+            let busy = step(
+                OfflineStep::Unmount,
+                &mount,
+                StepStatus::Failed,
+                Some("target is busy"),
+            );
+            report.steps.push(busy);
+            report.blockers.push(process.clone());
+            return Ok(report);
+        }
+        let done = step(OfflineStep::Unmount, &mount, StepStatus::Done, None);
+        report.steps.push(done);
+    }
+    for partition in &mut disk.partitions {
+        partition.mount_points.clear();
+    }
+    report
+        .steps
+        .push(step(last, disk_id, StepStatus::Done, None));
+    report.completed = true;
+    Ok(report)
+}
+
+/// Unmount `mount_point` wherever it is, as a step report
+fn unmount_one(state: &mut MockState, mount_point: &str, mode: UnmountMode) -> StepReport {
+    let kind = match mode {
+        UnmountMode::RemountReadOnly => OfflineStep::RemountReadOnly,
+        _ => OfflineStep::Unmount,
+    };
+    if mode == UnmountMode::Normal && state.busy.contains_key(mount_point) {
+        return step(
+            kind,
+            mount_point,
+            StepStatus::Failed,
+            Some("target is busy"),
+        );
+    }
+    for partition in state.disks.iter_mut().flat_map(|d| d.partitions.iter_mut()) {
+        let mounts = &mut partition.mount_points;
+        if mode == UnmountMode::RemountReadOnly {
+            mounts
+                .iter_mut()
+                .filter(|m| m.path == mount_point)
+                .for_each(|m| m.read_only = true);
+        } else {
+            mounts.retain(|m| m.path != mount_point);
+        }
+    }
+    state.busy.remove(mount_point);
+    step(kind, mount_point, StepStatus::Done, None)
+}
+
+impl DiskBackend for MockBackend {
+    fn enumerate_disks(&self) -> Result<Vec<DiskInfo>> {
+        let mut state = self.state();
+        if let Some(error) = state.failures.get("enumerate") {
+            return Err(error.clone().into());
+        }
+        state.enumerations += 1;
+        Ok(state.disks.clone())
+    }
+
+    fn system_info(&self, disks: &[DiskInfo]) -> Result<SystemInfo> {
+        Ok(SystemInfo {
+            os_name: "Mock".to_string(),
+            os_version: "1".to_string(),
+            is_admin: true,
+            total_disks: disks.len(),
+            total_capacity_bytes: disks.iter().map(|d| d.size_bytes).sum(),
+            system_disk_id: disks
+                .iter()
+                .find(|d| d.is_system_disk)
+                .map(|d| d.id.clone()),
+        })
+    }
+
+    fn set_disk_online(&self, disk_id: &str) -> Result<()> {
+        let mut state = self.begin(format!("online {disk_id}"))?;
+        find_disk(&mut state, disk_id)?.is_online = true;
+        Ok(())
+    }
+
+    fn set_disk_offline(&self, disk_id: &str) -> Result<OfflineReport> {
+        let mut state = self.begin(format!("offline {disk_id}"))?;
+        let report = take_down(&mut state, disk_id, OfflineStep::WriteState)?;
+        if report.completed {
+            find_disk(&mut state, disk_id)?.is_online = false;
+        }
+        Ok(report)
+    }
+
+    fn eject_disk(&self, disk_id: &str) -> Result<OfflineReport> {
+        let mut state = self.begin(format!("eject {disk_id}"))?;
+        let report = take_down(&mut state, disk_id, OfflineStep::PowerOff)?;
+        if report.completed {
+            let index = state.disks.iter().position(|d| d.id == disk_id);
+            let disk = state.disks.remove(index.unwrap_or_default());
+            state.ejected.push(disk);
+        }
+        Ok(report)
+    }
+
+    fn rescan_storage(&self) -> Result<RescanReport> {
+        let mut state = self.begin("rescan".to_string())?;
+        let reappeared = std::mem::take(&mut state.ejected);
+        state.disks.extend(reappeared.iter().cloned());
+        Ok(RescanReport {
+            scanned: vec![],
+            errors: vec![],
+            reappeared,
+        })
+    }
+
+    fn mount_partition(
+        &self,
+        disk_id: &str,
+        partition_number: u32,
+        letter: Option<char>,
+        options: &MountOptions,
+    ) -> Result<MountedPartition> {
+        let mut state = self.begin(format!("mount {disk_id} {partition_number}"))?;
+        let disk = find_disk(&mut state, disk_id)?;
+        let Some(partition) = disk
+            .partitions
+            .iter_mut()
+            .find(|p| p.partition_number == partition_number)
+        else {
+            return Err(DiskError::new(
+                ErrorCode::NotFound,
+                format!("Partition {partition_number} of disk {disk_id} not found"),
+            )
+            .into());
+        };
+        if !partition.mount_points.is_empty() {
+            return Err(DiskError::new(
+                ErrorCode::AlreadyMounted,
+                format!("{} is already mounted", partition.partition_id),
+            )
+            .into());
+        }
+        let path = options
+            .target
+            .clone()
+            .unwrap_or_else(|| format!("/media/{}", partition.partition_id));
+        partition.mount_points.push(MountPoint {
+            path: path.clone(),
+            read_only: options.read_only,
+            ..MountPoint::default()
+        });
+        Ok(MountedPartition {
+            letter,
+            mount_point: Some(path),
+        })
+    }
+
+    fn unmount_partition(&self, target: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+        let mut state = self.begin(format!("unmount {target} {mode:?}").to_lowercase())?;
+        let mounted = state
+            .disks
+            .iter()
+            .any(|d| mounts_of(d).iter().any(|m| m == target));
+        if state.stacked.remove(target).is_some() {
+            return Ok(vec![step(
+                OfflineStep::Unmount,
+                target,
+                StepStatus::Done,
+                None,
+            )]);
+        }
+        if !mounted {
+            return Err(DiskError::new(
+                ErrorCode::NotMounted,
+                format!("{target} is not a mount point"),
+            )
+            .into());
+        }
+        Ok(vec![unmount_one(&mut state, target, mode)])
+    }
+
+    fn unmount_disk(&self, disk_id: &str, mode: UnmountMode) -> Result<Vec<StepReport>> {
+        let mut state = self.begin(format!("unmount_disk {disk_id} {mode:?}").to_lowercase())?;
+        let mounts = mounts_of(find_disk(&mut state, disk_id)?);
+        Ok(mounts
+            .iter()
+            .map(|mount| unmount_one(&mut state, mount, mode))
+            .collect())
+    }
+
+    fn diagnose_busy(&self, target: &str) -> Result<BusyReport> {
+        let state = self.state();
+        Ok(BusyReport {
+            mount_points: vec![target.to_string()],
+            processes: state.busy.get(target).cloned().into_iter().collect(),
+            swap: vec![],
+        })
+    }
+
+    fn target_protection(&self, target: &str) -> Result<Vec<ProtectionReason>> {
+        match self.state().stacked.get(target) {
+            Some(reasons) => Ok(reasons.clone()),
+            None => Err(DiskError::new(
+                ErrorCode::NotMounted,
+                format!("{target} is neither a mount point nor a device"),
+            )
+            .into()),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+// src/tests/mock_udisks.rs
+// A private dbus-daemon and a UDisks2 stand-in serving block devices,
+// filesystems and drives on it, recording every call
+// zbus interface methods take their arguments by value, used or not
+#![allow(clippy::needless_pass_by_value, clippy::used_underscore_binding)]
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+const BUS_CONFIG: &str = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path=SOCKET</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#;
+
+/// A dbus-daemon of its own, stopped when dropped
+pub struct PrivateBus {
+    dir: PathBuf,
+    daemon: Child,
+    pub address: String,
+}
+
+impl PrivateBus {
+    /// None when dbus-daemon is not installed
+    pub fn start(name: &str) -> Option<Self> {
+        let dir =
+            std::env::temp_dir().join(format!("diskofflaner-bus-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("bus");
+        let config = dir.join("bus.conf");
+        std::fs::write(
+            &config,
+            BUS_CONFIG.replace("SOCKET", &socket.to_string_lossy()),
+        )
+        .unwrap();
+
+        let mut daemon = match Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config.display()))
+            .arg("--nofork")
+            .arg("--print-address=1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(daemon) => daemon,
+            Err(e) => {
+                eprintln!("Skipping, cannot start dbus-daemon: {e}");
+                return None;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Some(Self {
+            dir,
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    pub fn connect(&self) -> Connection {
+        Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[derive(Default)]
+pub struct MockState {
+    /// Calls in order, e.g. "Mount sdb1 fstype=vfat options=ro"
+    pub calls: Vec<String>,
+    pub mounted: BTreeSet<String>,
+    /// Devices whose unmount fails with `DeviceBusy`
+    pub busy: BTreeSet<String>,
+    /// Deny every call with `NotAuthorized`
+    pub deny: bool,
+}
+
+type Shared = Arc<Mutex<MockState>>;
+
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.freedesktop.UDisks2.Error")]
+enum MockError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotAuthorized(String),
+    DeviceBusy(String),
+    AlreadyMounted(String),
+    NotMounted(String),
+}
+
+fn option(options: &HashMap<String, OwnedValue>, key: &str) -> String {
+    options
+        .get(key)
+        .and_then(|value| <&str>::try_from(&**value).ok())
+        .map(|value| format!(" {key}={value}"))
+        .unwrap_or_default()
+}
+
+fn check_allowed(state: &MockState) -> Result<(), MockError> {
+    if state.deny {
+        return Err(MockError::NotAuthorized(
+            "Not authorized to perform operation".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+struct Filesystem {
+    device: String,
+    state: Shared,
+}
+
+#[zbus::interface(name = "org.freedesktop.UDisks2.Filesystem")]
+impl Filesystem {
+    fn mount(&self, options: HashMap<String, OwnedValue>) -> Result<String, MockError> {
+        let mut state = self.state.lock().unwrap();
+        let call = format!(
+            "Mount {}{}{}",
+            self.device,
+            option(&options, "fstype"),
+            option(&options, "options")
+        );
+        state.calls.push(call);
+        check_allowed(&state)?;
+        if !state.mounted.insert(self.device.clone()) {
+            return Err(MockError::AlreadyMounted(format!(
+                "Device /dev/{} is already mounted",
+                self.device
+            )));
+        }
+        Ok(format!("/media/user/{}", self.device))
+    }
+
+    fn unmount(&self, _options: HashMap<String, OwnedValue>) -> Result<(), MockError> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(format!("Unmount {}", self.device));
+        check_allowed(&state)?;
+        if state.busy.contains(&self.device) {
+            return Err(MockError::DeviceBusy(format!(
+                "Error unmounting /dev/{}: target is busy",
+                self.device
+            )));
+        }
+        if !state.mounted.remove(&self.device) {
+            return Err(MockError::NotMounted(format!(
+                "Device /dev/{} is not mounted",
+                self.device
+            )));
+        }
+        Ok(())
+    }
+}
+
+struct Block {
+    drive: OwnedObjectPath,
+}
+
+#[zbus::interface(name = "org.freedesktop.UDisks2.Block")]
+impl Block {
+    #[zbus(property)]
+    fn drive(&self) -> OwnedObjectPath {
+        self.drive.clone()
+    }
+}
+
+struct Drive {
+    name: String,
+    state: Shared,
+}
+
+#[zbus::interface(name = "org.freedesktop.UDisks2.Drive")]
+impl Drive {
+    fn power_off(&self, _options: HashMap<String, OwnedValue>) -> Result<(), MockError> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(format!("PowerOff {}", self.name));
+        check_allowed(&state)
+    }
+}
+
+/// The stand-in service, owning org.freedesktop.UDisks2 on a private bus
+pub struct MockUDisks {
+    pub state: Shared,
+    connection: Connection,
+}
+
+impl MockUDisks {
+    pub fn start(bus: &PrivateBus) -> Self {
+        let connection = Builder::address(bus.address.as_str())
+            .unwrap()
+            .name("org.freedesktop.UDisks2")
+            .unwrap()
+            .serve_at("/org/freedesktop/UDisks2", zbus::fdo::ObjectManager)
+            .unwrap()
+            .build()
+            .unwrap();
+        Self {
+            state: Shared::default(),
+            connection,
+        }
+    }
+
+    pub fn add_drive(&self, name: &str) {
+        let drive = Drive {
+            name: name.to_string(),
+            state: self.state.clone(),
+        };
+        let path = format!("/org/freedesktop/UDisks2/drives/{name}");
+        self.connection.object_server().at(path, drive).unwrap();
+    }
+
+    /// Add a block device (by escaped name) on `drive`, with a filesystem
+    /// when `filesystem` is set
+    pub fn add_block(&self, name: &str, drive: Option<&str>, filesystem: bool) {
+        let path = format!("/org/freedesktop/UDisks2/block_devices/{name}");
+        let drive = drive.map_or_else(
+            || "/".to_string(),
+            |d| format!("/org/freedesktop/UDisks2/drives/{d}"),
+        );
+        let server = self.connection.object_server();
+        if filesystem {
+            let fs = Filesystem {
+                device: name.to_string(),
+                state: self.state.clone(),
+            };
+            server.at(path.as_str(), fs).unwrap();
+        }
+        let block = Block {
+            drive: OwnedObjectPath::try_from(drive).unwrap(),
+        };
+        server.at(path, block).unwrap();
+    }
+
+    pub fn remove_block(&self, name: &str) {
+        let path = format!("/org/freedesktop/UDisks2/block_devices/{name}");
+        self.connection
+            .object_server()
+            .remove::<Block, _>(path)
+            .unwrap();
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
@@ -3,7 +3,7 @@
 
 #[cfg(test)]
 mod structs_tests {
-    use crate::structs::{DiskInfo, DiskType, PartitionInfo};
+    use crate::structs::{DiskCapabilities, DiskInfo, DiskType, PartitionInfo, ProtectionReason};
 
     #[test]
     fn test_disk_type_default() {
@@ -39,6 +39,15 @@ mod structs_tests {
         let partition = PartitionInfo {
             partition_number: 1,
             size_bytes: 1024 * 1024 * 1024, // 1 GB
+            used_bytes: None,
+            mount_points: vec![],
+            usage: None,
+            fs_type: None,
+            label: None,
+            uuid: None,
+            part_uuid: None,
+            part_type: None,
+            part_flags: vec![],
             drive_letter: "C".to_string(),
             partition_id: "PART1".to_string(),
         };
@@ -57,8 +66,13 @@ mod structs_tests {
             size_bytes: 500 * 1024 * 1024 * 1024, // 500 GB
             is_online: true,
             is_system_disk: false,
+            protection: vec![],
             partitions: vec![],
             disk_type: DiskType::SSD,
+            serial_number: None,
+            health_percentage: None,
+            usage_percentage: None,
+            capabilities: DiskCapabilities::default(),
         };
 
         assert_eq!(disk.id, "0");
@@ -74,6 +88,15 @@ mod structs_tests {
         let partition1 = PartitionInfo {
             partition_number: 1,
             size_bytes: 100 * 1024 * 1024 * 1024,
+            used_bytes: None,
+            mount_points: vec![],
+            usage: None,
+            fs_type: None,
+            label: None,
+            uuid: None,
+            part_uuid: None,
+            part_type: None,
+            part_flags: vec![],
             drive_letter: "C".to_string(),
             partition_id: "P1".to_string(),
         };
@@ -81,6 +104,15 @@ mod structs_tests {
         let partition2 = PartitionInfo {
             partition_number: 2,
             size_bytes: 200 * 1024 * 1024 * 1024,
+            used_bytes: None,
+            mount_points: vec![],
+            usage: None,
+            fs_type: None,
+            label: None,
+            uuid: None,
+            part_uuid: None,
+            part_type: None,
+            part_flags: vec![],
             drive_letter: "D".to_string(),
             partition_id: "P2".to_string(),
         };
@@ -91,8 +123,13 @@ mod structs_tests {
             size_bytes: 300 * 1024 * 1024 * 1024,
             is_online: true,
             is_system_disk: true,
+            protection: vec![ProtectionReason::SystemDisk],
             partitions: vec![partition1, partition2],
             disk_type: DiskType::HDD,
+            serial_number: None,
+            health_percentage: None,
+            usage_percentage: None,
+            capabilities: DiskCapabilities::default(),
         };
 
         assert_eq!(disk.partitions.len(), 2);
@@ -109,8 +146,13 @@ mod structs_tests {
             size_bytes: 1024,
             is_online: true,
             is_system_disk: false,
+            protection: vec![],
             partitions: vec![],
             disk_type: DiskType::USBFlash,
+            serial_number: None,
+            health_percentage: None,
+            usage_percentage: None,
+            capabilities: DiskCapabilities::default(),
         };
 
         let cloned = disk.clone();
@@ -120,3 +162,113 @@ mod structs_tests {
         assert_eq!(disk.disk_type, cloned.disk_type);
     }
 }
+
+#[cfg(test)]
+mod usage_tests {
+    use crate::structs::PartitionInfo;
+    use crate::utils::usage_percentage;
+
+    fn partition(size_bytes: u64, used_bytes: Option<u64>) -> PartitionInfo {
+        PartitionInfo {
+            partition_number: 1,
+            size_bytes,
+            used_bytes,
+            mount_points: vec![],
+            usage: None,
+            fs_type: None,
+            label: None,
+            uuid: None,
+            part_uuid: None,
+            part_type: None,
+            part_flags: vec![],
+            drive_letter: String::new(),
+            partition_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_usage_percentage_skips_unknown_partitions() {
+        let partitions = vec![
+            partition(100, Some(25)),
+            partition(300, Some(75)),
+            partition(1000, None),
+        ];
+        assert_eq!(usage_percentage(&partitions), Some(25.0));
+    }
+
+    #[test]
+    fn test_usage_percentage_unknown() {
+        assert_eq!(usage_percentage(&[]), None);
+        assert_eq!(usage_percentage(&[partition(100, None)]), None);
+        assert_eq!(usage_percentage(&[partition(0, Some(0))]), None);
+    }
+}
+
+#[cfg(test)]
+mod probe_tests;
+
+#[cfg(test)]
+mod partition_table_tests;
+
+#[cfg(test)]
+mod protection_tests;
+
+#[cfg(test)]
+mod inventory_tests;
+
+#[cfg(test)]
+mod builders;
+
+#[cfg(test)]
+mod mock_backend;
+
+#[cfg(test)]
+mod commands_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod fake_sysfs;
+
+#[cfg(all(test, target_os = "linux"))]
+mod disk_image;
+
+#[cfg(all(test, target_os = "linux"))]
+mod sysfs_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod lsblk_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod devnode_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod error_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod busy_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod hotplug_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod mock_udisks;
+
+#[cfg(all(test, target_os = "linux"))]
+mod mount_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod offline_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod rescan_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod unmount_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod udisks_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod loop_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod command_tests;
@@ -0,0 +1,254 @@
+// src/tests/mount_tests.rs
+// Mount option validation and where mounts go, with recorded side effects
+
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::mount::{mount_arguments, mount_partition, MountActions};
+use crate::structs::MountOptions;
+use anyhow::Result;
+
+const USB_PATH: &str = "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+
+const MOUNTINFO: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+40 22 8:18 / /mnt/backup rw shared:31 - ext4 /dev/sdb2 rw
+";
+
+fn build_tree(name: &str) -> FakeSysfs {
+    let fake = FakeSysfs::new(name);
+    fake.add_disk("sdb", "8:16", USB_PATH, 60_062_500);
+    fake.add_partition("sdb", "sdb1", 1, "8:17", 20_000_000);
+    fake.add_partition("sdb", "sdb2", 2, "8:18", 40_000_000);
+    fake.set_udev("8:17", &[("ID_FS_TYPE", "vfat")]);
+    fake.set_udev("8:18", &[("ID_FS_TYPE", "ext4")]);
+    fake.set_mountinfo(MOUNTINFO);
+    fake
+}
+
+#[derive(Default)]
+struct Recorder {
+    log: Vec<String>,
+}
+
+impl MountActions for Recorder {
+    fn udisks_mount(
+        &mut self,
+        device: &str,
+        fs_type: Option<&str>,
+        options: &[String],
+    ) -> Result<String> {
+        self.log.push(format!(
+            "udisks {device} {} [{}]",
+            fs_type.unwrap_or("auto"),
+            options.join(",")
+        ));
+        Ok("/media/user/STICK".to_string())
+    }
+
+    fn mount(
+        &mut self,
+        device: &str,
+        target: &str,
+        fs_type: &str,
+        options: &[String],
+    ) -> Result<()> {
+        self.log.push(format!(
+            "mount {device} {target} {fs_type} [{}]",
+            options.join(",")
+        ));
+        Ok(())
+    }
+}
+
+fn mount(fake: &FakeSysfs, number: u32, options: &MountOptions) -> Result<(String, Vec<String>)> {
+    let mut recorder = Recorder::default();
+    let mount_point = mount_partition(&fake.sysfs_root(), "sdb", number, options, &mut recorder)?;
+    Ok((mount_point, recorder.log))
+}
+
+fn error(fake: &FakeSysfs, number: u32, options: &MountOptions) -> String {
+    mount(fake, number, options).unwrap_err().to_string()
+}
+
+#[test]
+fn test_default_mount_goes_through_udisks() {
+    let fake = build_tree("mount-default");
+
+    let (mount_point, log) = mount(&fake, 1, &MountOptions::default()).unwrap();
+
+    assert_eq!(mount_point, "/media/user/STICK");
+    assert_eq!(log, vec!["udisks /dev/sdb1 auto []"]);
+}
+
+#[test]
+fn test_flags_and_owner_are_passed_on() {
+    let fake = build_tree("mount-flags");
+    let options = MountOptions {
+        read_only: true,
+        no_exec: true,
+        no_dev: true,
+        uid: Some(1000),
+        gid: Some(100),
+        fs_type: Some("vfat".to_string()),
+        ..MountOptions::default()
+    };
+
+    let (_, log) = mount(&fake, 1, &options).unwrap();
+
+    assert_eq!(
+        log,
+        vec!["udisks /dev/sdb1 vfat [ro,noexec,nodev,uid=1000,gid=100]"]
+    );
+}
+
+#[test]
+fn test_owner_needs_a_filesystem_without_one() {
+    let fake = build_tree("mount-owner");
+    let options = MountOptions {
+        uid: Some(1000),
+        ..MountOptions::default()
+    };
+
+    assert_eq!(
+        error(&fake, 2, &options),
+        "uid and gid only apply to FAT, exFAT and NTFS, not ext4"
+    );
+}
+
+#[test]
+fn test_unknown_filesystem_type_is_refused() {
+    let fake = build_tree("mount-fs-type");
+    let options = MountOptions {
+        fs_type: Some("ext4,rw".to_string()),
+        ..MountOptions::default()
+    };
+
+    assert_eq!(
+        error(&fake, 1, &options),
+        "Unsupported filesystem type ext4,rw"
+    );
+}
+
+#[test]
+fn test_target_is_created_and_mounted_directly() {
+    let fake = build_tree("mount-target");
+    let options = MountOptions {
+        target: Some("/mnt/stick/".to_string()),
+        no_suid: true,
+        ..MountOptions::default()
+    };
+
+    let (mount_point, log) = mount(&fake, 1, &options).unwrap();
+
+    assert_eq!(mount_point, "/mnt/stick");
+    assert_eq!(log, vec!["mount /dev/sdb1 /mnt/stick vfat [nosuid,nodev]"]);
+    assert!(fake.path("mnt/stick").is_dir());
+}
+
+#[test]
+fn test_direct_mounts_are_always_nosuid_and_nodev() {
+    let fake = build_tree("mount-target-defaults");
+    let options = MountOptions {
+        target: Some("/home/alice/stick".to_string()),
+        read_only: true,
+        ..MountOptions::default()
+    };
+
+    let (_, log) = mount(&fake, 1, &options).unwrap();
+
+    assert_eq!(
+        log,
+        vec!["mount /dev/sdb1 /home/alice/stick vfat [ro,nosuid,nodev]"]
+    );
+}
+
+#[test]
+fn test_bad_targets_are_refused() {
+    let fake = build_tree("mount-bad-target");
+    fake.write("srv/data/file", "");
+    let target = |path: &str| MountOptions {
+        target: Some(path.to_string()),
+        ..MountOptions::default()
+    };
+
+    assert_eq!(
+        error(&fake, 1, &target("mnt/stick")),
+        "Mount target mnt/stick must be an absolute path"
+    );
+    assert_eq!(
+        error(&fake, 1, &target("/mnt/../etc")),
+        "Mount target /mnt/../etc must not contain . or .."
+    );
+    assert_eq!(
+        error(&fake, 1, &target("/usr/")),
+        "Refusing to mount over /usr"
+    );
+    // Empty, but part of the system all the same
+    std::fs::create_dir_all(fake.path("usr/local/bin")).unwrap();
+    assert_eq!(
+        error(&fake, 1, &target("/usr/local/bin")),
+        "Refusing to mount over /usr/local/bin"
+    );
+    assert_eq!(
+        error(&fake, 1, &target("/mnt/backup")),
+        "/mnt/backup is already a mount point"
+    );
+    assert_eq!(
+        error(&fake, 1, &target("/srv/data")),
+        "Mount target /srv/data is not an empty directory"
+    );
+}
+
+#[test]
+fn test_symlinks_into_system_trees_are_refused() {
+    let fake = build_tree("mount-symlink-target");
+    std::fs::create_dir_all(fake.path("etc/ssl")).unwrap();
+    std::fs::create_dir_all(fake.path("mnt")).unwrap();
+    std::os::unix::fs::symlink("../etc", fake.path("mnt/x")).unwrap();
+    std::os::unix::fs::symlink("/etc", fake.path("mnt/host-etc")).unwrap();
+    std::os::unix::fs::symlink("backup", fake.path("mnt/again")).unwrap();
+    let target = |path: &str| MountOptions {
+        target: Some(path.to_string()),
+        ..MountOptions::default()
+    };
+
+    assert_eq!(
+        error(&fake, 1, &target("/mnt/x")),
+        "Refusing to mount over /etc"
+    );
+    assert_eq!(
+        error(&fake, 1, &target("/mnt/x/ssl/new")),
+        "Refusing to mount over /etc/ssl/new"
+    );
+    assert_eq!(
+        error(&fake, 1, &target("/mnt/host-etc")),
+        "Refusing to mount over /etc"
+    );
+    std::fs::create_dir_all(fake.path("mnt/backup")).unwrap();
+    assert_eq!(
+        error(&fake, 1, &target("/mnt/again")),
+        "/mnt/backup is already a mount point"
+    );
+}
+
+#[test]
+fn test_missing_partition_is_an_error() {
+    let fake = build_tree("mount-missing");
+
+    assert_eq!(
+        error(&fake, 7, &MountOptions::default()),
+        "Partition 7 of disk sdb not found"
+    );
+}
+
+#[test]
+fn test_mount_arguments_split_flags_from_data() {
+    let options: Vec<String> = ["ro", "nosuid", "uid=1000", "gid=100"]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    let (flags, data) = mount_arguments(&options);
+
+    assert_eq!(flags, libc::MS_RDONLY | libc::MS_NOSUID);
+    assert_eq!(data, "uid=1000,gid=100");
+}
@@ -0,0 +1,537 @@
+// src/tests/offline_tests.rs
+// Guarded offline sequence against a fake tree, with recorded side effects
+
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::mountinfo::{parse_mountinfo, MountEntry};
+use crate::disk_operations::offline::{
+    bring_online, eject, mount_flags, take_offline, with_offline_disks, OfflineActions,
+};
+use crate::disk_operations::offline_method::select;
+use crate::disk_operations::sysfs::enumerate_disks;
+use crate::structs::{BlockingProcess, OfflineMethod, OfflineStep, StepStatus, SysfsWrite};
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+const USB_PATH: &str = "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+const NVME_PATH: &str = "pci0000:00/0000:00:1d.0/0000:3d:00.0/nvme/nvme0";
+const VIRTIO_PATH: &str = "pci0000:00/0000:00:04.0/virtio1";
+const MMC_PATH: &str = "platform/fe320000.mmc/mmc_host/mmc0/mmc0:0001";
+
+const MOUNTINFO: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+40 22 8:17 / /media/stick rw,nosuid,nodev shared:30 - vfat /dev/sdb1 rw,fmask=0022
+41 40 8:18 / /media/stick/data rw,noexec shared:31 - ext4 /dev/sdb2 rw
+";
+
+fn build_tree(name: &str) -> FakeSysfs {
+    let fake = FakeSysfs::new(name);
+    fake.add_disk("sdb", "8:16", USB_PATH, 60_062_500);
+    fake.write(&format!("sys/devices/{USB_PATH}/state"), "running");
+    fake.add_partition("sdb", "sdb1", 1, "8:17", 20_000_000);
+    fake.add_partition("sdb", "sdb2", 2, "8:18", 40_000_000);
+    fake.set_mountinfo(MOUNTINFO);
+    fake
+}
+
+/// Records every action. `fail` makes the action whose description starts
+/// with it fail; `stuck_state` is what the kernel keeps reporting instead of
+/// the state written. Writing to a remove, delete or unbind attribute, or
+/// powering off, deletes `detaches` from the tree, as the kernel would.
+#[derive(Default)]
+struct Recorder {
+    log: Vec<String>,
+    fail: Option<&'static str>,
+    stuck_state: Option<&'static str>,
+    detaches: Option<PathBuf>,
+}
+
+impl Recorder {
+    fn act(&mut self, action: &str) -> Result<()> {
+        let failed = self.fail.is_some_and(|f| action.starts_with(f));
+        self.log.push(action.to_string());
+        if failed {
+            bail!("{action} failed");
+        }
+        Ok(())
+    }
+}
+
+impl OfflineActions for Recorder {
+    fn unmount(&mut self, mount: &MountEntry) -> Result<()> {
+        self.act(&format!("unmount {}", mount.mount_point))
+    }
+
+    fn remount(&mut self, mount: &MountEntry) -> Result<()> {
+        self.act(&format!("remount {}", mount.mount_point))
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.act("sync")
+    }
+
+    fn flush_buffers(&mut self, node: &Path) -> Result<()> {
+        let name = node.file_name().unwrap().to_string_lossy().into_owned();
+        self.act(&format!("flush {name}"))
+    }
+
+    fn write_attr(&mut self, path: &Path, value: &str) -> Result<()> {
+        let attr = path.file_name().unwrap().to_string_lossy().into_owned();
+        self.act(&format!("{attr} {value}"))?;
+        if matches!(attr.as_str(), "remove" | "delete" | "unbind") {
+            if let Some(detaches) = &self.detaches {
+                std::fs::remove_file(detaches)?;
+            }
+            return Ok(());
+        }
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, self.stuck_state.unwrap_or(value))?;
+        Ok(())
+    }
+
+    fn power_off(&mut self, node: &Path) -> Result<()> {
+        let name = node.file_name().unwrap().to_string_lossy().into_owned();
+        self.act(&format!("power-off {name}"))?;
+        if let Some(detaches) = &self.detaches {
+            std::fs::remove_file(detaches)?;
+        }
+        Ok(())
+    }
+}
+
+fn state(fake: &FakeSysfs) -> String {
+    std::fs::read_to_string(fake.path("sys/block/sdb/device/state"))
+        .unwrap()
+        .trim()
+        .to_string()
+}
+
+#[test]
+fn test_offline_runs_every_step_in_order() {
+    let fake = build_tree("offline-ok");
+    let mut recorder = Recorder::default();
+
+    let report = take_offline(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(report.completed);
+    assert_eq!(
+        recorder.log,
+        vec![
+            "unmount /media/stick/data",
+            "unmount /media/stick",
+            "sync",
+            "flush sdb",
+            "flush sdb1",
+            "flush sdb2",
+            "state offline",
+        ]
+    );
+    let steps: Vec<OfflineStep> = report.steps.iter().map(|s| s.step).collect();
+    assert_eq!(steps.first(), Some(&OfflineStep::Unmount));
+    assert_eq!(steps.last(), Some(&OfflineStep::VerifyState));
+    assert!(report.steps.iter().all(|s| s.status == StepStatus::Done));
+    assert_eq!(state(&fake), "offline");
+}
+
+#[test]
+fn test_failed_unmount_stops_before_anything_else() {
+    let fake = build_tree("offline-busy");
+    let mut recorder = Recorder {
+        fail: Some("unmount /media/stick/data"),
+        ..Recorder::default()
+    };
+
+    let report = take_offline(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(!report.completed);
+    assert_eq!(recorder.log, vec!["unmount /media/stick/data"]);
+    assert_eq!(report.steps.len(), 1);
+    assert_eq!(report.steps[0].status, StepStatus::Failed);
+    assert_eq!(state(&fake), "running");
+}
+
+#[test]
+fn test_failed_flush_remounts_what_was_unmounted() {
+    let fake = build_tree("offline-flush");
+    let mut recorder = Recorder {
+        fail: Some("flush sdb2"),
+        ..Recorder::default()
+    };
+
+    let report = take_offline(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(!report.completed);
+    assert_eq!(report.failure(), Some("flush sdb2 failed"));
+    assert_eq!(
+        &recorder.log[recorder.log.len() - 2..],
+        ["remount /media/stick", "remount /media/stick/data"]
+    );
+    assert_eq!(report.steps[0].status, StepStatus::RolledBack);
+    assert_eq!(report.steps[1].status, StepStatus::RolledBack);
+    assert_eq!(state(&fake), "running");
+}
+
+#[test]
+fn test_unconfirmed_state_is_rolled_back() {
+    let fake = build_tree("offline-stuck");
+    let mut recorder = Recorder {
+        stuck_state: Some("blocked"),
+        ..Recorder::default()
+    };
+
+    let report = take_offline(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(!report.completed);
+    let verify = report.steps.last().unwrap();
+    assert_eq!(verify.step, OfflineStep::VerifyState);
+    assert_eq!(verify.status, StepStatus::Failed);
+    assert_eq!(
+        verify.message.as_deref(),
+        Some("Disk sdb reports state \"blocked\"")
+    );
+    let write = &report.steps[report.steps.len() - 2];
+    assert_eq!(write.status, StepStatus::RolledBack);
+    assert_eq!(
+        &recorder.log[recorder.log.len() - 3..],
+        [
+            "state running",
+            "remount /media/stick",
+            "remount /media/stick/data"
+        ]
+    );
+    // Back online, so nothing is left to bring back
+    let records = std::fs::read_to_string(fake.path("run/diskofflaner/offline.json")).unwrap();
+    assert!(!records.contains("sdb"), "{records}");
+}
+
+#[test]
+fn test_record_is_saved_before_anything_is_done() {
+    let fake = build_tree("offline-unsaved");
+    // A directory where the records file goes cannot be written
+    std::fs::create_dir_all(fake.path("run/diskofflaner/offline.json")).unwrap();
+    let mut recorder = Recorder::default();
+
+    let err = take_offline(&fake.sysfs_root(), "sdb", &mut recorder).unwrap_err();
+
+    assert!(err.to_string().starts_with("Failed to write"));
+    assert!(recorder.log.is_empty());
+    assert_eq!(state(&fake), "running");
+}
+
+#[test]
+fn test_failed_rollback_is_reported() {
+    let fake = build_tree("offline-rollback");
+    let mut recorder = FailingRemount::default();
+
+    let report = take_offline(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(!report.completed);
+    assert_eq!(report.steps[0].status, StepStatus::RollbackFailed);
+    assert_eq!(
+        report.steps[0].message.as_deref(),
+        Some("remount /media/stick/data failed")
+    );
+    assert_eq!(report.failure(), Some("sync failed"));
+}
+
+/// Fails at sync, then fails every remount
+#[derive(Default)]
+struct FailingRemount(Recorder);
+
+impl OfflineActions for FailingRemount {
+    fn unmount(&mut self, mount: &MountEntry) -> Result<()> {
+        self.0.unmount(mount)
+    }
+
+    fn remount(&mut self, mount: &MountEntry) -> Result<()> {
+        bail!("remount {} failed", mount.mount_point)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        bail!("sync failed")
+    }
+
+    fn flush_buffers(&mut self, node: &Path) -> Result<()> {
+        self.0.flush_buffers(node)
+    }
+
+    fn write_attr(&mut self, path: &Path, value: &str) -> Result<()> {
+        self.0.write_attr(path, value)
+    }
+
+    fn power_off(&mut self, node: &Path) -> Result<()> {
+        self.0.power_off(node)
+    }
+}
+
+#[test]
+fn test_disk_without_any_method_is_an_error() {
+    let fake = FakeSysfs::new("offline-nvme");
+    fake.add_disk("nvme0n1", "259:0", "pci0000:00/nvme/nvme0", 1_000);
+    let mut recorder = Recorder::default();
+
+    let err = take_offline(&fake.sysfs_root(), "nvme0n1", &mut recorder).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Cannot change state for nvme0n1: no offline method for this device"
+    );
+    assert!(recorder.log.is_empty());
+}
+
+#[test]
+fn test_mount_flags() {
+    let mounts = parse_mountinfo(MOUNTINFO);
+    let (flags, data) = mount_flags(&mounts[1]);
+    assert_eq!(flags, libc::MS_NOSUID | libc::MS_NODEV);
+    assert_eq!(data, "fmask=0022");
+
+    let (flags, data) = mount_flags(&mounts[2]);
+    assert_eq!(flags, libc::MS_NOEXEC);
+    assert_eq!(data, "");
+}
+
+fn write(path: &str, value: &str) -> SysfsWrite {
+    SysfsWrite {
+        path: path.to_string(),
+        value: value.to_string(),
+    }
+}
+
+#[test]
+fn test_scsi_disks_use_device_state() {
+    let fake = build_tree("method-scsi");
+    let strategy = select(&fake.sysfs_root(), "sdb").unwrap();
+    assert_eq!(strategy.method, OfflineMethod::ScsiState);
+    assert_eq!(
+        strategy.offline,
+        write("/sys/block/sdb/device/state", "offline")
+    );
+    assert_eq!(
+        strategy.bring_back,
+        vec![write("/sys/block/sdb/device/state", "running")]
+    );
+}
+
+#[test]
+fn test_nvme_removes_the_pci_function() {
+    let fake = FakeSysfs::new("method-nvme");
+    fake.add_disk("nvme0n1", "259:0", NVME_PATH, 1_000_215_216);
+
+    let strategy = select(&fake.sysfs_root(), "nvme0n1").unwrap();
+    assert_eq!(strategy.method, OfflineMethod::PciRemove);
+    assert_eq!(
+        strategy.offline,
+        write("/sys/bus/pci/devices/0000:3d:00.0/remove", "1")
+    );
+    assert_eq!(strategy.bring_back, vec![write("/sys/bus/pci/rescan", "1")]);
+}
+
+#[test]
+fn test_nvme_with_other_namespaces_is_refused() {
+    let fake = FakeSysfs::new("method-nvme-shared");
+    fake.add_disk("nvme0n1", "259:0", NVME_PATH, 1_000_215_216);
+    fake.add_disk("nvme0n2", "259:1", NVME_PATH, 1_000_215_216);
+
+    let err = select(&fake.sysfs_root(), "nvme0n1").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Removing PCI device 0000:3d:00.0 would also take nvme0n2 offline"
+    );
+}
+
+#[test]
+fn test_virtio_and_mmc_unbind_their_driver() {
+    let fake = FakeSysfs::new("method-unbind");
+    fake.add_disk("vda", "252:0", VIRTIO_PATH, 41_943_040);
+    fake.bind_driver(VIRTIO_PATH, "virtio", "virtio_blk");
+    fake.add_disk("mmcblk0", "179:0", MMC_PATH, 62_333_952);
+    fake.bind_driver(MMC_PATH, "mmc", "mmcblk");
+
+    let strategy = select(&fake.sysfs_root(), "vda").unwrap();
+    assert_eq!(strategy.method, OfflineMethod::DriverUnbind);
+    assert_eq!(
+        strategy.offline,
+        write("/sys/bus/virtio/drivers/virtio_blk/unbind", "virtio1")
+    );
+    assert_eq!(
+        strategy.bring_back,
+        vec![write("/sys/bus/virtio/drivers/virtio_blk/bind", "virtio1")]
+    );
+
+    let strategy = select(&fake.sysfs_root(), "mmcblk0").unwrap();
+    assert_eq!(
+        strategy.offline,
+        write("/sys/bus/mmc/drivers/mmcblk/unbind", "mmc0:0001")
+    );
+}
+
+#[test]
+fn test_scsi_without_state_is_deleted_and_rescanned() {
+    let fake = FakeSysfs::new("method-delete");
+    fake.add_disk("sdb", "8:16", USB_PATH, 60_062_500);
+    fake.write(&format!("sys/devices/{USB_PATH}/delete"), "");
+
+    let strategy = select(&fake.sysfs_root(), "sdb").unwrap();
+    assert_eq!(strategy.method, OfflineMethod::ScsiDelete);
+    assert_eq!(strategy.offline, write("/sys/block/sdb/device/delete", "1"));
+    assert_eq!(
+        strategy.bring_back,
+        vec![write("/sys/class/scsi_host/host6/scan", "0 0 0")]
+    );
+}
+
+#[test]
+fn test_detached_nvme_is_listed_and_brought_back() {
+    let fake = FakeSysfs::new("offline-nvme-detach");
+    fake.add_disk("nvme0n1", "259:0", NVME_PATH, 1_000_215_216);
+    let root = fake.sysfs_root();
+    let mut recorder = Recorder {
+        detaches: Some(fake.path("sys/block/nvme0n1")),
+        ..Recorder::default()
+    };
+
+    let report = take_offline(&root, "nvme0n1", &mut recorder).unwrap();
+    assert!(report.completed);
+    assert_eq!(report.method, Some(OfflineMethod::PciRemove));
+    assert_eq!(report.bring_back, vec![write("/sys/bus/pci/rescan", "1")]);
+    assert_eq!(recorder.log.last().unwrap(), "remove 1");
+
+    // Gone from sysfs, but still listed as an offline disk
+    let disks = with_offline_disks(&root, enumerate_disks(&root).unwrap());
+    assert_eq!(disks.len(), 1);
+    assert_eq!(disks[0].id, "nvme0n1");
+    assert!(!disks[0].is_online);
+
+    bring_online(&root, "nvme0n1", &mut recorder).unwrap();
+    assert_eq!(recorder.log.last().unwrap(), "rescan 1");
+    assert!(with_offline_disks(&root, vec![]).is_empty());
+}
+
+#[test]
+fn test_disk_that_stays_attached_is_brought_back() {
+    let fake = FakeSysfs::new("offline-nvme-stuck");
+    fake.add_disk("nvme0n1", "259:0", NVME_PATH, 1_000_215_216);
+    let mut recorder = Recorder::default();
+
+    let report = take_offline(&fake.sysfs_root(), "nvme0n1", &mut recorder).unwrap();
+
+    assert!(!report.completed);
+    assert_eq!(report.failure(), Some("Disk nvme0n1 is still present"));
+    assert_eq!(recorder.log.last().unwrap(), "rescan 1");
+    assert!(with_offline_disks(&fake.sysfs_root(), vec![]).is_empty());
+}
+
+#[test]
+fn test_bring_online_without_record_restores_state() {
+    let fake = build_tree("online-legacy");
+    fake.write(&format!("sys/devices/{USB_PATH}/state"), "offline");
+    let mut recorder = Recorder::default();
+
+    bring_online(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+    assert_eq!(recorder.log, vec!["state running"]);
+    assert_eq!(state(&fake), "running");
+}
+
+#[test]
+fn test_eject_unmounts_flushes_and_powers_off() {
+    let fake = build_tree("eject-ok");
+    let mut recorder = Recorder {
+        detaches: Some(fake.path("sys/block/sdb")),
+        ..Recorder::default()
+    };
+
+    let report = eject(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(report.completed);
+    assert_eq!(
+        recorder.log,
+        vec![
+            "unmount /media/stick/data",
+            "unmount /media/stick",
+            "sync",
+            "flush sdb",
+            "flush sdb1",
+            "flush sdb2",
+            "power-off sdb",
+        ]
+    );
+    let last: Vec<OfflineStep> = report.steps.iter().rev().take(2).map(|s| s.step).collect();
+    assert_eq!(last, vec![OfflineStep::VerifyState, OfflineStep::PowerOff]);
+    assert_eq!(report.method, None);
+}
+
+#[test]
+fn test_eject_falls_back_to_deleting_the_device() {
+    let fake = build_tree("eject-delete");
+    fake.write(&format!("sys/devices/{USB_PATH}/delete"), "");
+    let mut recorder = Recorder {
+        fail: Some("power-off"),
+        detaches: Some(fake.path("sys/block/sdb")),
+        ..Recorder::default()
+    };
+
+    let report = eject(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(report.completed);
+    assert_eq!(
+        recorder.log[recorder.log.len() - 2..],
+        ["power-off sdb", "delete 1"]
+    );
+    let power_off = report
+        .steps
+        .iter()
+        .find(|s| s.step == OfflineStep::PowerOff)
+        .unwrap();
+    assert_eq!(power_off.target, "/sys/block/sdb/device/delete");
+}
+
+#[test]
+fn test_eject_that_cannot_power_off_remounts() {
+    let fake = build_tree("eject-stuck");
+    let mut recorder = Recorder {
+        fail: Some("power-off"),
+        ..Recorder::default()
+    };
+
+    let report = eject(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(!report.completed);
+    assert_eq!(report.failure(), Some("power-off sdb failed"));
+    assert_eq!(
+        recorder.log[recorder.log.len() - 2..],
+        ["remount /media/stick", "remount /media/stick/data"]
+    );
+}
+
+#[test]
+fn test_busy_unmount_reports_blocking_processes() {
+    let fake = build_tree("eject-busy");
+    fake.add_process(
+        4242,
+        &["vim", "notes.txt"],
+        &[
+            ("cwd", "/media/stick/data"),
+            ("fd/3", "/media/stick/data/notes.txt"),
+        ],
+    );
+    fake.add_process(4300, &["bash"], &[("cwd", "/home/user")]);
+    let mut recorder = Recorder {
+        fail: Some("unmount /media/stick/data"),
+        ..Recorder::default()
+    };
+
+    let report = eject(&fake.sysfs_root(), "sdb", &mut recorder).unwrap();
+
+    assert!(!report.completed);
+    assert_eq!(
+        report.blockers,
+        vec![BlockingProcess {
+            pid: 4242,
+            command: "vim notes.txt".to_string(),
+            user: "1000".to_string(),
+            paths: vec![
+                "/media/stick/data".to_string(),
+                "/media/stick/data/notes.txt".to_string(),
+            ],
+        }]
+    );
+}
@@ -0,0 +1,386 @@
+// src/tests/partition_table_tests.rs
+// MBR and GPT parsing against disk images built in memory
+use crate::disk_operations::partition_table::{
+    crc32, read_partition_table, read_partition_table_from, MbrKind, PartitionTable,
+};
+use std::io::Cursor;
+
+const ESP_TYPE: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+const LINUX_TYPE: &str = "0fc63daf-8483-4772-8e79-3d69d8477de4";
+const DISK_GUID: &str = "5e9a2b1c-3d4f-4a6b-8c7d-9e0f1a2b3c4d";
+const ESP_GUID: &str = "0b3c5f2e-7d1a-4e8b-9c6f-2a1d3e4f5a6b";
+const ROOT_GUID: &str = "7f6e5d4c-3b2a-4190-8f7e-6d5c4b3a2918";
+
+const ENTRY_COUNT: usize = 128;
+const ENTRY_SIZE: usize = 128;
+
+fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+    image[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn read(image: Vec<u8>) -> PartitionTable {
+    read_partition_table_from(&mut Cursor::new(image), None).unwrap()
+}
+
+/// On-disk GUID layout: the first three groups are little endian
+fn guid_bytes(guid: &str) -> [u8; 16] {
+    let hex: String = guid.chars().filter(|c| *c != '-').collect();
+    let mut bytes = [0u8; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+    }
+    bytes[0..4].reverse();
+    bytes[4..6].reverse();
+    bytes[6..8].reverse();
+    bytes
+}
+
+fn mbr_entry(
+    image: &mut [u8],
+    sector: usize,
+    slot: usize,
+    boot: bool,
+    kind: u8,
+    lba: u32,
+    len: u32,
+) {
+    let offset = sector * 512 + 446 + 16 * slot;
+    image[offset] = if boot { 0x80 } else { 0 };
+    image[offset + 4] = kind;
+    put(image, offset + 8, &lba.to_le_bytes());
+    put(image, offset + 12, &len.to_le_bytes());
+    put(image, sector * 512 + 510, &[0x55, 0xAA]);
+}
+
+struct Part {
+    type_guid: &'static str,
+    guid: &'static str,
+    first: u64,
+    last: u64,
+    attributes: u64,
+    name: &'static str,
+}
+
+const PARTS: [Part; 2] = [
+    Part {
+        type_guid: ESP_TYPE,
+        guid: ESP_GUID,
+        first: 2048,
+        last: 206_847,
+        attributes: 1,
+        name: "EFI System Partition",
+    },
+    Part {
+        type_guid: LINUX_TYPE,
+        guid: ROOT_GUID,
+        first: 206_848,
+        last: 409_566,
+        attributes: 0,
+        name: "root",
+    },
+];
+
+fn entry_array(parts: &[Part]) -> Vec<u8> {
+    let mut array = vec![0; ENTRY_COUNT * ENTRY_SIZE];
+    for (i, part) in parts.iter().enumerate() {
+        let entry = &mut array[i * ENTRY_SIZE..(i + 1) * ENTRY_SIZE];
+        put(entry, 0, &guid_bytes(part.type_guid));
+        put(entry, 16, &guid_bytes(part.guid));
+        put(entry, 32, &part.first.to_le_bytes());
+        put(entry, 40, &part.last.to_le_bytes());
+        put(entry, 48, &part.attributes.to_le_bytes());
+        let name: Vec<u8> = part
+            .name
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        put(entry, 56, &name);
+    }
+    array
+}
+
+fn gpt_header(current: u64, backup: u64, entries_lba: u64, sectors: u64, array: &[u8]) -> Vec<u8> {
+    let entry_sectors = (ENTRY_COUNT * ENTRY_SIZE) as u64 / 512;
+    let mut header = vec![0; 92];
+    put(&mut header, 0, b"EFI PART");
+    put(&mut header, 8, &0x0001_0000u32.to_le_bytes());
+    put(&mut header, 12, &92u32.to_le_bytes());
+    put(&mut header, 24, &current.to_le_bytes());
+    put(&mut header, 32, &backup.to_le_bytes());
+    put(&mut header, 40, &(2 + entry_sectors).to_le_bytes());
+    put(
+        &mut header,
+        48,
+        &(sectors - 2 - entry_sectors).to_le_bytes(),
+    );
+    put(&mut header, 56, &guid_bytes(DISK_GUID));
+    put(&mut header, 72, &entries_lba.to_le_bytes());
+    put(
+        &mut header,
+        80,
+        &u32::try_from(ENTRY_COUNT).unwrap().to_le_bytes(),
+    );
+    put(
+        &mut header,
+        84,
+        &u32::try_from(ENTRY_SIZE).unwrap().to_le_bytes(),
+    );
+    put(&mut header, 88, &crc32(array).to_le_bytes());
+    let crc = crc32(&header);
+    put(&mut header, 16, &crc.to_le_bytes());
+    header
+}
+
+/// A GPT disk with a protective MBR and both header copies
+fn gpt_image(sector_size: usize, sectors: usize) -> Vec<u8> {
+    let mut image = vec![0; sector_size * sectors];
+    let last = sectors as u64 - 1;
+    mbr_entry(
+        &mut image,
+        0,
+        0,
+        false,
+        0xEE,
+        1,
+        u32::try_from(last).unwrap(),
+    );
+
+    let array = entry_array(&PARTS);
+    let array_sectors = array.len() / sector_size;
+    put(&mut image, 2 * sector_size, &array);
+    put(
+        &mut image,
+        sector_size,
+        &gpt_header(1, last, 2, last + 1, &array),
+    );
+
+    let backup_entries = sectors - 1 - array_sectors;
+    put(&mut image, backup_entries * sector_size, &array);
+    put(
+        &mut image,
+        (sectors - 1) * sector_size,
+        &gpt_header(last, 1, backup_entries as u64, last + 1, &array),
+    );
+    image
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn test_gpt_with_protective_mbr() {
+    let table = read(gpt_image(512, 4096));
+    assert_eq!(table.sector_size, 512);
+    assert_eq!(table.mbr.unwrap().kind, MbrKind::Protective);
+
+    let gpt = table.gpt.unwrap();
+    assert_eq!(gpt.disk_guid, DISK_GUID);
+    assert!(gpt.primary.unwrap().is_valid());
+    let backup = gpt.backup.unwrap();
+    assert!(backup.is_valid());
+    assert_eq!(backup.current_lba, 4095);
+
+    assert_eq!(gpt.entries.len(), 2);
+    let esp = &gpt.entries[0];
+    assert_eq!(esp.number, 1);
+    assert_eq!(esp.type_guid, ESP_TYPE);
+    assert_eq!(esp.unique_guid, ESP_GUID);
+    assert_eq!((esp.first_lba, esp.last_lba), (2048, 206_847));
+    assert_eq!(esp.attributes, 1);
+    assert_eq!(esp.name, "EFI System Partition");
+    assert_eq!(gpt.entries[1].name, "root");
+    assert_eq!(gpt.entries[1].type_guid, LINUX_TYPE);
+}
+
+#[test]
+fn test_gpt_4k_sectors() {
+    let table = read(gpt_image(4096, 64));
+    assert_eq!(table.sector_size, 4096);
+    let gpt = table.gpt.unwrap();
+    assert_eq!(gpt.entries.len(), 2);
+    assert_eq!(gpt.backup.unwrap().current_lba, 63);
+}
+
+#[test]
+fn test_gpt_falls_back_to_backup_header() {
+    let mut image = gpt_image(512, 4096);
+    image[512 + 56] ^= 0xFF; // disk GUID no longer matches the header CRC
+    let gpt = read(image).gpt.unwrap();
+
+    let primary = gpt.primary.unwrap();
+    assert!(!primary.header_crc_valid);
+    assert!(gpt.backup.unwrap().is_valid());
+    assert_eq!(gpt.disk_guid, DISK_GUID);
+    assert_eq!(gpt.entries.len(), 2);
+}
+
+#[test]
+fn test_gpt_detects_corrupt_entry_array() {
+    let mut image = gpt_image(512, 4096);
+    image[2 * 512 + 56] = b'X'; // rename the ESP in the primary array only
+    let gpt = read(image).gpt.unwrap();
+
+    let primary = gpt.primary.unwrap();
+    assert!(primary.header_crc_valid);
+    assert!(!primary.entries_crc_valid);
+    assert_eq!(gpt.entries[0].name, "EFI System Partition");
+}
+
+#[test]
+fn test_gpt_header_with_oversized_entries_is_ignored() {
+    let mut image = gpt_image(512, 4096);
+    // 4096 entries of 1 MiB would be a 4 GiB entry array
+    put(&mut image, 512 + 80, &4096u32.to_le_bytes());
+    put(&mut image, 512 + 84, &(1u32 << 20).to_le_bytes());
+    let gpt = read(image).gpt.unwrap();
+
+    assert!(gpt.primary.is_none());
+    assert!(gpt.backup.unwrap().is_valid());
+    assert_eq!(gpt.entries.len(), 2);
+}
+
+#[test]
+fn test_gpt_entry_array_is_capped() {
+    let mut image = gpt_image(512, 4096);
+    // Each value is allowed, the 16 MiB array they add up to is not
+    put(&mut image, 512 + 80, &4096u32.to_le_bytes());
+    put(&mut image, 512 + 84, &4096u32.to_le_bytes());
+    let gpt = read(image).gpt.unwrap();
+
+    assert!(gpt.primary.is_none());
+    assert_eq!(gpt.entries.len(), 2);
+}
+
+#[test]
+fn test_gpt_both_headers_corrupt() {
+    let mut image = gpt_image(512, 4096);
+    image[512 + 56] ^= 0xFF;
+    image[4095 * 512 + 56] ^= 0xFF;
+    let err = read_partition_table_from(&mut Cursor::new(image), None).unwrap_err();
+    assert!(err.to_string().contains("corrupt"));
+}
+
+#[test]
+fn test_hybrid_mbr() {
+    let mut image = gpt_image(512, 4096);
+    // The ESP mirrored as a bootable FAT32 entry
+    mbr_entry(&mut image, 0, 1, true, 0x0C, 2048, 204_800);
+    let table = read(image);
+
+    let mbr = table.mbr.unwrap();
+    assert_eq!(mbr.kind, MbrKind::Hybrid);
+    assert_eq!(mbr.entries.len(), 2);
+    assert!(mbr.entries[1].bootable);
+    assert_eq!(table.gpt.unwrap().entries.len(), 2);
+}
+
+#[test]
+fn test_mbr_with_logical_partitions() {
+    let mut image = vec![0; 512 * 4096];
+    put(&mut image, 440, &0xA1B2_C3D4u32.to_le_bytes());
+    mbr_entry(&mut image, 0, 0, true, 0x83, 2048, 1024);
+    mbr_entry(&mut image, 0, 1, false, 0x05, 3072, 1024);
+    // First EBR: logical at +63, link to the next EBR at extended + 512
+    mbr_entry(&mut image, 3072, 0, false, 0x83, 63, 400);
+    mbr_entry(&mut image, 3072, 1, false, 0x05, 512, 512);
+    // Second EBR: last logical, no link
+    mbr_entry(&mut image, 3584, 0, false, 0x82, 63, 300);
+
+    let table = read(image);
+    assert!(table.gpt.is_none());
+    let mbr = table.mbr.unwrap();
+    assert_eq!(mbr.kind, MbrKind::Classic);
+    assert_eq!(mbr.disk_signature, 0xA1B2_C3D4);
+
+    let summary: Vec<(u32, u8, u64, u64, bool)> = mbr
+        .entries
+        .iter()
+        .map(|e| (e.number, e.type_id, e.first_lba, e.last_lba, e.logical))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (1, 0x83, 2048, 3071, false),
+            (2, 0x05, 3072, 4095, false),
+            (5, 0x83, 3135, 3534, true),
+            (6, 0x82, 3647, 3946, true),
+        ]
+    );
+    assert!(mbr.entries[0].bootable);
+    assert!(mbr.entries[1].extended);
+}
+
+#[test]
+fn test_logical_partitions_with_4k_sectors() {
+    // EBRs are found at LBA * 4096; mbr_entry counts in 512 byte units
+    let mut image = vec![0; 4096 * 1024];
+    mbr_entry(&mut image, 0, 0, false, 0x83, 256, 128);
+    mbr_entry(&mut image, 0, 1, false, 0x05, 384, 256);
+    mbr_entry(&mut image, 384 * 8, 0, false, 0x83, 8, 50);
+    mbr_entry(&mut image, 384 * 8, 1, false, 0x05, 128, 128);
+    mbr_entry(&mut image, 512 * 8, 0, false, 0x82, 8, 40);
+
+    let table = read_partition_table_from(&mut Cursor::new(image.clone()), Some(4096)).unwrap();
+    assert_eq!(table.sector_size, 4096);
+    let logical: Vec<(u32, u8, u64, u64)> = table
+        .mbr
+        .unwrap()
+        .entries
+        .iter()
+        .filter(|e| e.logical)
+        .map(|e| (e.number, e.type_id, e.first_lba, e.last_lba))
+        .collect();
+    assert_eq!(logical, vec![(5, 0x83, 392, 441), (6, 0x82, 520, 559)]);
+
+    // Nothing on an MBR disk gives the sector size away
+    let guessed = read(image).mbr.unwrap();
+    assert!(guessed.entries.iter().all(|e| !e.logical));
+}
+
+#[test]
+fn test_invalid_sector_size_is_an_error() {
+    let image = gpt_image(512, 4096);
+    assert!(read_partition_table_from(&mut Cursor::new(image.clone()), Some(520)).is_err());
+    assert!(read_partition_table_from(&mut Cursor::new(image), Some(256)).is_err());
+}
+
+#[test]
+fn test_ebr_loop_terminates() {
+    let mut image = vec![0; 512 * 4096];
+    mbr_entry(&mut image, 0, 0, false, 0x0F, 2048, 2048);
+    mbr_entry(&mut image, 2048, 0, false, 0x83, 63, 100);
+    // Links back to itself
+    mbr_entry(&mut image, 2048, 1, false, 0x05, 0, 512);
+
+    let mbr = read(image).mbr.unwrap();
+    assert_eq!(mbr.entries.len(), 2);
+}
+
+#[test]
+fn test_no_partition_table() {
+    let err = read_partition_table_from(&mut Cursor::new(vec![0; 65536]), None).unwrap_err();
+    assert!(err.to_string().contains("No partition table"));
+
+    // A FAT boot sector ends in 55 AA too, but is not an MBR
+    let mut image = vec![0; 65536];
+    put(&mut image, 0, &[0xEB, 0x3C, 0x90]);
+    put(&mut image, 3, b"mkfs.fat");
+    put(&mut image, 446, b"This is not a bootable disk.  ");
+    put(&mut image, 510, &[0x55, 0xAA]);
+    assert!(read_partition_table_from(&mut Cursor::new(image), None).is_err());
+}
+
+#[test]
+fn test_read_image_file() {
+    let path = std::env::temp_dir().join(format!("diskofflaner-gpt-{}.img", std::process::id()));
+    std::fs::write(&path, gpt_image(512, 4096)).unwrap();
+    let table = read_partition_table(&path, None);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(table.unwrap().gpt.unwrap().entries.len(), 2);
+
+    let err = read_partition_table(&path, None).unwrap_err();
+    assert!(format!("{err:#}").contains("Failed to open"));
+}
@@ -0,0 +1,519 @@
+// src/tests/probe_tests.rs
+// Signature probing against small images built in memory
+use crate::disk_operations::probe::{apply_signature, probe_path, probe_reader, Signature};
+use crate::structs::PartitionInfo;
+use std::io::Cursor;
+
+const UUID_BYTES: [u8; 16] = [
+    0x3e, 0x6b, 0xe9, 0xde, 0x81, 0x39, 0x11, 0xd1, 0x91, 0x06, 0xa4, 0x3f, 0x08, 0xd8, 0x23, 0xa6,
+];
+const UUID_TEXT: &str = "3e6be9de-8139-11d1-9106-a43f08d823a6";
+
+fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+    image[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn probe(image: Vec<u8>) -> Option<Signature> {
+    probe_reader(&mut Cursor::new(image)).unwrap()
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn ext_image(compat: u32, incompat: u32, ro_compat: u32) -> Vec<u8> {
+    let mut image = vec![0; 8192];
+    let sb = 1024;
+    put(&mut image, sb + 0x38, &0xEF53u16.to_le_bytes());
+    put(&mut image, sb + 0x5C, &compat.to_le_bytes());
+    put(&mut image, sb + 0x60, &incompat.to_le_bytes());
+    put(&mut image, sb + 0x64, &ro_compat.to_le_bytes());
+    put(&mut image, sb + 0x68, &UUID_BYTES);
+    put(&mut image, sb + 0x78, b"rootfs");
+    image
+}
+
+#[test]
+fn test_ext_versions() {
+    let ext4 = probe(ext_image(0x3C, 0x2C2, 0x46B)).unwrap();
+    assert_eq!(ext4.fs_type, "ext4");
+    assert_eq!(ext4.label.as_deref(), Some("rootfs"));
+    assert_eq!(ext4.uuid.as_deref(), Some(UUID_TEXT));
+
+    assert_eq!(probe(ext_image(0x3C, 0x2, 0x3)).unwrap().fs_type, "ext3");
+    assert_eq!(probe(ext_image(0x38, 0x2, 0x3)).unwrap().fs_type, "ext2");
+    assert_eq!(probe(ext_image(0, 0x8, 0)).unwrap().fs_type, "jbd");
+}
+
+#[test]
+fn test_xfs() {
+    let mut image = vec![0; 4096];
+    put(&mut image, 0, b"XFSB");
+    put(&mut image, 4, &4096u32.to_be_bytes());
+    put(&mut image, 32, &UUID_BYTES);
+    put(&mut image, 108, b"data");
+
+    let sig = probe(image.clone()).unwrap();
+    assert_eq!(sig.fs_type, "xfs");
+    assert_eq!(sig.label.as_deref(), Some("data"));
+    assert_eq!(sig.uuid.as_deref(), Some(UUID_TEXT));
+
+    // A bogus block size is not XFS
+    put(&mut image, 4, &1000u32.to_be_bytes());
+    assert_eq!(probe(image), None);
+}
+
+#[test]
+fn test_btrfs() {
+    let mut image = vec![0; 0x11000];
+    put(&mut image, 0x10020, &UUID_BYTES);
+    put(&mut image, 0x10040, b"_BHRfS_M");
+    put(&mut image, 0x1012B, b"fedora_root");
+
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "btrfs");
+    assert_eq!(sig.label.as_deref(), Some("fedora_root"));
+    assert_eq!(sig.uuid.as_deref(), Some(UUID_TEXT));
+}
+
+/// NTFS with 4 KiB clusters, the MFT at cluster 4 and 1 KiB records. The
+/// volume name of record 3 straddles the end of the first sector, so it is
+/// only readable once the update sequence fixups are applied.
+fn ntfs_image(label: &str) -> Vec<u8> {
+    let mut image = vec![0; 24576];
+    put(&mut image, 0, &[0xEB, 0x52, 0x90]);
+    put(&mut image, 3, b"NTFS    ");
+    put(&mut image, 0x0B, &512u16.to_le_bytes());
+    image[0x0D] = 8;
+    put(&mut image, 0x30, &4u64.to_le_bytes());
+    image[0x40] = 0xF6; // 2^10 bytes per record
+    put(&mut image, 0x48, &0x1234_5678_9ABC_DEF0u64.to_le_bytes());
+
+    let mut record = vec![0u8; 1024];
+    put(&mut record, 0, b"FILE");
+    put(&mut record, 4, &0x30u16.to_le_bytes());
+    put(&mut record, 6, &3u16.to_le_bytes());
+    put(&mut record, 0x14, &0x38u16.to_le_bytes());
+    // $STANDARD_INFORMATION-sized filler up to the volume name
+    put(&mut record, 0x38, &0x10u32.to_le_bytes());
+    put(&mut record, 0x3C, &(0x1E0u32 - 0x38).to_le_bytes());
+    let name = utf16(label);
+    let attr = 0x1E0;
+    let attr_len = (0x18 + name.len()).next_multiple_of(8);
+    put(&mut record, attr, &0x60u32.to_le_bytes());
+    put(
+        &mut record,
+        attr + 4,
+        &u32::try_from(attr_len).unwrap().to_le_bytes(),
+    );
+    put(
+        &mut record,
+        attr + 0x10,
+        &u32::try_from(name.len()).unwrap().to_le_bytes(),
+    );
+    put(&mut record, attr + 0x14, &0x18u16.to_le_bytes());
+    put(&mut record, attr + 0x18, &name);
+    put(&mut record, attr + attr_len, &0xFFFF_FFFFu32.to_le_bytes());
+
+    // Save the sector tails in the update sequence array, stamp the USN
+    let usn = [0x07, 0x00];
+    put(&mut record, 0x30, &usn);
+    for i in 1..3 {
+        let end = i * 512 - 2;
+        let saved = [record[end], record[end + 1]];
+        put(&mut record, 0x30 + 2 * i, &saved);
+        put(&mut record, end, &usn);
+    }
+    put(&mut image, 16384 + 3 * 1024, &record);
+    image
+}
+
+#[test]
+fn test_ntfs_label_from_mft() {
+    let sig = probe(ntfs_image("Windows Data")).unwrap();
+    assert_eq!(sig.fs_type, "ntfs");
+    assert_eq!(sig.label.as_deref(), Some("Windows Data"));
+    assert_eq!(sig.uuid.as_deref(), Some("123456789ABCDEF0"));
+}
+
+#[test]
+fn test_ntfs_bad_fixup_loses_only_the_label() {
+    let mut image = ntfs_image("Windows Data");
+    put(&mut image, 16384 + 3 * 1024 + 510, &[0xAA, 0xAA]);
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "ntfs");
+    assert_eq!(sig.label, None);
+}
+
+#[test]
+fn test_ntfs_impossible_geometry_loses_only_the_label() {
+    // 1- and 2-byte records, 2^63 sectors per cluster, 2^127 clusters per
+    // record
+    for (offset, value) in [(0x40, 0x00), (0x40, 0xFF), (0x0D, 0xC1), (0x40, 0x7F)] {
+        let mut image = ntfs_image("Windows Data");
+        image[offset] = value;
+        let sig = probe(image).unwrap();
+        assert_eq!(sig.fs_type, "ntfs");
+        assert_eq!(sig.label, None);
+    }
+}
+
+struct FatGeometry {
+    sectors_per_cluster: u8,
+    reserved: u16,
+    root_entries: u16,
+    total_sectors: u32,
+    fat_size: u32,
+    fat32: bool,
+}
+
+impl FatGeometry {
+    /// Right after the FATs: the fixed root directory on FAT12/16, cluster 2
+    /// on FAT32
+    fn root_dir(&self) -> usize {
+        (usize::from(self.reserved) + 2 * self.fat_size as usize) * 512
+    }
+
+    fn image(&self, boot_label: &[u8; 11], root_label: Option<&[u8; 11]>) -> Vec<u8> {
+        let mut image = vec![0; self.root_dir() + 16384];
+        put(&mut image, 0, &[0xEB, 0x3C, 0x90]);
+        put(&mut image, 3, b"mkfs.fat");
+        put(&mut image, 0x0B, &512u16.to_le_bytes());
+        image[0x0D] = self.sectors_per_cluster;
+        put(&mut image, 0x0E, &self.reserved.to_le_bytes());
+        image[0x10] = 2;
+        put(&mut image, 0x11, &self.root_entries.to_le_bytes());
+        image[0x15] = 0xF8;
+        match u16::try_from(self.total_sectors) {
+            Ok(total) => put(&mut image, 0x13, &total.to_le_bytes()),
+            Err(_) => put(&mut image, 0x20, &self.total_sectors.to_le_bytes()),
+        }
+        let ebpb = if self.fat32 {
+            put(&mut image, 0x24, &self.fat_size.to_le_bytes());
+            put(&mut image, 0x2C, &2u32.to_le_bytes());
+            0x40
+        } else {
+            put(
+                &mut image,
+                0x16,
+                &u16::try_from(self.fat_size).unwrap().to_le_bytes(),
+            );
+            0x24
+        };
+        image[ebpb + 2] = 0x29;
+        put(&mut image, ebpb + 3, &0x1C2D_7A3Bu32.to_le_bytes());
+        put(&mut image, ebpb + 7, boot_label);
+        put(&mut image, 510, &[0x55, 0xAA]);
+
+        let root = self.root_dir();
+        // A deleted entry and a long name entry come before the label
+        image[root] = 0xE5;
+        image[root + 11] = 0x08;
+        image[root + 32] = b'A';
+        image[root + 32 + 11] = 0x0F;
+        if let Some(label) = root_label {
+            put(&mut image, root + 64, label);
+            image[root + 64 + 11] = 0x08;
+        }
+        image
+    }
+}
+
+const FAT12: FatGeometry = FatGeometry {
+    sectors_per_cluster: 1,
+    reserved: 1,
+    root_entries: 224,
+    total_sectors: 2880,
+    fat_size: 9,
+    fat32: false,
+};
+
+const FAT16: FatGeometry = FatGeometry {
+    sectors_per_cluster: 4,
+    reserved: 4,
+    root_entries: 512,
+    total_sectors: 131_072,
+    fat_size: 128,
+    fat32: false,
+};
+
+const FAT32: FatGeometry = FatGeometry {
+    sectors_per_cluster: 8,
+    reserved: 32,
+    root_entries: 0,
+    total_sectors: 1_000_000,
+    fat_size: 1000,
+    fat32: true,
+};
+
+#[test]
+fn test_fat_versions() {
+    for (fat, version) in [(FAT12, "FAT12"), (FAT16, "FAT16"), (FAT32, "FAT32")] {
+        let sig = probe(fat.image(b"BOOTLABEL  ", Some(b"MY STICK   "))).unwrap();
+        assert_eq!(sig.fs_type, "vfat", "{version}");
+        assert_eq!(sig.version.as_deref(), Some(version));
+        assert_eq!(sig.label.as_deref(), Some("MY STICK"), "{version}");
+        assert_eq!(sig.uuid.as_deref(), Some("1C2D-7A3B"), "{version}");
+    }
+}
+
+#[test]
+fn test_fat_label_fallbacks() {
+    let sig = probe(FAT16.image(b"BOOTLABEL  ", None)).unwrap();
+    assert_eq!(sig.label.as_deref(), Some("BOOTLABEL"));
+
+    let sig = probe(FAT32.image(b"NO NAME    ", None)).unwrap();
+    assert_eq!(sig.label, None);
+}
+
+#[test]
+fn test_fat_rejects_mbr() {
+    // An MBR has the boot signature but no BPB
+    let mut image = vec![0; 4096];
+    put(&mut image, 0, &[0xEB, 0x63, 0x90]);
+    put(&mut image, 510, &[0x55, 0xAA]);
+    assert_eq!(probe(image), None);
+}
+
+#[test]
+fn test_exfat() {
+    let mut image = vec![0; 81920];
+    put(&mut image, 0, &[0xEB, 0x76, 0x90]);
+    put(&mut image, 3, b"EXFAT   ");
+    put(&mut image, 0x58, &128u32.to_le_bytes());
+    put(&mut image, 0x60, &4u32.to_le_bytes());
+    put(&mut image, 0x64, &0xDEAD_BEEFu32.to_le_bytes());
+    image[0x6C] = 9;
+    image[0x6D] = 3;
+
+    let root = 65536 + 2 * 4096;
+    image[root] = 0x81; // allocation bitmap
+    image[root + 32] = 0x83;
+    image[root + 33] = 6;
+    put(&mut image, root + 34, &utf16("EXDATA"));
+
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "exfat");
+    assert_eq!(sig.label.as_deref(), Some("EXDATA"));
+    assert_eq!(sig.uuid.as_deref(), Some("DEAD-BEEF"));
+}
+
+#[test]
+fn test_iso9660() {
+    let mut image = vec![0; 20 * 2048];
+    // A boot record before the primary descriptor
+    image[16 * 2048] = 0;
+    put(&mut image, 16 * 2048 + 1, b"CD001");
+    let pvd = 17 * 2048;
+    image[pvd] = 1;
+    put(&mut image, pvd + 1, b"CD001\x01");
+    put(&mut image, pvd + 40, b"Ubuntu 24.04 LTS amd64          ");
+    put(&mut image, pvd + 813, b"2024042512000000\x00");
+    image[18 * 2048] = 255;
+    put(&mut image, 18 * 2048 + 1, b"CD001");
+
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "iso9660");
+    assert_eq!(sig.label.as_deref(), Some("Ubuntu 24.04 LTS amd64"));
+    assert_eq!(sig.uuid.as_deref(), Some("2024-04-25-12-00-00-00"));
+}
+
+#[test]
+fn test_swap_page_sizes() {
+    for page in [4096, 16384, 65536] {
+        let mut image = vec![0; page];
+        put(&mut image, 1024, &1u32.to_le_bytes());
+        put(&mut image, 1036, &UUID_BYTES);
+        put(&mut image, 1052, b"swap0");
+        put(&mut image, page - 10, b"SWAPSPACE2");
+
+        let sig = probe(image).unwrap();
+        assert_eq!(sig.fs_type, "swap", "page size {page}");
+        assert_eq!(sig.version.as_deref(), Some("1"));
+        assert_eq!(sig.label.as_deref(), Some("swap0"));
+        assert_eq!(sig.uuid.as_deref(), Some(UUID_TEXT));
+    }
+}
+
+fn luks_image(version: u16) -> Vec<u8> {
+    let mut image = vec![0; 4096];
+    put(&mut image, 0, b"LUKS\xba\xbe");
+    put(&mut image, 6, &version.to_be_bytes());
+    put(&mut image, 24, b"cryptroot");
+    put(&mut image, 168, UUID_TEXT.as_bytes());
+    image
+}
+
+#[test]
+fn test_luks() {
+    let luks1 = probe(luks_image(1)).unwrap();
+    assert_eq!(luks1.fs_type, "crypto_LUKS");
+    assert_eq!(luks1.version.as_deref(), Some("1"));
+    assert_eq!(luks1.label, None);
+    assert_eq!(luks1.uuid.as_deref(), Some(UUID_TEXT));
+
+    let luks2 = probe(luks_image(2)).unwrap();
+    assert_eq!(luks2.version.as_deref(), Some("2"));
+    assert_eq!(luks2.label.as_deref(), Some("cryptroot"));
+}
+
+#[test]
+fn test_lvm2_pv() {
+    let mut image = vec![0; 4096];
+    put(&mut image, 512, b"LABELONE");
+    put(&mut image, 520, &1u64.to_le_bytes());
+    put(&mut image, 532, &32u32.to_le_bytes());
+    put(&mut image, 536, b"LVM2 001");
+    put(&mut image, 544, b"aZ3kq0Yh7cXw1lPz9Qe2Rn4Tb8Vm6Ud5");
+
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "LVM2_member");
+    assert_eq!(
+        sig.uuid.as_deref(),
+        Some("aZ3kq0-Yh7c-Xw1l-Pz9Q-e2Rn-4Tb8-Vm6Ud5")
+    );
+}
+
+#[test]
+fn test_lvm2_non_ascii_uuid() {
+    let mut image = vec![0; 4096];
+    put(&mut image, 512, b"LABELONE");
+    put(&mut image, 532, &32u32.to_le_bytes());
+    put(&mut image, 536, b"LVM2 001");
+    // A two-byte character across the end of the first group
+    put(
+        &mut image,
+        544,
+        "aZ3kq\u{e9}h7cXw1lPz9Qe2Rn4Tb8Vm6Ud5".as_bytes(),
+    );
+
+    assert_eq!(probe(image), None);
+}
+
+fn md_superblock(image: &mut [u8], offset: usize) {
+    put(image, offset, &0xA92B_4EFCu32.to_le_bytes());
+    put(image, offset + 4, &1u32.to_le_bytes());
+    put(image, offset + 16, &UUID_BYTES);
+    put(image, offset + 32, b"nas:0");
+}
+
+#[test]
+fn test_mdraid_versions() {
+    let mut image = vec![0; 65536];
+    md_superblock(&mut image, 4096);
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "linux_raid_member");
+    assert_eq!(sig.version.as_deref(), Some("1.2"));
+    assert_eq!(sig.label.as_deref(), Some("nas:0"));
+    assert_eq!(sig.uuid.as_deref(), Some(UUID_TEXT));
+
+    // 1.0 lives 8 KiB from the end, 4 KiB aligned, and wins over the
+    // filesystem of the RAID1 member at the start
+    let mut image = ext_image(0x3C, 0x2C2, 0);
+    image.resize(1_052_672 + 3000, 0);
+    let offset = (image.len() - 8192) & !4095;
+    md_superblock(&mut image, offset);
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "linux_raid_member");
+    assert_eq!(sig.version.as_deref(), Some("1.0"));
+}
+
+#[test]
+fn test_mdraid_0_90() {
+    let mut image = vec![0; 300_000];
+    // Last 64 KiB boundary, minus 64 KiB
+    let offset = 0x3_0000;
+    put(&mut image, offset, &0xA92B_4EFCu32.to_le_bytes());
+    put(&mut image, offset + 0x14, &UUID_BYTES[0..4]);
+    put(&mut image, offset + 0x34, &UUID_BYTES[4..16]);
+
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "linux_raid_member");
+    assert_eq!(sig.version.as_deref(), Some("0.90.0"));
+    assert_eq!(sig.uuid.as_deref(), Some(UUID_TEXT));
+}
+
+fn nvpair(name: &str, kind: u32, value: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&u32::try_from(name.len()).unwrap().to_be_bytes());
+    body.extend_from_slice(name.as_bytes());
+    body.resize(4 + name.len().next_multiple_of(4), 0);
+    body.extend_from_slice(&kind.to_be_bytes());
+    body.extend_from_slice(&1u32.to_be_bytes());
+    body.extend_from_slice(value);
+
+    let size = u32::try_from(8 + body.len()).unwrap();
+    let mut pair = size.to_be_bytes().to_vec();
+    pair.extend_from_slice(&size.to_be_bytes());
+    pair.extend(body);
+    pair
+}
+
+#[test]
+fn test_zfs_member() {
+    let mut pool_name = 5u32.to_be_bytes().to_vec();
+    pool_name.extend_from_slice(b"tank\0\0\0\0");
+    let mut nvlist = vec![1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    nvlist.extend(nvpair("version", 8, &5000u64.to_be_bytes()));
+    nvlist.extend(nvpair("name", 9, &pool_name));
+    nvlist.extend(nvpair("pool_guid", 8, &1_234_567_890_123u64.to_be_bytes()));
+    nvlist.extend([0; 8]);
+
+    // Label 0 wiped, label 1 intact
+    let label = 256 * 1024;
+    let mut image = vec![0; 2 * label];
+    put(&mut image, label + 16 * 1024, &nvlist);
+    put(
+        &mut image,
+        label + 128 * 1024 + 3 * 1024,
+        &0x00BA_B10Cu64.to_le_bytes(),
+    );
+
+    let sig = probe(image).unwrap();
+    assert_eq!(sig.fs_type, "zfs_member");
+    assert_eq!(sig.label.as_deref(), Some("tank"));
+    assert_eq!(sig.uuid.as_deref(), Some("1234567890123"));
+}
+
+#[test]
+fn test_unknown_and_tiny_images() {
+    assert_eq!(probe(vec![0; 1024 * 1024]), None);
+    assert_eq!(probe(vec![0xFF; 100]), None);
+    assert_eq!(probe(Vec::new()), None);
+}
+
+#[test]
+fn test_probe_path() {
+    let path = std::env::temp_dir().join(format!("diskofflaner-probe-{}.img", std::process::id()));
+    std::fs::write(&path, ext_image(0x3C, 0x2C2, 0)).unwrap();
+    let sig = probe_path(&path).unwrap().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(sig.fs_type, "ext4");
+
+    assert!(probe_path(&path).is_err());
+}
+
+#[test]
+fn test_apply_signature_keeps_udev_values() {
+    let mut partition = PartitionInfo {
+        partition_number: 1,
+        size_bytes: 0,
+        used_bytes: None,
+        drive_letter: String::new(),
+        partition_id: "sdb1".to_string(),
+        mount_points: vec![],
+        usage: None,
+        fs_type: None,
+        label: Some("From udev".to_string()),
+        uuid: None,
+        part_uuid: None,
+        part_type: None,
+        part_flags: vec![],
+    };
+    let sig = probe(FAT32.image(b"NO NAME    ", Some(b"PROBED     "))).unwrap();
+    apply_signature(&mut partition, &sig);
+
+    assert_eq!(partition.fs_type.as_deref(), Some("vfat"));
+    assert_eq!(partition.label.as_deref(), Some("From udev"));
+    assert_eq!(partition.uuid.as_deref(), Some("1C2D-7A3B"));
+}
@@ -0,0 +1,83 @@
+// src/tests/protection_tests.rs
+use super::builders::{disk, partition, protected_disk};
+use crate::disk_operations::protection::{check, disk_for_target};
+use crate::structs::{DiskError, PartitionInfo, ProtectedOperation, ProtectionReason};
+
+/// A Windows partition, reached through its drive letter
+fn lettered(id: &str, letter: &str) -> PartitionInfo {
+    PartitionInfo {
+        drive_letter: letter.to_string(),
+        partition_id: id.to_string(),
+        ..partition("", 1, &[])
+    }
+}
+
+#[test]
+fn test_unprotected_disk_is_allowed() {
+    let data = disk("sdb", vec![]);
+    assert!(check(&data, ProtectedOperation::Offline, false).is_ok());
+}
+
+#[test]
+fn test_protected_disk_is_refused() {
+    let root = protected_disk(
+        "nvme0n1",
+        vec![ProtectionReason::RootFilesystem, ProtectionReason::Swap],
+        vec![],
+    );
+    let refusal = check(&root, ProtectedOperation::Offline, false).unwrap_err();
+
+    assert_eq!(refusal.disk_id, "nvme0n1");
+    assert_eq!(refusal.operation, ProtectedOperation::Offline);
+    assert_eq!(
+        refusal.reasons,
+        vec![ProtectionReason::RootFilesystem, ProtectionReason::Swap]
+    );
+    assert_eq!(
+        refusal.message,
+        "Disk nvme0n1 holds the root filesystem and hosts active swap: offline refused \
+         without an explicit override"
+    );
+}
+
+#[test]
+fn test_override_allows_protected_disk() {
+    let boot = protected_disk("sda", vec![ProtectionReason::Boot], vec![]);
+    assert!(check(&boot, ProtectedOperation::Unmount, true).is_ok());
+    assert!(check(&boot, ProtectedOperation::Eject, false).is_err());
+}
+
+#[test]
+fn test_disk_for_target() {
+    let disks = vec![
+        protected_disk(
+            "0",
+            vec![ProtectionReason::SystemDisk],
+            vec![lettered("P1", "C")],
+        ),
+        disk("1", vec![lettered("P2", "E")]),
+        disk("sdb", vec![partition("sdb", 1, &["/media/usb"])]),
+    ];
+    let id = |target: &str| disk_for_target(&disks, target).map(|d| d.id.as_str());
+
+    assert_eq!(id("C"), Some("0"));
+    assert_eq!(id("c:"), Some("0"));
+    assert_eq!(id("E:\\"), Some("1"));
+    assert_eq!(id("/media/usb"), Some("sdb"));
+    assert_eq!(id("/media"), None);
+    assert_eq!(id("/"), None);
+}
+
+#[test]
+fn test_refusal_serialization() {
+    let root = protected_disk("sda", vec![ProtectionReason::RootFilesystem], vec![]);
+    let err: DiskError = check(&root, ProtectedOperation::Offline, false)
+        .unwrap_err()
+        .into();
+    let json = serde_json::to_value(&err).unwrap();
+    assert_eq!(json["code"], "refused");
+    assert_eq!(json["disk"], "sda");
+    assert_eq!(json["details"]["type"], "refusal");
+    assert_eq!(json["details"]["operation"], "offline");
+    assert_eq!(json["details"]["reasons"][0], "root_filesystem");
+}
@@ -0,0 +1,162 @@
+// src/tests/rescan_tests.rs
+// Storage rescan against a fake tree whose "kernel" brings disks back
+
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::mountinfo::MountEntry;
+use crate::disk_operations::offline::{take_offline, with_offline_disks, OfflineActions};
+use crate::disk_operations::rescan::{rescan, scan_writes};
+use crate::disk_operations::sysfs::enumerate_disks;
+use crate::structs::SysfsWrite;
+use anyhow::{bail, Result};
+use std::path::Path;
+
+const SATA_PATH: &str = "pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0";
+const USB_PATH: &str = "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+const NVME_PATH: &str = "pci0000:00/0000:00:1d.0/0000:3d:00.0/nvme/nvme0";
+
+fn build_tree(name: &str) -> FakeSysfs {
+    let fake = FakeSysfs::new(name);
+    fake.add_disk("sda", "8:0", SATA_PATH, 1_000_215_216);
+    fake.write("sys/bus/pci/rescan", "");
+    for host in ["host0", "host6"] {
+        fake.write(&format!("sys/class/scsi_host/{host}/scan"), "");
+    }
+    fake.write("sys/class/nvme/nvme0/rescan_controller", "");
+    fake.set_mountinfo("");
+    fake
+}
+
+/// Plays the kernel: removing the nvme controller deletes its disk, a PCI
+/// rescan adds it back and scanning host6 finds the USB stick. Writes whose
+/// path contains `fail` are rejected.
+struct Kernel<'a> {
+    fake: &'a FakeSysfs,
+    fail: Option<&'static str>,
+    nvme_removed: bool,
+}
+
+impl<'a> Kernel<'a> {
+    fn new(fake: &'a FakeSysfs) -> Self {
+        Self {
+            fake,
+            fail: None,
+            nvme_removed: false,
+        }
+    }
+}
+
+impl OfflineActions for Kernel<'_> {
+    fn unmount(&mut self, _mount: &MountEntry) -> Result<()> {
+        unreachable!("nothing is mounted")
+    }
+
+    fn remount(&mut self, _mount: &MountEntry) -> Result<()> {
+        unreachable!("nothing is mounted")
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn flush_buffers(&mut self, _node: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_attr(&mut self, path: &Path, value: &str) -> Result<()> {
+        let path = path.to_string_lossy();
+        let rel = path.split_once("sys/").map_or(&*path, |(_, rel)| rel);
+        if self.fail.is_some_and(|f| rel.contains(f)) {
+            bail!("Failed to write {value} to /sys/{rel}");
+        }
+        if rel.ends_with("/remove") {
+            self.fake.remove_disk("nvme0n1", "259:0", NVME_PATH);
+            self.nvme_removed = true;
+        } else if rel == "bus/pci/rescan" && self.nvme_removed {
+            self.nvme_removed = false;
+            self.fake
+                .add_disk("nvme0n1", "259:0", NVME_PATH, 1_000_215_216);
+        } else if rel == "class/scsi_host/host6/scan" && !self.fake.path("sys/block/sdb").exists() {
+            self.fake.add_disk("sdb", "8:16", USB_PATH, 60_062_500);
+        }
+        Ok(())
+    }
+
+    fn power_off(&mut self, _node: &Path) -> Result<()> {
+        unreachable!("rescans do not power anything off")
+    }
+}
+
+#[test]
+fn test_scan_writes_cover_pci_scsi_and_nvme() {
+    let fake = build_tree("rescan-writes");
+    let write = |path: &str, value: &str| SysfsWrite {
+        path: path.to_string(),
+        value: value.to_string(),
+    };
+
+    assert_eq!(
+        scan_writes(&fake.sysfs_root()),
+        vec![
+            write("/sys/bus/pci/rescan", "1"),
+            write("/sys/class/scsi_host/host0/scan", "- - -"),
+            write("/sys/class/scsi_host/host6/scan", "- - -"),
+            write("/sys/class/nvme/nvme0/rescan_controller", "1"),
+        ]
+    );
+}
+
+#[test]
+fn test_rescan_reports_disks_that_reappeared() {
+    let fake = build_tree("rescan-found");
+    let mut kernel = Kernel::new(&fake);
+    let mut settled = false;
+
+    let report = rescan(&fake.sysfs_root(), &mut kernel, || settled = true).unwrap();
+
+    assert!(settled);
+    assert_eq!(report.scanned.len(), 4);
+    assert!(report.errors.is_empty());
+    let ids: Vec<&str> = report.reappeared.iter().map(|d| d.id.as_str()).collect();
+    assert_eq!(ids, vec!["sdb"]);
+}
+
+#[test]
+fn test_rescan_brings_back_a_removed_nvme_controller() {
+    let fake = build_tree("rescan-nvme");
+    fake.add_disk("nvme0n1", "259:0", NVME_PATH, 1_000_215_216);
+    let root = fake.sysfs_root();
+    let mut kernel = Kernel::new(&fake);
+
+    assert!(
+        take_offline(&root, "nvme0n1", &mut kernel)
+            .unwrap()
+            .completed
+    );
+    assert_eq!(with_offline_disks(&root, vec![]).len(), 1);
+
+    let report = rescan(&root, &mut kernel, || {}).unwrap();
+
+    let ids: Vec<&str> = report.reappeared.iter().map(|d| d.id.as_str()).collect();
+    assert_eq!(ids, vec!["nvme0n1", "sdb"]);
+    // Back for real, so no longer listed from the offline record
+    assert!(with_offline_disks(&root, vec![]).is_empty());
+    assert_eq!(enumerate_disks(&root).unwrap().len(), 3);
+}
+
+#[test]
+fn test_failed_scan_is_reported_and_the_rest_still_run() {
+    let fake = build_tree("rescan-error");
+    let mut kernel = Kernel {
+        fail: Some("host0"),
+        ..Kernel::new(&fake)
+    };
+
+    let report = rescan(&fake.sysfs_root(), &mut kernel, || {}).unwrap();
+
+    assert_eq!(report.scanned.len(), 3);
+    assert_eq!(
+        report.errors,
+        vec!["Failed to write - - - to /sys/class/scsi_host/host0/scan"]
+    );
+    assert_eq!(report.reappeared.len(), 1);
+}
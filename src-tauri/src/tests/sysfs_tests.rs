@@ -0,0 +1,575 @@
+// src/tests/sysfs_tests.rs
+// Sysfs enumeration against a fake tree
+
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::mountinfo::parse_mountinfo;
+use crate::disk_operations::statvfs::filesystem_usage;
+use crate::disk_operations::swaps::parse_swaps;
+use crate::disk_operations::sysfs::{decode_partition_flags, enumerate_disks};
+use crate::disk_operations::system_devices::target_protection;
+use crate::structs::{DiskError, DiskType, ErrorCode, ProtectionReason};
+
+const SATA_PATH: &str = "pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0";
+const USB_PATH: &str = "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+const NVME_PATH: &str = "pci0000:00/0000:00:1d.0/0000:3d:00.0/nvme/nvme0";
+
+const MOUNTINFO: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+23 22 259:1 / /boot/efi rw,relatime shared:2 - vfat /dev/nvme0n1p1 rw
+24 22 0:21 / /proc rw,nosuid shared:5 - proc proc rw
+40 22 8:17 / /media/user/My\\040Stick rw,nosuid shared:30 - vfat /dev/sdb1 rw
+";
+
+fn build_tree(name: &str) -> FakeSysfs {
+    let fake = FakeSysfs::new(name);
+
+    fake.add_disk("sda", "8:0", SATA_PATH, 1_953_525_168);
+    fake.write(
+        &format!("sys/devices/{SATA_PATH}/model"),
+        "WDC WD10EZEX-08W",
+    );
+    fake.write(&format!("sys/devices/{SATA_PATH}/state"), "running");
+    fake.set_disk_attr("sda", "queue/rotational", "1");
+    fake.add_partition("sda", "sda1", 1, "8:1", 1_953_523_120);
+    fake.set_udev(
+        "8:0",
+        &[("ID_SERIAL_SHORT", "WD-WCC6Y1234567"), ("ID_BUS", "ata")],
+    );
+
+    fake.add_disk("sdb", "8:16", USB_PATH, 60_062_500);
+    fake.write(&format!("sys/devices/{USB_PATH}/model"), "Cruzer Blade");
+    fake.set_disk_attr("sdb", "removable", "1");
+    fake.add_partition("sdb", "sdb1", 1, "8:17", 60_060_672);
+
+    fake.add_disk("nvme0n1", "259:0", NVME_PATH, 1_000_215_216);
+    fake.write(
+        &format!("sys/devices/{NVME_PATH}/model"),
+        "Samsung SSD 980 PRO 1TB",
+    );
+    fake.write(&format!("sys/devices/{NVME_PATH}/serial"), "S5GXNF0R123456");
+    fake.set_disk_attr("nvme0n1", "queue/rotational", "0");
+    fake.add_partition("nvme0n1", "nvme0n1p1", 1, "259:1", 1_048_576);
+    fake.add_partition("nvme0n1", "nvme0n1p2", 2, "259:2", 999_164_559);
+
+    fake.add_virtual("loop0", "7:0", 131_072);
+    fake.add_virtual("dm-0", "253:0", 999_164_559);
+
+    fake.set_mountinfo(MOUNTINFO);
+    fake
+}
+
+#[test]
+fn test_enumerate_skips_virtual_devices() {
+    let fake = build_tree("skip-virtual");
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let ids: Vec<&str> = disks.iter().map(|d| d.id.as_str()).collect();
+    assert_eq!(ids, vec!["nvme0n1", "sda", "sdb"]);
+}
+
+#[test]
+fn test_enumerate_skips_optical_drives() {
+    let fake = build_tree("skip-optical");
+    let rom_path = "pci0000:00/0000:00:17.0/ata2/host1/target1:0:0/1:0:0:0";
+    fake.add_disk("sr0", "11:0", rom_path, 2_097_151);
+    fake.write(&format!("sys/devices/{rom_path}/type"), "5");
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    assert!(disks.iter().all(|d| d.id != "sr0"));
+}
+
+#[test]
+fn test_enumerate_disk_attributes() {
+    let fake = build_tree("attributes");
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    assert_eq!(sda.model, "WDC WD10EZEX-08W");
+    assert_eq!(sda.size_bytes, 1_953_525_168 * 512);
+    assert_eq!(sda.serial_number.as_deref(), Some("WD-WCC6Y1234567"));
+    assert_eq!(sda.disk_type, DiskType::HDD);
+    assert!(sda.is_online);
+    assert!(!sda.is_system_disk);
+
+    let sdb = disks.iter().find(|d| d.id == "sdb").unwrap();
+    assert_eq!(sdb.disk_type, DiskType::USBFlash);
+
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    assert_eq!(nvme.disk_type, DiskType::NVMe);
+    assert_eq!(nvme.serial_number.as_deref(), Some("S5GXNF0R123456"));
+}
+
+#[test]
+fn test_enumerate_partitions_and_mounts() {
+    let fake = build_tree("partitions");
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    assert!(nvme.is_system_disk);
+    assert_eq!(nvme.partitions.len(), 2);
+    assert_eq!(nvme.partitions[0].partition_id, "nvme0n1p1");
+    assert_eq!(nvme.partitions[0].mount_points[0].path, "/boot/efi");
+    assert_eq!(nvme.partitions[0].mount_points[0].fs_type, "vfat");
+    assert_eq!(nvme.partitions[1].partition_number, 2);
+    assert_eq!(nvme.partitions[1].mount_points[0].path, "/");
+    assert!(nvme.partitions.iter().all(|p| p.drive_letter.is_empty()));
+
+    let sdb = disks.iter().find(|d| d.id == "sdb").unwrap();
+    assert_eq!(
+        sdb.partitions[0].mount_points[0].path,
+        "/media/user/My Stick"
+    );
+    assert_eq!(sdb.partitions[0].size_bytes, 60_060_672 * 512);
+}
+
+#[test]
+fn test_enumerate_multiple_mounts_of_one_partition() {
+    let fake = build_tree("multi-mount");
+    fake.set_mountinfo(
+        "\
+22 1 259:2 /@ / rw,relatime shared:1 - btrfs /dev/nvme0n1p2 rw,subvol=/@
+23 22 259:2 /@home /home rw,relatime shared:2 - btrfs /dev/nvme0n1p2 rw,subvol=/@home
+24 22 259:2 /@/srv/www /var/www ro,nosuid,relatime shared:3 - btrfs /dev/nvme0n1p2 rw,subvol=/@
+25 22 8:1 / /mnt/archive rw,relatime shared:4 - ext4 /dev/sda1 ro
+",
+    );
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    let mounts = &nvme.partitions[1].mount_points;
+    let paths: Vec<&str> = mounts.iter().map(|m| m.path.as_str()).collect();
+    assert_eq!(paths, vec!["/", "/home", "/var/www"]);
+    assert_eq!(mounts[1].root, "/@home");
+    assert_eq!(mounts[2].root, "/@/srv/www");
+    assert!(!mounts[0].read_only);
+    assert!(mounts[2].read_only); // per-mount ro
+    assert_eq!(mounts[2].options, vec!["ro", "nosuid", "relatime"]);
+
+    // Read-only at the superblock level
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    assert!(sda.partitions[0].mount_points[0].read_only);
+}
+
+#[test]
+fn test_enumerate_offline_disk() {
+    let fake = build_tree("offline");
+    fake.write(&format!("sys/devices/{SATA_PATH}/state"), "offline");
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    assert!(!sda.is_online);
+}
+
+#[test]
+fn test_enumerate_without_mountinfo() {
+    let fake = build_tree("no-mountinfo");
+    std::fs::remove_file(fake.path("proc/self/mountinfo")).unwrap();
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    assert_eq!(disks.len(), 3);
+    assert!(disks.iter().all(|d| !d.is_system_disk));
+}
+
+#[test]
+fn test_enumerate_missing_sysfs_fails() {
+    let fake = FakeSysfs::new("missing");
+    std::fs::remove_dir_all(fake.path("sys")).unwrap();
+    assert!(enumerate_disks(&fake.sysfs_root()).is_err());
+}
+
+#[test]
+fn test_parse_mountinfo() {
+    let mounts = parse_mountinfo(MOUNTINFO);
+    assert_eq!(mounts.len(), 4);
+    assert_eq!(mounts[0].dev(), "259:2");
+    assert_eq!(mounts[0].mount_point, "/");
+    assert_eq!(mounts[0].fs_type, "ext4");
+    assert_eq!(mounts[3].mount_point, "/media/user/My Stick");
+    assert_eq!(mounts[3].source, "/dev/sdb1");
+    assert_eq!(mounts[3].options, vec!["rw", "nosuid"]);
+    assert_eq!(mounts[3].super_options, vec!["rw"]);
+}
+
+#[test]
+fn test_parse_mountinfo_skips_malformed_lines() {
+    let mounts = parse_mountinfo("garbage\n22 1 8:1 / /mnt rw - ext4 /dev/sda1 rw\n");
+    assert_eq!(mounts.len(), 1);
+    assert_eq!(mounts[0].mount_point, "/mnt");
+}
+
+#[test]
+fn test_mounted_partition_usage() {
+    let fake = build_tree("usage");
+    // Give the USB stick's mount point a real directory to statvfs
+    std::fs::create_dir_all(fake.path("media/user/My Stick")).unwrap();
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sdb = disks.iter().find(|d| d.id == "sdb").unwrap();
+    let usage = sdb.partitions[0].usage.as_ref().unwrap();
+    assert_eq!(sdb.partitions[0].used_bytes, Some(usage.used_bytes));
+    assert!(sdb.usage_percentage.is_some());
+
+    // Unmounted partition, and a mount point that doesn't exist in the tree
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    assert!(sda.partitions[0].usage.is_none());
+    assert!(sda.partitions[0].used_bytes.is_none());
+    assert!(sda.usage_percentage.is_none());
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    assert!(nvme.partitions[0].usage.is_none());
+}
+
+#[test]
+fn test_filesystem_usage() {
+    let usage = filesystem_usage(&std::env::temp_dir()).unwrap();
+    assert!(usage.total_bytes > 0);
+    assert!(usage.used_bytes <= usage.total_bytes);
+    assert!(usage.available_bytes <= usage.free_bytes);
+    assert_eq!(
+        usage.reserved_bytes,
+        usage.free_bytes - usage.available_bytes
+    );
+    assert_eq!(usage.used_inodes + usage.free_inodes, usage.total_inodes);
+
+    assert!(filesystem_usage(std::path::Path::new("/nonexistent/mount")).is_none());
+}
+
+#[test]
+fn test_partition_metadata_from_udev() {
+    let fake = build_tree("metadata");
+    fake.set_udev(
+        "259:1",
+        &[
+            ("ID_FS_TYPE", "vfat"),
+            ("ID_FS_LABEL", "EFI_System"),
+            ("ID_FS_LABEL_ENC", "EFI\\x20System"),
+            ("ID_FS_UUID", "7A3B-1C2D"),
+            ("ID_PART_ENTRY_SCHEME", "gpt"),
+            ("ID_PART_ENTRY_UUID", "0b3c5f2e-7d1a-4e8b-9c6f-2a1d3e4f5a6b"),
+            ("ID_PART_ENTRY_TYPE", "c12a7328-f81f-11d2-ba4b-00a0c93ec93b"),
+            ("ID_PART_ENTRY_FLAGS", "0x8000000000000001"),
+        ],
+    );
+    fake.set_udev(
+        "8:1",
+        &[
+            ("ID_FS_TYPE", "ntfs"),
+            ("ID_FS_LABEL", "Data"),
+            ("ID_PART_ENTRY_SCHEME", "dos"),
+            ("ID_PART_ENTRY_UUID", "a1b2c3d4-01"),
+            ("ID_PART_ENTRY_TYPE", "0x7"),
+            ("ID_PART_ENTRY_FLAGS", "0x80"),
+        ],
+    );
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let esp = &disks.iter().find(|d| d.id == "nvme0n1").unwrap().partitions[0];
+    assert_eq!(esp.fs_type.as_deref(), Some("vfat"));
+    assert_eq!(esp.label.as_deref(), Some("EFI System"));
+    assert_eq!(esp.uuid.as_deref(), Some("7A3B-1C2D"));
+    assert_eq!(
+        esp.part_uuid.as_deref(),
+        Some("0b3c5f2e-7d1a-4e8b-9c6f-2a1d3e4f5a6b")
+    );
+    assert_eq!(
+        esp.part_type.as_deref(),
+        Some("c12a7328-f81f-11d2-ba4b-00a0c93ec93b")
+    );
+    assert_eq!(esp.part_flags, vec!["required", "no-automount"]);
+
+    let data = &disks.iter().find(|d| d.id == "sda").unwrap().partitions[0];
+    assert_eq!(data.label.as_deref(), Some("Data"));
+    assert_eq!(data.part_type.as_deref(), Some("0x7"));
+    assert_eq!(data.part_flags, vec!["boot"]);
+
+    // No udev record: the type comes from the mount, the rest is unknown
+    let stick = &disks.iter().find(|d| d.id == "sdb").unwrap().partitions[0];
+    assert_eq!(stick.fs_type.as_deref(), Some("vfat"));
+    assert_eq!(stick.uuid, None);
+    assert!(stick.part_flags.is_empty());
+}
+
+#[test]
+fn test_decode_partition_flags() {
+    assert_eq!(
+        decode_partition_flags(Some("gpt"), "0xd000000000000004"),
+        vec![
+            "legacy-bios-bootable",
+            "read-only",
+            "hidden",
+            "no-automount"
+        ]
+    );
+    assert!(decode_partition_flags(Some("gpt"), "0x0").is_empty());
+    assert_eq!(decode_partition_flags(Some("dos"), "0x80"), vec!["boot"]);
+    assert!(decode_partition_flags(Some("dos"), "0x00").is_empty());
+    assert!(decode_partition_flags(None, "0x80").is_empty());
+    assert!(decode_partition_flags(Some("gpt"), "garbage").is_empty());
+}
+
+#[test]
+fn test_partition_metadata_from_probe() {
+    let fake = build_tree("probe");
+    // nvme0n1p2 has no udev record; give it an ext4 superblock
+    let mut image = vec![0u8; 4096];
+    image[1024 + 0x38..1024 + 0x3A].copy_from_slice(&0xEF53u16.to_le_bytes());
+    image[1024 + 0x60..1024 + 0x64].copy_from_slice(&0x2C2u32.to_le_bytes());
+    image[1024 + 0x78..1024 + 0x7E].copy_from_slice(b"system");
+    std::fs::create_dir_all(fake.path("dev")).unwrap();
+    std::fs::write(fake.path("dev/nvme0n1p2"), image).unwrap();
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    assert_eq!(nvme.partitions[1].fs_type.as_deref(), Some("ext4"));
+    assert_eq!(nvme.partitions[1].label.as_deref(), Some("system"));
+}
+
+#[test]
+fn test_partition_metadata_from_table() {
+    let fake = build_tree("table");
+    // sdb has 4 KiB sectors and no udev records; sdb5 is a logical
+    // partition whose EBR is only found at LBA 384 * 4096
+    fake.set_disk_attr("sdb", "queue/logical_block_size", "4096");
+    fake.add_partition("sdb", "sdb5", 5, "8:21", 400);
+    let mut image = vec![0u8; 4096 * 1024];
+    let mut entry = |sector: usize, slot: usize, boot: bool, kind: u8, lba: u32, len: u32| {
+        let offset = sector * 4096 + 446 + 16 * slot;
+        image[offset] = if boot { 0x80 } else { 0 };
+        image[offset + 4] = kind;
+        image[offset + 8..offset + 12].copy_from_slice(&lba.to_le_bytes());
+        image[offset + 12..offset + 16].copy_from_slice(&len.to_le_bytes());
+        image[sector * 4096 + 510..sector * 4096 + 512].copy_from_slice(&[0x55, 0xAA]);
+    };
+    entry(0, 0, true, 0x0c, 256, 128);
+    entry(0, 1, false, 0x05, 384, 256);
+    entry(384, 0, false, 0x83, 8, 50);
+    image[440..444].copy_from_slice(&0xA1B2_C3D4u32.to_le_bytes());
+    std::fs::create_dir_all(fake.path("dev")).unwrap();
+    std::fs::write(fake.path("dev/sdb"), image).unwrap();
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sdb = disks.iter().find(|d| d.id == "sdb").unwrap();
+    let sdb1 = &sdb.partitions[0];
+    assert_eq!(sdb1.part_type.as_deref(), Some("0x0c"));
+    assert_eq!(sdb1.part_uuid.as_deref(), Some("a1b2c3d4-01"));
+    assert_eq!(sdb1.part_flags, vec!["boot"]);
+    let sdb5 = &sdb.partitions[1];
+    assert_eq!(sdb5.partition_number, 5);
+    assert_eq!(sdb5.part_type.as_deref(), Some("0x83"));
+    assert_eq!(sdb5.part_uuid.as_deref(), Some("a1b2c3d4-05"));
+    assert!(sdb5.part_flags.is_empty());
+}
+
+#[test]
+fn test_protection_reasons() {
+    let fake = build_tree("protection");
+    fake.set_swaps(
+        "Filename\tType\tSize\tUsed\tPriority\n\
+         /dev/sda1                               partition\t8388604\t0\t-2\n",
+    );
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let find = |id: &str| disks.iter().find(|d| d.id == id).unwrap();
+    assert_eq!(
+        find("nvme0n1").protection,
+        vec![ProtectionReason::RootFilesystem, ProtectionReason::Boot]
+    );
+    assert_eq!(find("sda").protection, vec![ProtectionReason::Swap]);
+    assert!(find("sda").is_system_disk);
+    assert!(find("sdb").protection.is_empty());
+}
+
+#[test]
+fn test_swap_file_protects_its_filesystem() {
+    let fake = build_tree("swapfile");
+    fake.set_swaps(
+        "Filename\tType\tSize\tUsed\tPriority\n\
+         /media/user/My\\040Stick/swap.img       file\t2097148\t0\t-3\n",
+    );
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sdb = disks.iter().find(|d| d.id == "sdb").unwrap();
+    assert_eq!(sdb.protection, vec![ProtectionReason::Swap]);
+}
+
+#[test]
+fn test_btrfs_root_is_found_through_mount_source() {
+    let fake = build_tree("btrfs-root");
+    // btrfs reports an anonymous device number in mountinfo
+    fake.set_mountinfo("22 1 0:31 /@ / rw,relatime shared:1 - btrfs /dev/sda1 rw,subvol=/@");
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    assert_eq!(sda.protection, vec![ProtectionReason::RootFilesystem]);
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    assert!(!nvme.is_system_disk);
+}
+
+#[test]
+fn test_parse_swaps() {
+    let swaps = parse_swaps(
+        "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n\
+         /dev/dm-1                               partition\t8388604\t\t0\t\t-2\n\
+         /swap\\040file                          file\t\t2097148\t\t0\t\t-3\n",
+    );
+    assert_eq!(swaps.len(), 2);
+    assert_eq!(swaps[0].path, "/dev/dm-1");
+    assert!(swaps[0].is_partition);
+    assert_eq!(swaps[1].path, "/swap file");
+    assert!(!swaps[1].is_partition);
+}
+
+#[test]
+fn test_root_on_lvm_on_luks_protects_the_disk() {
+    let fake = build_tree("lvm-luks");
+    // sda1 -> dm-0 (LUKS) -> dm-1 (LVM root), swap on dm-2 from the same VG
+    fake.add_virtual("dm-1", "253:1", 900_000_000);
+    fake.add_virtual("dm-2", "253:2", 16_000_000);
+    fake.add_slave("dm-0", "sda1");
+    fake.add_slave("dm-1", "dm-0");
+    fake.add_slave("dm-2", "dm-0");
+    fake.set_mountinfo(
+        "22 1 253:1 / / rw,relatime shared:1 - ext4 /dev/mapper/vg-root rw\n\
+         23 22 259:1 / /boot/efi rw,relatime shared:2 - vfat /dev/nvme0n1p1 rw",
+    );
+    fake.set_swaps(
+        "Filename\tType\tSize\tUsed\tPriority\n\
+         /dev/dm-2                               partition\t8388604\t0\t-2\n",
+    );
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    assert_eq!(
+        sda.protection,
+        vec![
+            ProtectionReason::RootFilesystem,
+            ProtectionReason::Swap,
+            ProtectionReason::RootStack
+        ]
+    );
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    assert_eq!(nvme.protection, vec![ProtectionReason::Boot]);
+    assert!(disks
+        .iter()
+        .find(|d| d.id == "sdb")
+        .unwrap()
+        .protection
+        .is_empty());
+}
+
+#[test]
+fn test_target_on_a_stack_is_protected_through_its_device() {
+    let fake = build_tree("lvm-target");
+    fake.add_virtual("dm-1", "253:1", 900_000_000);
+    fake.add_virtual("dm-3", "253:3", 50_000_000);
+    fake.add_slave("dm-0", "sda1");
+    fake.add_slave("dm-1", "dm-0");
+    fake.add_slave("dm-3", "sdb1");
+    fake.set_mountinfo(
+        "22 1 253:1 / / rw,relatime shared:1 - ext4 /dev/mapper/vg-root rw\n\
+         30 22 253:3 / /srv/data rw,relatime shared:9 - ext4 /dev/mapper/data rw\n\
+         31 22 0:40 / /run/user/1000 rw,nosuid shared:10 - tmpfs tmpfs rw",
+    );
+    let root = fake.sysfs_root();
+
+    assert_eq!(
+        target_protection(&root, "/").unwrap(),
+        vec![
+            ProtectionReason::RootFilesystem,
+            ProtectionReason::RootStack
+        ]
+    );
+    assert!(target_protection(&root, "/srv/data").unwrap().is_empty());
+    assert!(target_protection(&root, "/run/user/1000")
+        .unwrap()
+        .is_empty());
+    let err = DiskError::from(target_protection(&root, "/mnt/gone").unwrap_err());
+    assert_eq!(err.code, ErrorCode::NotMounted);
+}
+
+#[test]
+fn test_root_on_partitioned_mdraid_protects_every_member() {
+    let fake = build_tree("mdraid");
+    fake.add_virtual("md0", "9:0", 60_000_000);
+    fake.add_partition("md0", "md0p1", 1, "259:8", 59_000_000);
+    fake.add_slave("md0", "sda1");
+    fake.add_slave("md0", "sdb1");
+    fake.set_mountinfo("22 1 259:8 / / rw,relatime shared:1 - ext4 /dev/md0p1 rw");
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    for id in ["sda", "sdb"] {
+        let disk = disks.iter().find(|d| d.id == id).unwrap();
+        assert!(disk.is_system_disk, "{id}");
+        assert!(disk.protection.contains(&ProtectionReason::RootStack));
+    }
+    let nvme = disks.iter().find(|d| d.id == "nvme0n1").unwrap();
+    assert!(!nvme.is_system_disk);
+}
+
+#[test]
+fn test_plain_root_partition_is_not_a_stack() {
+    let fake = build_tree("plain-root");
+    fake.set_mountinfo("22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw");
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    assert_eq!(sda.protection, vec![ProtectionReason::RootFilesystem]);
+}
+
+#[test]
+fn test_btrfs_mount_points_are_matched_by_source() {
+    let fake = build_tree("btrfs-mounts");
+    fake.set_mountinfo(
+        "22 1 0:31 /@ / rw,relatime shared:1 - btrfs /dev/sda1 rw,subvol=/@\n\
+         23 22 0:31 /@home /home rw,relatime shared:2 - btrfs /dev/sda1 rw,subvol=/@home",
+    );
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap();
+    let paths: Vec<&str> = sda.partitions[0]
+        .mount_points
+        .iter()
+        .map(|m| m.path.as_str())
+        .collect();
+    assert_eq!(paths, vec!["/", "/home"]);
+}
+
+#[test]
+fn test_capabilities() {
+    let fake = build_tree("capabilities");
+    fake.set_disk_attr("sda", "queue/discard_max_bytes", "0");
+    fake.set_disk_attr("nvme0n1", "queue/discard_max_bytes", "2199023255040");
+    fake.set_disk_attr("sdb", "ro", "1");
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let find = |id: &str| disks.iter().find(|d| d.id == id).unwrap().capabilities;
+
+    let sda = find("sda");
+    assert!(sda.can_offline);
+    assert!(!sda.can_online);
+    assert!(!sda.can_eject);
+    assert!(sda.can_spin_down);
+    assert!(sda.supports_smart);
+    assert!(!sda.supports_trim);
+
+    let sdb = find("sdb");
+    assert!(sdb.can_eject);
+    assert!(sdb.can_power_off);
+    assert!(!sdb.supports_smart);
+    assert!(sdb.is_read_only);
+
+    let nvme = find("nvme0n1");
+    assert!(nvme.can_offline, "PCI remove is available");
+    assert!(!nvme.can_spin_down);
+    assert!(nvme.supports_smart);
+    assert!(nvme.supports_trim);
+}
+
+#[test]
+fn test_offline_disk_can_only_come_online() {
+    let fake = build_tree("capabilities-offline");
+    fake.write(&format!("sys/devices/{SATA_PATH}/state"), "offline");
+
+    let disks = enumerate_disks(&fake.sysfs_root()).unwrap();
+    let sda = disks.iter().find(|d| d.id == "sda").unwrap().capabilities;
+    assert!(!sda.can_offline);
+    assert!(sda.can_online);
+}
@@ -0,0 +1,184 @@
+// src/tests/udisks_tests.rs
+// The UDisks2 client against a mock service on a private bus
+use super::mock_udisks::{MockUDisks, PrivateBus};
+use crate::disk_operations::udisks::{
+    block_object_path, escape, unescape, UDisksClient, UDisksError,
+};
+use crate::structs::{UDisksChange, UDisksEvent};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// A bus with the mock service holding a USB stick: drive Kingston, disk
+/// sdb, partition sdb1 with a filesystem. None without dbus-daemon.
+fn start(name: &str) -> Option<(PrivateBus, MockUDisks, UDisksClient)> {
+    let bus = PrivateBus::start(name)?;
+    let mock = MockUDisks::start(&bus);
+    mock.add_drive("Kingston");
+    mock.add_block("sdb", Some("Kingston"), false);
+    mock.add_block("sdb1", Some("Kingston"), true);
+    let client = UDisksClient::with_connection(bus.connect());
+    Some((bus, mock, client))
+}
+
+#[test]
+fn test_escape_object_path_names() {
+    assert_eq!(escape("sdb1"), "sdb1");
+    assert_eq!(escape("dm-0"), "dm_2d0");
+    assert_eq!(escape("md_home"), "md_5fhome");
+    assert_eq!(unescape("dm_2d0"), "dm-0");
+    assert_eq!(unescape("md_5fhome"), "md_home");
+    assert_eq!(unescape("odd_zz"), "odd_zz");
+    assert_eq!(
+        block_object_path("/dev/nvme0n1p1"),
+        "/org/freedesktop/UDisks2/block_devices/nvme0n1p1"
+    );
+}
+
+#[test]
+fn test_mount_passes_type_and_options() {
+    let Some((_bus, mock, client)) = start("mount") else {
+        return;
+    };
+    let options = vec!["ro".to_string(), "nosuid".to_string()];
+
+    let mount_point = client.mount("/dev/sdb1", Some("vfat"), &options).unwrap();
+
+    assert_eq!(mount_point, "/media/user/sdb1");
+    assert_eq!(
+        mock.calls(),
+        vec!["Mount sdb1 fstype=vfat options=ro,nosuid"]
+    );
+}
+
+#[test]
+fn test_mount_errors_are_typed() {
+    let Some((_bus, mock, client)) = start("mount-errors") else {
+        return;
+    };
+    client.mount("/dev/sdb1", None, &[]).unwrap();
+
+    assert_eq!(
+        client.mount("/dev/sdb1", None, &[]),
+        Err(UDisksError::AlreadyMounted(
+            "Device /dev/sdb1 is already mounted".to_string()
+        ))
+    );
+    assert!(matches!(
+        client.mount("/dev/sdb", None, &[]),
+        Err(UDisksError::NoSuchDevice(_))
+    ));
+    assert!(matches!(
+        client.mount("/dev/sdz1", None, &[]),
+        Err(UDisksError::NoSuchDevice(_))
+    ));
+
+    mock.state.lock().unwrap().deny = true;
+    assert_eq!(
+        client
+            .mount("/dev/sdb1", None, &[])
+            .unwrap_err()
+            .to_string(),
+        "Not authorized to perform operation"
+    );
+}
+
+#[test]
+fn test_unmount_errors_are_typed() {
+    let Some((_bus, mock, client)) = start("unmount") else {
+        return;
+    };
+
+    assert!(matches!(
+        client.unmount("/dev/sdb1"),
+        Err(UDisksError::NotMounted(_))
+    ));
+
+    client.mount("/dev/sdb1", None, &[]).unwrap();
+    mock.state.lock().unwrap().busy.insert("sdb1".to_string());
+    assert_eq!(
+        client.unmount("/dev/sdb1"),
+        Err(UDisksError::DeviceBusy(
+            "Error unmounting /dev/sdb1: target is busy".to_string()
+        ))
+    );
+
+    mock.state.lock().unwrap().busy.clear();
+    client.unmount("/dev/sdb1").unwrap();
+    assert_eq!(
+        mock.calls(),
+        vec!["Unmount sdb1", "Mount sdb1", "Unmount sdb1", "Unmount sdb1"]
+    );
+}
+
+#[test]
+fn test_power_off_goes_to_the_drive() {
+    let Some((_bus, mock, client)) = start("power-off") else {
+        return;
+    };
+    mock.add_block("loop0", None, true);
+
+    client.power_off("/dev/sdb").unwrap();
+    assert_eq!(mock.calls(), vec!["PowerOff Kingston"]);
+
+    assert_eq!(
+        client.power_off("/dev/loop0"),
+        Err(UDisksError::NoSuchDevice(
+            "/dev/loop0 has no drive to power off".to_string()
+        ))
+    );
+
+    mock.state.lock().unwrap().deny = true;
+    assert!(matches!(
+        client.power_off("/dev/sdb"),
+        Err(UDisksError::NotAuthorized(_))
+    ));
+}
+
+#[test]
+fn test_missing_daemon_is_unavailable() {
+    let Some(bus) = PrivateBus::start("no-daemon") else {
+        return;
+    };
+    let client = UDisksClient::with_connection(bus.connect());
+
+    assert!(matches!(
+        client.unmount("/dev/sdb1"),
+        Err(UDisksError::Unavailable(_))
+    ));
+}
+
+#[test]
+fn test_events_report_added_and_removed_devices() {
+    let Some((_bus, mock, client)) = start("events") else {
+        return;
+    };
+    let (send, receive) = mpsc::channel();
+    let events = client.events().unwrap();
+    std::thread::spawn(move || {
+        for event in events {
+            if send.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    mock.add_block("dm_2d0", None, false);
+    mock.remove_block("dm_2d0");
+
+    let next = || receive.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(
+        next(),
+        UDisksEvent {
+            change: UDisksChange::Added,
+            object: "/org/freedesktop/UDisks2/block_devices/dm_2d0".to_string(),
+            device: Some("dm-0".to_string()),
+            interfaces: vec!["org.freedesktop.UDisks2.Block".to_string()],
+        }
+    );
+    let removed = next();
+    assert_eq!(removed.change, UDisksChange::Removed);
+    assert_eq!(removed.device.as_deref(), Some("dm-0"));
+    assert!(removed
+        .interfaces
+        .contains(&"org.freedesktop.UDisks2.Block".to_string()));
+}
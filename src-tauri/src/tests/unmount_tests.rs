@@ -0,0 +1,184 @@
+// src/tests/unmount_tests.rs
+// Unmount modes and unmounting a whole disk, with recorded side effects
+
+use super::fake_sysfs::FakeSysfs;
+use crate::disk_operations::mountinfo::MountEntry;
+use crate::disk_operations::unmount::{unmount_disk, unmount_mount_point, UnmountActions};
+use crate::structs::{OfflineStep, StepStatus, UnmountMode};
+use anyhow::{bail, Result};
+
+const USB_PATH: &str = "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+
+// sdb1 holds a bind mount of one of its directories, sdb2 sits inside it
+const MOUNTINFO: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+40 22 8:17 / /media/stick rw,nosuid,nodev shared:30 - vfat /dev/sdb1 rw,fmask=0022
+41 40 8:18 / /media/stick/data rw,noexec shared:31 - ext4 /dev/sdb2 rw
+42 22 8:17 /photos /srv/photos rw shared:30 - vfat /dev/sdb1 rw,fmask=0022
+";
+
+fn build_tree(name: &str) -> FakeSysfs {
+    let fake = FakeSysfs::new(name);
+    fake.add_disk("sdb", "8:16", USB_PATH, 60_062_500);
+    fake.add_partition("sdb", "sdb1", 1, "8:17", 20_000_000);
+    fake.add_partition("sdb", "sdb2", 2, "8:18", 40_000_000);
+    fake.set_mountinfo(MOUNTINFO);
+    fake
+}
+
+/// Records every call; the one whose description starts with `fail` fails
+#[derive(Default)]
+struct Recorder {
+    log: Vec<String>,
+    fail: Option<&'static str>,
+}
+
+impl Recorder {
+    fn act(&mut self, action: String) -> Result<()> {
+        let failed = self.fail.is_some_and(|f| action.starts_with(f));
+        self.log.push(action);
+        if failed {
+            bail!("{} failed", self.log.last().unwrap());
+        }
+        Ok(())
+    }
+}
+
+impl UnmountActions for Recorder {
+    fn udisks_unmount(&mut self, device: &str) -> Result<()> {
+        self.act(format!("udisks {device}"))
+    }
+
+    fn umount(&mut self, mount_point: &str, flags: libc::c_int) -> Result<()> {
+        let flags = match flags {
+            0 => "",
+            libc::MNT_DETACH => " detach",
+            libc::MNT_FORCE => " force",
+            _ => " ?",
+        };
+        self.act(format!("umount {mount_point}{flags}"))
+    }
+
+    fn remount_read_only(&mut self, mount: &MountEntry) -> Result<()> {
+        self.act(format!("remount-ro {}", mount.mount_point))
+    }
+}
+
+fn unmount_one(name: &str, mount_point: &str, mode: UnmountMode) -> Vec<String> {
+    let fake = build_tree(name);
+    let mut recorder = Recorder::default();
+    let results =
+        unmount_mount_point(&fake.sysfs_root(), mount_point, mode, &mut recorder).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].target, mount_point);
+    assert_eq!(results[0].status, StepStatus::Done);
+    recorder.log
+}
+
+#[test]
+fn test_normal_unmount_goes_through_udisks() {
+    let log = unmount_one("unmount-udisks", "/media/stick/data", UnmountMode::Normal);
+    assert_eq!(log, vec!["udisks /dev/sdb2"]);
+}
+
+#[test]
+fn test_bind_mounted_filesystem_is_unmounted_by_path() {
+    // udisks would take sdb1 off both /media/stick and /srv/photos
+    let log = unmount_one("unmount-bind", "/srv/photos", UnmountMode::Normal);
+    assert_eq!(log, vec!["umount /srv/photos"]);
+}
+
+#[test]
+fn test_lazy_and_forced_unmounts() {
+    let log = unmount_one("unmount-lazy", "/media/stick/data", UnmountMode::Lazy);
+    assert_eq!(log, vec!["umount /media/stick/data detach"]);
+    let log = unmount_one("unmount-force", "/media/stick/data", UnmountMode::Force);
+    assert_eq!(log, vec!["umount /media/stick/data force"]);
+}
+
+#[test]
+fn test_remount_read_only_is_its_own_step() {
+    let fake = build_tree("unmount-ro");
+    let mut recorder = Recorder::default();
+
+    let results = unmount_mount_point(
+        &fake.sysfs_root(),
+        "/media/stick",
+        UnmountMode::RemountReadOnly,
+        &mut recorder,
+    )
+    .unwrap();
+
+    assert_eq!(recorder.log, vec!["remount-ro /media/stick"]);
+    assert_eq!(results[0].step, OfflineStep::RemountReadOnly);
+}
+
+#[test]
+fn test_unknown_mount_point_is_an_error() {
+    let fake = build_tree("unmount-unknown");
+    let mut recorder = Recorder::default();
+
+    let err = unmount_mount_point(
+        &fake.sysfs_root(),
+        "/media/nothing",
+        UnmountMode::Normal,
+        &mut recorder,
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "/media/nothing is not a mount point");
+}
+
+#[test]
+fn test_unmount_disk_goes_deepest_first() {
+    let fake = build_tree("unmount-disk");
+    let mut recorder = Recorder::default();
+
+    let results =
+        unmount_disk(&fake.sysfs_root(), "sdb", UnmountMode::Lazy, &mut recorder).unwrap();
+
+    assert_eq!(
+        recorder.log,
+        vec![
+            "umount /media/stick/data detach",
+            "umount /srv/photos detach",
+            "umount /media/stick detach",
+        ]
+    );
+    assert!(results.iter().all(|r| r.status == StepStatus::Done));
+}
+
+#[test]
+fn test_unmount_disk_skips_what_a_failed_mount_sits_on() {
+    let fake = build_tree("unmount-disk-busy");
+    let mut recorder = Recorder {
+        fail: Some("udisks /dev/sdb2"),
+        ..Recorder::default()
+    };
+
+    let results = unmount_disk(
+        &fake.sysfs_root(),
+        "sdb",
+        UnmountMode::Normal,
+        &mut recorder,
+    )
+    .unwrap();
+
+    // /media/stick is never tried, /srv/photos is independent of it
+    assert_eq!(recorder.log, vec!["udisks /dev/sdb2", "umount /srv/photos"]);
+    let outcome: Vec<(&str, StepStatus)> = results
+        .iter()
+        .map(|r| (r.target.as_str(), r.status))
+        .collect();
+    assert_eq!(
+        outcome,
+        vec![
+            ("/media/stick/data", StepStatus::Failed),
+            ("/srv/photos", StepStatus::Done),
+            ("/media/stick", StepStatus::Failed),
+        ]
+    );
+    assert_eq!(
+        results[2].message.as_deref(),
+        Some("Skipped, /media/stick/data is still mounted")
+    );
+}
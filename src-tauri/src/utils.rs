@@ -1,5 +1,4 @@
-use std::mem;
-use std::ptr;
+use crate::structs::PartitionInfo;
 
 #[allow(clippy::borrow_as_ptr)]
 #[allow(clippy::ptr_as_ptr)]
@@ -8,6 +7,7 @@ use std::ptr;
 pub fn is_elevated() -> bool {
     #[cfg(windows)]
     {
+        use std::{mem, ptr};
         use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
         use winapi::um::securitybaseapi::GetTokenInformation;
         use winapi::um::winnt::{TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
@@ -41,3 +41,25 @@ pub fn is_elevated() -> bool {
         false
     }
 }
+
+/// Disk usage in percent, from the partitions whose used space is known.
+/// Partitions with unknown usage are left out of both sides of the ratio.
+#[allow(clippy::cast_precision_loss)]
+pub fn usage_percentage(partitions: &[PartitionInfo]) -> Option<f64> {
+    let mut total_used: u64 = 0;
+    let mut total_size: u64 = 0;
+    let mut has_usage = false;
+    for p in partitions {
+        if let Some(used) = p.used_bytes {
+            total_used += used;
+            total_size += p.size_bytes;
+            has_usage = true;
+        }
+    }
+
+    if has_usage && total_size > 0 {
+        Some((total_used as f64 / total_size as f64) * 100.0)
+    } else {
+        None
+    }
+}
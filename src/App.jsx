@@ -1,6 +1,7 @@
 import { createSignal, onMount, onCleanup, createEffect } from "solid-js";
 import { invoke } from "@tauri-apps/api/core";
-import { Moon, Sun, ZoomIn, ZoomOut, RotateCw } from "lucide-solid";
+import { listen } from "@tauri-apps/api/event";
+import { Moon, Sun, ZoomIn, ZoomOut, RotateCw, ScanSearch } from "lucide-solid";
 import Sidebar from "./components/Sidebar";
 import DiskCard from "./components/DiskCard";
 import SystemInfo from "./components/SystemInfo";
@@ -18,10 +19,12 @@ function App() {
   const [zoomLevel, setZoomLevel] = createSignal(parseFloat(localStorage.getItem("zoomLevel")) || 0.8);
   const [safeMode, setSafeMode] = createSignal(localStorage.getItem("safeMode") === "true"); // Default false
   const [autoRefresh, setAutoRefresh] = createSignal(localStorage.getItem("autoRefresh") !== "false"); // Default true
+  const [cacheTtl, setCacheTtl] = createSignal(localStorage.getItem("cacheTtl") || ""); // Seconds, "" for the default
 
   const [disks, setDisks] = createSignal([]);
   const [togglingDiskId, setTogglingDiskId] = createSignal(null);
   const [loading, setLoading] = createSignal(true);
+  const [rescanning, setRescanning] = createSignal(false);
   const [modal, setModal] = createSignal({ show: false, title: "", message: "", onConfirm: null, isDanger: false });
   const [pendingMount, setPendingMount] = createSignal(null);
   const [assignedLetters, setAssignedLetters] = createSignal(new Set());
@@ -31,6 +34,11 @@ function App() {
   createEffect(() => localStorage.setItem("zoomLevel", zoomLevel().toString()));
   createEffect(() => localStorage.setItem("safeMode", safeMode().toString()));
   createEffect(() => localStorage.setItem("autoRefresh", autoRefresh().toString()));
+  createEffect(() => {
+    localStorage.setItem("cacheTtl", cacheTtl());
+    invoke("set_cache_ttl_command", { seconds: cacheTtl() === "" ? null : Number(cacheTtl()) })
+      .catch((error) => console.error("Failed to set cache lifetime:", error));
+  });
 
   const showConfirm = (config) => {
     setModal({ ...config, show: true });
@@ -42,10 +50,11 @@ function App() {
 
   const [isAdmin, setIsAdmin] = createSignal(false);
 
-  const fetchDisks = async (silent = false) => {
+  // The backend caches the disk list; refresh bypasses the cache
+  const fetchDisks = async (silent = false, refresh = false) => {
     if (!silent) setLoading(true);
     try {
-      const result = await invoke("enumerate_disks_command");
+      const result = await invoke("enumerate_disks_command", { refresh });
       const sysInfo = await invoke("get_system_info_command"); // Fetch admin status
       setIsAdmin(sysInfo.is_admin);
 
@@ -68,18 +77,37 @@ function App() {
   };
 
 
+  // The backend refuses offline/unmount on protected disks; offer to retry
+  // with an explicit override
+  const confirmOverride = (error, retry) => {
+    showConfirm({
+      title: "Protected Disk",
+      message: `${error.message}. Continue anyway?`,
+      confirmLabel: "Override",
+      isDanger: true,
+      onConfirm: retry
+    });
+  };
+
   const toggleDisk = async (disk) => {
-    const action = async () => {
+    const action = async (overrideProtection = false) => {
       try {
         setTogglingDiskId(disk.id);
         if (disk.is_online) {
-          await invoke("set_disk_offline_command", { diskId: disk.id });
+          await invoke("set_disk_offline_command", { diskId: disk.id, overrideProtection });
         } else {
           await invoke("set_disk_online_command", { diskId: disk.id });
         }
         await fetchDisks();
       } catch (error) {
-        console.error("Failed to toggle disk:", error);
+        if (error?.code === "refused") {
+          confirmOverride(error, () => action(true));
+        } else if (error?.code === "incomplete") {
+          // Offline stopped part way and was rolled back, log each step
+          console.error(`Failed to set Disk ${disk.id} offline:`, error.message, error.details.report.steps);
+        } else {
+          console.error("Failed to toggle disk:", error);
+        }
       } finally {
         setTogglingDiskId(null);
       }
@@ -92,13 +120,63 @@ function App() {
         message: `Are you sure you want to set Disk ${disk.id} offline?`,
         confirmLabel: "Set Offline",
         isDanger: true,
-        onConfirm: action
+        onConfirm: () => action()
+      });
+    } else {
+      action();
+    }
+  };
+
+  const ejectDisk = async (diskId) => {
+    const action = async (overrideProtection = false) => {
+      try {
+        setTogglingDiskId(diskId);
+        await invoke("eject_disk_command", { diskId, overrideProtection });
+        await fetchDisks();
+      } catch (error) {
+        if (error?.code === "refused") {
+          confirmOverride(error, () => action(true));
+        } else if (error?.code === "incomplete") {
+          // Eject stopped part way, log each step and who holds the disk
+          console.error(`Failed to eject Disk ${diskId}:`, error.message, error.details.report.steps, error.details.report.blockers);
+        } else {
+          console.error("Failed to eject disk:", error);
+        }
+      } finally {
+        setTogglingDiskId(null);
+      }
+    };
+
+    if (safeMode()) {
+      showConfirm({
+        title: "Eject Disk",
+        message: `Unmount every partition on Disk ${diskId} and power it off?`,
+        confirmLabel: "Eject",
+        isDanger: false,
+        onConfirm: () => action()
       });
     } else {
       action();
     }
   };
 
+  // Look for disks that were deleted or powered off and have come back
+  const rescanStorage = async () => {
+    try {
+      setRescanning(true);
+      const report = await invoke("rescan_storage_command");
+      if (report.errors.length > 0) {
+        console.error("Some buses could not be rescanned:", report.errors);
+      }
+      // The backend logs which disks reappeared
+      await fetchDisks(true);
+    } catch (error) {
+      console.error("Failed to rescan storage:", error);
+    } finally {
+      setRescanning(false);
+    }
+  };
+
   const showMountModal = (diskId, partitionNumber) => {
     setPendingMount({ diskId, partitionNumber });
   };
@@ -107,7 +185,7 @@ function App() {
     setPendingMount(null);
   };
 
-  const mountPartition = async (letter) => {
+  const mountPartition = async (letter, options) => {
     const pending = pendingMount();
     if (!pending) return;
 
@@ -117,10 +195,11 @@ function App() {
       const assigned = await invoke("mount_partition_command", {
         diskId: pending.diskId,
         partitionNumber: pending.partitionNumber,
-        letter: letter
+        letter: letter,
+        options
       });
-      if (assigned) {
-        setAssignedLetters(prev => new Set(prev).add(assigned.toUpperCase()));
+      if (assigned.letter) {
+        setAssignedLetters(prev => new Set(prev).add(assigned.letter.toUpperCase()));
       }
       await fetchDisks();
     } catch (error) {
@@ -131,12 +210,14 @@ function App() {
   };
 
   const unmountPartition = async (diskId, driveLetter) => {
-    const action = async () => {
+    const action = async (overrideProtection = false, mode = "normal") => {
       try {
         setTogglingDiskId(diskId);
         const letter = driveLetter.substring(0, 1).toUpperCase();
         await invoke("unmount_partition_command", {
-          volumeOrLetter: driveLetter
+          volumeOrLetter: driveLetter,
+          overrideProtection,
+          mode
         });
         setAssignedLetters(prev => {
           const next = new Set(prev);
@@ -145,7 +226,25 @@ function App() {
         });
         await fetchDisks(true);
       } catch (error) {
-        console.error("Failed to unmount partition:", error);
+        if (error?.code === "refused") {
+          confirmOverride(error, () => action(true));
+        } else if (error?.code === "busy") {
+          // List who holds the filesystem and offer to detach it anyway;
+          // the kernel finishes the unmount once they let go
+          const holders = error.details.report.processes.map(
+            (p) => `${p.command} (PID ${p.pid}, ${p.user})`
+          );
+          const swap = error.details.report.swap.map((path) => `swap ${path}`);
+          showConfirm({
+            title: "Partition In Use",
+            message: `${driveLetter} is in use by ${[...holders, ...swap].join(", ")}. Detach it now and finish unmounting once they close?`,
+            confirmLabel: "Detach",
+            isDanger: true,
+            onConfirm: () => action(overrideProtection, "lazy")
+          });
+        } else {
+          console.error("Failed to unmount partition:", error);
+        }
       } finally {
         setTogglingDiskId(null);
       }
@@ -157,7 +256,40 @@ function App() {
         message: `Are you sure you want to unmount partition ${driveLetter}?`,
         confirmLabel: "Unmount",
         isDanger: false,
-        onConfirm: action
+        onConfirm: () => action()
+      });
+    } else {
+      action();
+    }
+  };
+
+  const unmountDisk = async (diskId) => {
+    const action = async (overrideProtection = false) => {
+      try {
+        setTogglingDiskId(diskId);
+        await invoke("unmount_disk_command", { diskId, overrideProtection });
+        await fetchDisks(true);
+      } catch (error) {
+        if (error?.code === "refused") {
+          confirmOverride(error, () => action(true));
+        } else if (error?.code === "partly_unmounted") {
+          console.error(error.message, error.details.results);
+          await fetchDisks(true);
+        } else {
+          console.error("Failed to unmount disk:", error);
+        }
+      } finally {
+        setTogglingDiskId(null);
+      }
+    };
+
+    if (safeMode()) {
+      showConfirm({
+        title: "Unmount All",
+        message: `Are you sure you want to unmount every partition of Disk ${diskId}?`,
+        confirmLabel: "Unmount",
+        isDanger: false,
+        onConfirm: () => action()
       });
     } else {
       action();
@@ -203,6 +335,30 @@ function App() {
       if (autoRefresh()) fetchDisks(true);
     }, 15000);
     onCleanup(() => clearInterval(interval));
+
+    // UDisks2 reports several changes per plug or mount; refresh once
+    let refresh;
+    const unlisten = listen("udisks-changed", () => {
+      clearTimeout(refresh);
+      refresh = setTimeout(() => fetchDisks(true), 500);
+    });
+    onCleanup(() => {
+      clearTimeout(refresh);
+      unlisten.then((stop) => stop());
+    });
+
+    // Hotplug events carry the disk as it is now (as it was, once removed)
+    const sortDisks = (list) => list.sort((a, b) => parseInt(a.id) - parseInt(b.id));
+    const replaceDisk = (disk) => setDisks((list) => list.map((d) => (d.id === disk.id ? disk : d)));
+    const hotplug = [
+      listen("disk-added", ({ payload }) =>
+        setDisks((list) => sortDisks([...list.filter((d) => d.id !== payload.id), payload]))
+      ),
+      listen("disk-removed", ({ payload }) => setDisks((list) => list.filter((d) => d.id !== payload.id))),
+      listen("disk-changed", ({ payload }) => replaceDisk(payload)),
+      listen("partition-mounted", ({ payload }) => replaceDisk(payload.disk)),
+    ];
+    onCleanup(() => hotplug.forEach((unlistenHotplug) => unlistenHotplug.then((stop) => stop())));
   });
 
   return (
@@ -235,9 +391,20 @@ function App() {
                 {theme() === "dark" ? <Sun size={20} /> : <Moon size={20} />}
               </button>
 
+              {isAdmin() && (
+                <button
+                  class="icon-btn"
+                  onClick={rescanStorage}
+                  disabled={rescanning() || togglingDiskId() !== null}
+                  data-tooltip="Rescan Storage"
+                >
+                  <ScanSearch size={20} class={rescanning() ? "spin" : ""} />
+                </button>
+              )}
+
               <button
                 class="refresh-btn"
-                onClick={fetchDisks}
+                onClick={() => fetchDisks(false, true)}
                 disabled={loading() || togglingDiskId() !== null}
                 data-tooltip="Refresh Disk List"
               >
@@ -257,6 +424,8 @@ function App() {
                 onToggle={() => toggleDisk(disk)}
                 onMount={(diskId, partNum) => showMountModal(diskId, partNum)}
                 onUnmount={(diskId, letter) => unmountPartition(diskId, letter)}
+                onUnmountAll={(diskId) => unmountDisk(diskId)}
+                onEject={(diskId) => ejectDisk(diskId)}
                 onOpenExplorer={openExplorer}
                 isToggling={togglingDiskId() === disk.id}
                 isAnyToggling={togglingDiskId() !== null}
@@ -277,6 +446,8 @@ function App() {
             setSafeMode={setSafeMode}
             autoRefresh={autoRefresh()}
             setAutoRefresh={setAutoRefresh}
+            cacheTtl={cacheTtl()}
+            setCacheTtl={setCacheTtl}
             onClearLogs={handleClearLogs}
           />
         )}
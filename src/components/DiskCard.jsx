@@ -52,8 +52,15 @@ function DiskCard(props) {
   };
 
   const Icon = getIcon();
-  const isUsb = () => props.disk.disk_type === "USBFlash";
-  const canEject = () => props.disk.disk_type === "USBFlash" || props.disk.disk_type === "ExtHDD";
+  const capabilities = () => props.disk.capabilities || {};
+  const canEject = () => capabilities().can_eject;
+  const mountedCount = () =>
+    (props.disk.partitions || []).filter(
+      (p) => p.drive_letter || p.mount_points?.length > 0
+    ).length;
+  // The backend only lists the direction the disk can go from its current state
+  const canToggle = () =>
+    props.disk.is_online ? capabilities().can_offline : capabilities().can_online;
 
   return (
     <div
@@ -67,7 +74,7 @@ function DiskCard(props) {
           </div>
           <span class="disk-name">Disk {props.disk.id} - {props.disk.model}</span>
         </div>
-        {!isUsb() && props.isAdmin && (
+        {canToggle() && props.isAdmin && (
           <button
             class={`status-badge ${props.disk.is_online ? "online" : "offline"}`}
             disabled={props.isToggling || props.isAnyToggling || !props.isAdmin}
@@ -90,10 +97,10 @@ function DiskCard(props) {
             )}
           </button>
         )}
-        {!isUsb() && !props.isAdmin && (
+        {!(canToggle() && props.isAdmin) && (
           <div class={`status-badge ${props.disk.is_online ? "online" : "offline"}`}
             style={{ opacity: 1, cursor: "not-allowed" }}
-            data-tooltip="Administrator privileges required">
+            data-tooltip={props.isAdmin ? "Not supported by this disk" : "Administrator privileges required"}>
             {props.disk.is_online ? "ONLINE" : "OFFLINE"}
           </div>
         )}
@@ -126,9 +133,28 @@ function DiskCard(props) {
         </div>
 
         <div class="partition-list">
-          <div class="partition-header">Partition:</div>
+          <div class="partition-header">
+            Partition:
+            {props.isAdmin && !canEject() && mountedCount() > 1 && (
+              <button
+                class="partition-btn mounted"
+                disabled={props.isAnyToggling}
+                onClick={(e) => {
+                  e.stopPropagation();
+                  props.onUnmountAll && props.onUnmountAll(props.disk.id);
+                }}
+                data-tooltip="Unmount every partition of this disk"
+              >
+                Unmount All
+              </button>
+            )}
+          </div>
           {props.disk.partitions && props.disk.partitions.length > 0 ? (
-            props.disk.partitions.map((partition) => (
+            props.disk.partitions.map((partition) => {
+              // Windows partitions have a drive letter, Linux ones mount points
+              const mountPath = partition.mount_points?.[0]?.path;
+              const unmountTarget = partition.drive_letter || mountPath;
+              return (
               <div class="partition-row" key={partition.partition_id}>
                 <span
                   class={`partition-info ${partition.drive_letter ? "link" : ""}`}
@@ -140,15 +166,21 @@ function DiskCard(props) {
                   }}
                   data-tooltip={partition.drive_letter ? "Open in Explorer" : ""}
                 >
-                  [{partition.drive_letter || "?"}:\] - {formatBytes(partition.size_bytes)}
+                  {mountPath && !partition.drive_letter
+                    ? `[${mountPath}]`
+                    : `[${partition.drive_letter || "?"}:\\]`} - {formatBytes(partition.size_bytes)}
                 </span>
-                {partition.drive_letter ? (
+                {unmountTarget ? (
                   (canEject() || props.isAdmin) && (
                     <button
                       class={`partition-btn ${canEject() ? "eject" : "mounted"}`}
                       onClick={(e) => {
                         e.stopPropagation();
-                        props.onUnmount && props.onUnmount(props.disk.id, partition.drive_letter);
+                        if (canEject()) {
+                          props.onEject && props.onEject(props.disk.id);
+                        } else {
+                          props.onUnmount && props.onUnmount(props.disk.id, unmountTarget);
+                        }
                       }}
                       data-tooltip={canEject() ? "Safely Remove (Eject)" : "Unmount Drive"}
                     >
@@ -171,7 +203,8 @@ function DiskCard(props) {
                   )
                 )}
               </div>
-            ))
+              );
+            })
           ) : (
             <div class="no-partitions">No partitions</div>
           )}
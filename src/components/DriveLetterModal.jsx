@@ -5,6 +5,7 @@ function DriveLetterModal(props) {
     const [letters, setLetters] = createSignal([]);
     const [selectedLetter, setSelectedLetter] = createSignal(null);
     const [loading, setLoading] = createSignal(true);
+    const [options, setOptions] = createSignal({});
 
     const fetchLetters = async () => {
         setLoading(true);
@@ -22,13 +23,24 @@ function DriveLetterModal(props) {
         if (props.show) {
             fetchLetters();
             setSelectedLetter(null);
+            setOptions({});
         }
     });
 
     const handleMount = () => {
-        props.onConfirm(selectedLetter());
+        props.onConfirm(selectedLetter(), options());
     };
 
+    // Linux has no drive letters; offer mount options instead
+    const usesMountPoints = () => !loading() && letters().length === 0;
+    const setOption = (name, value) => setOptions(prev => ({ ...prev, [name]: value }));
+    const flags = [
+        ["read_only", "Read-only"],
+        ["no_exec", "No execution (noexec)"],
+        ["no_suid", "Ignore setuid (nosuid)"],
+        ["no_dev", "No device files (nodev)"]
+    ];
+
     const filteredLetters = () => letters().filter(l => !props.assignedLetters.has(l));
 
     return (
@@ -36,12 +48,37 @@ function DriveLetterModal(props) {
             <div class="modal-overlay" onClick={props.onCancel}>
                 <div class="modal-container drive-letter-modal" onClick={(e) => e.stopPropagation()}>
                     <div class="modal-header">
-                        Select Drive Letter
+                        {usesMountPoints() ? "Mount Options" : "Select Drive Letter"}
                     </div>
                     <div class="modal-body">
-                        <p class="modal-description">Choose a drive letter for the partition, or use Auto to assign automatically.</p>
+                        <Show when={!usesMountPoints()}>
+                            <p class="modal-description">Choose a drive letter for the partition, or use Auto to assign automatically.</p>
+                        </Show>
 
-                        {loading() ? (
+                        {usesMountPoints() ? (
+                            <div class="mount-options">
+                                <p class="modal-description">Leave the directory empty to mount under /media.</p>
+                                <input
+                                    class="mount-target"
+                                    type="text"
+                                    placeholder="/mnt/data"
+                                    value={options().target || ""}
+                                    onInput={(e) => setOption("target", e.currentTarget.value || null)}
+                                />
+                                <For each={flags}>
+                                    {([name, label]) => (
+                                        <label class="mount-flag">
+                                            <input
+                                                type="checkbox"
+                                                checked={!!options()[name]}
+                                                onChange={(e) => setOption(name, e.currentTarget.checked)}
+                                            />
+                                            {label}
+                                        </label>
+                                    )}
+                                </For>
+                            </div>
+                        ) : loading() ? (
                             <div class="letter-loading">
                                 <div class="spinner"></div>
                                 <span>Loading available letters...</span>
@@ -76,7 +113,9 @@ function DriveLetterModal(props) {
                             onClick={handleMount}
                             disabled={loading()}
                         >
-                            Mount {selectedLetter() ? `as ${selectedLetter()}:` : '(Auto)'}
+                            {usesMountPoints()
+                                ? "Mount"
+                                : `Mount ${selectedLetter() ? `as ${selectedLetter()}:` : '(Auto)'}`}
                         </button>
                     </div>
                 </div>
@@ -71,6 +71,25 @@ function Settings(props) {
                     </div>
                     <Toggle checked={props.autoRefresh} onChange={() => props.setAutoRefresh(!props.autoRefresh)} />
                 </div>
+
+                <div class="settings-row">
+                    <div class="settings-info">
+                        <span class="setting-label">Disk List Cache</span>
+                        <span class="setting-desc">How long the disk list is reused; plugging disks in and your own actions refresh it sooner</span>
+                    </div>
+                    <select
+                        class="settings-select"
+                        value={props.cacheTtl}
+                        onChange={(e) => props.setCacheTtl(e.target.value)}
+                    >
+                        <option value="">Automatic</option>
+                        <option value="0">Off</option>
+                        <option value="10">10 seconds</option>
+                        <option value="60">1 minute</option>
+                        <option value="300">5 minutes</option>
+                        <option value="1800">30 minutes</option>
+                    </select>
+                </div>
             </section>
 
             {/* Data Management */}